@@ -0,0 +1,456 @@
+use std::{fmt, time::Duration, time::Instant};
+
+use crate::{
+    rays::ray::Ray,
+    utils::{aabb::AABB, vector::Point},
+};
+
+// based on PBRT's 3rd ed. book, sec 4.3 (BVH with the surface area heuristic)
+
+const SAH_BUCKETS: usize = 12;
+// past this depth nodes are split at the object median, which keeps the
+// traversal stack bounded for degenerate inputs
+const SAH_MAX_DEPTH: usize = 40;
+const MAX_LEAF_PRIMS: usize = 4;
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECT_COST: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BvhNode {
+    pub bb: AABB,
+    // leaf: index of the first primitive in `prim_inds`
+    // interior: index of the second child (the first one is right after this node)
+    pub offset: u32,
+    // 0 for interior nodes
+    pub prim_count: u16,
+    pub axis: u8,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.prim_count > 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BvhStats {
+    pub prim_count: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub max_leaf_prims: usize,
+    pub sah_cost: f32,
+    pub build_time: Duration,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} prims | {} nodes | {} leaves | max depth {} | max leaf prims {} | avg leaf prims {:.2} | sah cost {:.2} | {} ms",
+            self.prim_count,
+            self.node_count,
+            self.leaf_count,
+            self.max_depth,
+            self.max_leaf_prims,
+            self.prim_count as f32 / self.leaf_count.max(1) as f32,
+            self.sah_cost,
+            self.build_time.as_millis()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub prim_inds: Vec<u32>,
+    pub stats: BvhStats,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BuildPrim {
+    ind: u32,
+    bb: AABB,
+    centroid: Point,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    count: usize,
+    bb: AABB,
+}
+
+impl Bvh {
+    // builds a BVH over primitives given by their bounding boxes,
+    // the index of a box in `prim_bbs` is the primitive id handed back on traversal
+    pub fn build(prim_bbs: &[AABB]) -> Self {
+        let inst = Instant::now();
+        let mut build_prims: Vec<BuildPrim> = prim_bbs
+            .iter()
+            .enumerate()
+            .filter(|(_, bb)| !bb.is_empty())
+            .map(|(i, bb)| BuildPrim {
+                ind: i as u32,
+                bb: *bb,
+                centroid: bb.centroid(),
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * build_prims.len()),
+            prim_inds: Vec::with_capacity(build_prims.len()),
+            stats: BvhStats {
+                prim_count: build_prims.len(),
+                ..Default::default()
+            },
+        };
+
+        if !build_prims.is_empty() {
+            bvh.build_recursive(&mut build_prims, 1);
+        }
+
+        let root_area = bvh.nodes.first().map_or(0.0, |n| n.bb.surface_area());
+        if root_area > 0.0 {
+            bvh.stats.sah_cost = bvh.nodes.iter().fold(0.0, |acc, n| {
+                let cost = if n.is_leaf() {
+                    INTERSECT_COST * n.prim_count as f32
+                } else {
+                    TRAVERSAL_COST
+                };
+                acc + cost * n.bb.surface_area() / root_area
+            });
+        }
+        bvh.stats.node_count = bvh.nodes.len();
+        bvh.stats.build_time = inst.elapsed();
+        bvh
+    }
+
+    fn build_recursive(&mut self, prims: &mut [BuildPrim], depth: usize) -> usize {
+        let node_ind = self.nodes.len();
+        self.nodes.push(BvhNode::default());
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        let bb = prims
+            .iter()
+            .fold(AABB::default(), |acc, p| acc.union(&p.bb));
+
+        let mut centroid_bb = AABB::default();
+        for p in prims.iter() {
+            centroid_bb.update(&p.centroid);
+        }
+        let axis = centroid_bb.diagonal().max_dimension();
+
+        let mid = if prims.len() <= 2
+            || depth > SAH_MAX_DEPTH
+            || centroid_bb.max[axis] == centroid_bb.min[axis]
+        {
+            // too few primitives to bother, too deep, or all centroids on top of each other
+            None
+        } else {
+            Self::sah_split(prims, &bb, &centroid_bb, axis)
+        };
+
+        let mid = match mid {
+            Some(mid) => mid,
+            None if prims.len() <= MAX_LEAF_PRIMS => {
+                self.make_leaf(node_ind, bb, prims);
+                return node_ind;
+            }
+            None if centroid_bb.max[axis] == centroid_bb.min[axis] => {
+                // cannot be separated spatially, split the list in half
+                prims.len() / 2
+            }
+            None => {
+                prims.sort_unstable_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+                prims.len() / 2
+            }
+        };
+
+        let (left, right) = prims.split_at_mut(mid);
+        self.build_recursive(left, depth + 1);
+        let second = self.build_recursive(right, depth + 1);
+
+        self.nodes[node_ind] = BvhNode {
+            bb,
+            offset: second as u32,
+            prim_count: 0,
+            axis: axis as u8,
+        };
+        node_ind
+    }
+
+    // returns the partition point if splitting is cheaper than making a leaf
+    fn sah_split(
+        prims: &mut [BuildPrim],
+        bb: &AABB,
+        centroid_bb: &AABB,
+        axis: usize,
+    ) -> Option<usize> {
+        let bucket_of = |p: &BuildPrim| -> usize {
+            let b = (SAH_BUCKETS as f32 * centroid_bb.offset(&p.centroid)[axis]) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+
+        let mut buckets = [Bucket::default(); SAH_BUCKETS];
+        for p in prims.iter() {
+            let b = &mut buckets[bucket_of(p)];
+            b.count += 1;
+            b.bb = b.bb.union(&p.bb);
+        }
+
+        // sweep from both sides so each split cost is O(1)
+        let mut right_area = [0f32; SAH_BUCKETS];
+        let mut right_count = [0usize; SAH_BUCKETS];
+        let mut acc_bb = AABB::default();
+        let mut acc_count = 0;
+        for i in (1..SAH_BUCKETS).rev() {
+            acc_bb = acc_bb.union(&buckets[i].bb);
+            acc_count += buckets[i].count;
+            right_area[i] = acc_bb.surface_area();
+            right_count[i] = acc_count;
+        }
+
+        let total_area = bb.surface_area();
+        let mut best_cost = f32::MAX;
+        let mut best_split = 0;
+        acc_bb = AABB::default();
+        acc_count = 0;
+        for i in 0..SAH_BUCKETS - 1 {
+            acc_bb = acc_bb.union(&buckets[i].bb);
+            acc_count += buckets[i].count;
+            if acc_count == 0 || right_count[i + 1] == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + INTERSECT_COST
+                    * (acc_count as f32 * acc_bb.surface_area()
+                        + right_count[i + 1] as f32 * right_area[i + 1])
+                    / total_area;
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = i;
+            }
+        }
+
+        let leaf_cost = INTERSECT_COST * prims.len() as f32;
+        if best_cost == f32::MAX || (prims.len() <= MAX_LEAF_PRIMS && best_cost >= leaf_cost) {
+            return None;
+        }
+
+        // in-place partition around the chosen bucket boundary
+        let mut mid = 0;
+        for i in 0..prims.len() {
+            if bucket_of(&prims[i]) <= best_split {
+                prims.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some(mid)
+    }
+
+    fn make_leaf(&mut self, node_ind: usize, bb: AABB, prims: &[BuildPrim]) {
+        self.nodes[node_ind] = BvhNode {
+            bb,
+            offset: self.prim_inds.len() as u32,
+            prim_count: prims.len() as u16,
+            axis: 0,
+        };
+        self.prim_inds.extend(prims.iter().map(|p| p.ind));
+        self.stats.leaf_count += 1;
+        self.stats.max_leaf_prims = self.stats.max_leaf_prims.max(prims.len());
+    }
+
+    // closest hit query, `isect_prim` is called with the primitive id and the current
+    // closest distance and must return the hit distance when it is closer than that
+    pub fn intersect<F>(&self, ray: &Ray, t_max: f32, mut isect_prim: F) -> Option<f32>
+    where
+        F: FnMut(u32, f32) -> Option<f32>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let dir_neg = [
+            ray.direction_inv.x < 0.0,
+            ray.direction_inv.y < 0.0,
+            ray.direction_inv.z < 0.0,
+        ];
+
+        let mut closest = t_max;
+        let mut hit = false;
+        let mut stack = [0u32; 64];
+        let mut stack_len = 0;
+        let mut node_ind = 0;
+
+        loop {
+            let node = &self.nodes[node_ind];
+            if node.bb.intersect_range(ray, closest).is_some() {
+                if node.is_leaf() {
+                    let start = node.offset as usize;
+                    for &prim in &self.prim_inds[start..start + node.prim_count as usize] {
+                        if let Some(t) = isect_prim(prim, closest) {
+                            if t < closest {
+                                closest = t;
+                                hit = true;
+                            }
+                        }
+                    }
+                } else if dir_neg[node.axis as usize] {
+                    // visit the child on the near side first
+                    stack[stack_len] = node_ind as u32 + 1;
+                    stack_len += 1;
+                    node_ind = node.offset as usize;
+                    continue;
+                } else {
+                    stack[stack_len] = node.offset;
+                    stack_len += 1;
+                    node_ind += 1;
+                    continue;
+                }
+            }
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            node_ind = stack[stack_len] as usize;
+        }
+
+        if hit {
+            Some(closest)
+        } else {
+            None
+        }
+    }
+
+    // any hit query, stops at the first primitive for which `test_prim` returns true
+    pub fn test_line_intersect<F>(&self, ray: &Ray, t_max: f32, mut test_prim: F) -> bool
+    where
+        F: FnMut(u32) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut stack = [0u32; 64];
+        let mut stack_len = 0;
+        let mut node_ind = 0;
+
+        loop {
+            let node = &self.nodes[node_ind];
+            if node.bb.intersect_range(ray, t_max).is_some() {
+                if node.is_leaf() {
+                    let start = node.offset as usize;
+                    for &prim in &self.prim_inds[start..start + node.prim_count as usize] {
+                        if test_prim(prim) {
+                            return true;
+                        }
+                    }
+                } else {
+                    stack[stack_len] = node.offset;
+                    stack_len += 1;
+                    node_ind += 1;
+                    continue;
+                }
+            }
+            if stack_len == 0 {
+                return false;
+            }
+            stack_len -= 1;
+            node_ind = stack[stack_len] as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        rays::ray::Ray,
+        utils::{
+            aabb::AABB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::Bvh;
+
+    fn random_boxes(n: usize) -> Vec<AABB> {
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..n)
+            .map(|_| {
+                let c = Point::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                );
+                let mut bb = AABB::default();
+                bb.update(&c);
+                bb.update(&(c + Vector::new(rng.gen(), rng.gen(), rng.gen())));
+                bb
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bvh_structure() {
+        let boxes = random_boxes(1000);
+        let bvh = Bvh::build(&boxes);
+
+        assert_eq!(bvh.stats.prim_count, 1000);
+        assert_eq!(bvh.prim_inds.len(), 1000);
+        assert!(bvh.stats.max_leaf_prims <= super::MAX_LEAF_PRIMS);
+
+        let mut seen = bvh.prim_inds.clone();
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), 1000);
+
+        // every leaf box must contain its primitives
+        for node in bvh.nodes.iter().filter(|n| n.is_leaf()) {
+            let start = node.offset as usize;
+            for &p in &bvh.prim_inds[start..start + node.prim_count as usize] {
+                assert_eq!(node.bb.union(&boxes[p as usize]), node.bb);
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_box_hits_match_brute_force() {
+        let boxes = random_boxes(500);
+        let bvh = Bvh::build(&boxes);
+        let mut rng = StdRng::seed_from_u64(11);
+
+        for _ in 0..200 {
+            let origin = Point::new(
+                rng.gen_range(-15.0..15.0),
+                rng.gen_range(-15.0..15.0),
+                rng.gen_range(-15.0..15.0),
+            );
+            let dir = Vector::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            let ray = Ray::new(origin, dir);
+
+            let brute = boxes
+                .iter()
+                .filter_map(|bb| bb.intersect_range(&ray, f32::MAX))
+                .fold(None, |acc: Option<f32>, t| {
+                    Some(acc.map_or(t, |a| a.min(t)))
+                });
+            let accel = bvh.intersect(&ray, f32::MAX, |p, t_max| {
+                boxes[p as usize]
+                    .intersect_range(&ray, t_max)
+                    .filter(|t| *t < t_max)
+            });
+
+            assert_eq!(brute, accel);
+            assert_eq!(
+                brute.is_some(),
+                bvh.test_line_intersect(&ray, f32::MAX, |p| boxes[p as usize]
+                    .intersect_range(&ray, f32::MAX)
+                    .is_some())
+            );
+        }
+    }
+}
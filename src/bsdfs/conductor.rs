@@ -0,0 +1,150 @@
+use serde::Deserialize;
+
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use super::{microfacet::MicrofacetDistribution, reflect, same_hemisphere, Bsdf, BsdfSample};
+
+// complex index of refraction eta + ik of common metals, sampled at roughly 650, 550
+// and 450 nm from the measurements PBRT ships with
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+}
+
+impl ConductorPreset {
+    pub fn eta_k(&self) -> (RGB, RGB) {
+        match self {
+            ConductorPreset::Gold => (RGB::new(0.143, 0.374, 1.442), RGB::new(3.983, 2.385, 1.603)),
+            ConductorPreset::Copper => {
+                (RGB::new(0.200, 0.924, 1.102), RGB::new(3.912, 2.452, 2.142))
+            }
+            ConductorPreset::Aluminium => {
+                (RGB::new(1.657, 0.880, 0.521), RGB::new(9.224, 6.270, 4.837))
+            }
+        }
+    }
+}
+
+// fresnel reflectance of a conductor for one wavelength, PBRT 3rd ed. sec 8.2.1
+pub fn fr_conductor(cos_i: f32, eta: f32, k: f32) -> f32 {
+    let cos_i = cos_i.abs().min(1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    (rp + rs) / 2.0
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fresnel {
+    Conductor { eta: RGB, k: RGB },
+    // F0 + (1 - F0)(1 - cos)^5, for base color driven metals
+    Schlick { f0: RGB },
+}
+
+impl Fresnel {
+    pub fn eval(&self, cos_i: f32) -> RGB {
+        match self {
+            Fresnel::Conductor { eta, k } => RGB::new(
+                fr_conductor(cos_i, eta.r, k.r),
+                fr_conductor(cos_i, eta.g, k.g),
+                fr_conductor(cos_i, eta.b, k.b),
+            ),
+            Fresnel::Schlick { f0 } => {
+                let m = (1.0 - cos_i.abs().min(1.0)).powi(5);
+                *f0 * (1.0 - m) + RGB::new(m, m, m)
+            }
+        }
+    }
+}
+
+// Torrance-Sparrow microfacet reflection, f = tint * D * G * F / (4 cos_o cos_i)
+#[derive(Debug, Clone, Copy)]
+pub struct ConductorBsdf {
+    pub distrib: MicrofacetDistribution,
+    pub fresnel: Fresnel,
+    pub tint: RGB,
+}
+
+impl Bsdf for ConductorBsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        if self.distrib.is_smooth() || !same_hemisphere(wo, wi) {
+            return RGB::default();
+        }
+        let mut wh = wo + wi;
+        let len = wh.norm();
+        if len == 0.0 {
+            return RGB::default();
+        }
+        wh = wh / len;
+        let f = self.fresnel.eval(wi.dot(wh));
+        let d_g = self.distrib.d(wh) * self.distrib.g(wo, wi);
+        self.tint * f * (d_g / (4.0 * wo.z.abs() * wi.z.abs()))
+    }
+
+    fn sample(&self, wo: Vector, _uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        if self.distrib.is_smooth() {
+            let wi = Vector::new(-wo.x, -wo.y, wo.z);
+            return Some(BsdfSample {
+                f: self.tint * self.fresnel.eval(wo.z) / wi.z.abs(),
+                wi,
+                pdf: 1.0,
+                is_specular: true,
+            });
+        }
+        let wh = self.distrib.sample_wh(wo, u);
+        let wi = reflect(wo, wh);
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+        Some(BsdfSample {
+            f: self.eval(wo, wi),
+            wi,
+            pdf: self.pdf(wo, wi),
+            is_specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        if self.distrib.is_smooth() || !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+        let mut wh = wo + wi;
+        let len = wh.norm();
+        if len == 0.0 {
+            return 0.0;
+        }
+        wh = (wh / len).face_forward(wo);
+        self.distrib.pdf(wo, wh) / (4.0 * wo.dot(wh).abs())
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distrib.is_smooth()
+    }
+
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, mut f: F) {
+        if self.distrib.is_smooth() {
+            if let Some(smpl) = self.sample(wo, 0.0, [0.0, 0.0]) {
+                f(smpl);
+            }
+        }
+    }
+}
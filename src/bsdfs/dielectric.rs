@@ -0,0 +1,149 @@
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use super::{Bsdf, BsdfSample};
+
+// fresnel reflectance of an unpolarized wave hitting a boundary with relative index of
+// refraction `eta` (inside over outside), cos_i is negative when coming from the inside
+pub fn fr_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+    let cos_i = cos_i.min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+// direction refracted through a boundary with normal `n` pointing to the outside,
+// None on total internal reflection
+pub fn refract(wo: Vector, n: Vector, eta: f32) -> Option<Vector> {
+    let cos_i = wo.dot(n);
+    let (n, eta_rel) = if cos_i > 0.0 {
+        (n, 1.0 / eta)
+    } else {
+        (-1.0 * n, eta)
+    };
+    let cos_i = cos_i.abs();
+    let sin2_t = eta_rel * eta_rel * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-eta_rel * wo + (eta_rel * cos_i - cos_t) * n)
+}
+
+// smooth boundary between the outside (+z) and a medium with index of refraction `eta`,
+// reflection and refraction are tinted by `kr` and `kt`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DielectricBsdf {
+    pub kr: RGB,
+    pub kt: RGB,
+    pub eta: f32,
+}
+
+impl DielectricBsdf {
+    fn reflection(&self, wo: Vector, fr: f32) -> BsdfSample {
+        let wi = Vector::new(-wo.x, -wo.y, wo.z);
+        BsdfSample {
+            f: self.kr * (fr / wi.z.abs()),
+            wi,
+            pdf: fr,
+            is_specular: true,
+        }
+    }
+
+    fn transmission(&self, wo: Vector, fr: f32) -> Option<BsdfSample> {
+        let wi = refract(wo, Vector::new(0.0, 0.0, 1.0), self.eta)?;
+        // radiance is compressed into the smaller solid angle on the denser side
+        let eta_ratio = if wo.z > 0.0 { 1.0 / self.eta } else { self.eta };
+        Some(BsdfSample {
+            f: self.kt * ((1.0 - fr) * eta_ratio * eta_ratio / wi.z.abs()),
+            wi,
+            pdf: 1.0 - fr,
+            is_specular: true,
+        })
+    }
+}
+
+impl Bsdf for DielectricBsdf {
+    fn eval(&self, _wo: Vector, _wi: Vector) -> RGB {
+        RGB::default()
+    }
+
+    fn sample(&self, wo: Vector, uc: f32, _u: [f32; 2]) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let fr = fr_dielectric(wo.z, self.eta);
+        if uc < fr {
+            Some(self.reflection(wo, fr))
+        } else {
+            self.transmission(wo, fr)
+        }
+    }
+
+    fn pdf(&self, _wo: Vector, _wi: Vector) -> f32 {
+        0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn has_transmission(&self) -> bool {
+        true
+    }
+
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, mut f: F) {
+        if wo.z == 0.0 {
+            return;
+        }
+        let fr = fr_dielectric(wo.z, self.eta);
+        if fr > 0.0 {
+            f(BsdfSample {
+                pdf: 1.0,
+                ..self.reflection(wo, fr)
+            });
+        }
+        if let Some(smpl) = self.transmission(wo, fr) {
+            f(BsdfSample { pdf: 1.0, ..smpl });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vector::Vector;
+
+    use super::{fr_dielectric, refract};
+
+    #[test]
+    fn dielectric_fresnel_and_refraction() {
+        // normal incidence on glass reflects ((n - 1) / (n + 1))^2
+        assert!((fr_dielectric(1.0, 1.5) - 0.04).abs() < 1e-5);
+        assert!((fr_dielectric(-1.0, 1.5) - 0.04).abs() < 1e-5);
+        // grazing light from inside the glass is totally reflected
+        assert_eq!(fr_dielectric(-0.2, 1.5), 1.0);
+
+        let s = 0.5f32;
+        let wo = Vector::new(s, 0.0, (1.0 - s * s).sqrt());
+        let n = Vector::new(0.0, 0.0, 1.0);
+        let wt = refract(wo, n, 1.5).unwrap();
+        // snell's law, sin_t = sin_i / eta on the opposite side
+        assert!(wt.z < 0.0);
+        assert!((wt.x + s / 1.5).abs() < 1e-5);
+        assert!((wt.norm() - 1.0).abs() < 1e-5);
+        // and the path is reversible
+        let back = refract(wt, n, 1.5).unwrap();
+        assert!((back.x - wo.x).abs() < 1e-5 && (back.z - wo.z).abs() < 1e-5);
+        assert!(refract(Vector::new(0.9, 0.0, -(1.0f32 - 0.81).sqrt()), n, 1.5).is_none());
+    }
+}
@@ -0,0 +1,39 @@
+use std::f32::consts::PI;
+
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use super::{same_hemisphere, sample_cosine_hemisphere, Bsdf, BsdfSample};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LambertianBsdf {
+    pub kd: RGB,
+}
+
+impl Bsdf for LambertianBsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        if !same_hemisphere(wo, wi) {
+            return RGB::default();
+        }
+        self.kd / PI
+    }
+
+    fn sample(&self, wo: Vector, _uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        let mut wi = sample_cosine_hemisphere(u);
+        if wo.z < 0.0 {
+            wi.z = -wi.z;
+        }
+        Some(BsdfSample {
+            f: self.eval(wo, wi),
+            wi,
+            pdf: self.pdf(wo, wi),
+            is_specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+        wi.z.abs() / PI
+    }
+}
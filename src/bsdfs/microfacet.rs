@@ -0,0 +1,246 @@
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+
+use crate::utils::vector::Vector;
+
+// based on PBRT's 3rd ed. book, sec 8.4, and on Heitz's "Sampling the GGX Distribution
+// of Visible Normals" (JCGT 2018) for GGX visible normal sampling
+
+// below this alpha the surface is treated as perfectly smooth
+pub const SMOOTH_ALPHA: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MicrofacetType {
+    #[default]
+    Ggx,
+    Beckmann,
+}
+
+// isotropic microfacet distribution, directions in the shading frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MicrofacetDistribution {
+    pub kind: MicrofacetType,
+    pub alpha: f32,
+}
+
+// perceptual roughness, e.g. MTL Pr, to alpha
+pub fn roughness_to_alpha(roughness: f32) -> f32 {
+    roughness * roughness
+}
+
+// alpha of the Beckmann lobe that matches a Phong exponent (Walter et al. 2007)
+pub fn phong_to_alpha(ns: f32) -> f32 {
+    (2.0 / (ns.max(0.0) + 2.0)).sqrt()
+}
+
+impl MicrofacetDistribution {
+    pub fn new(kind: MicrofacetType, alpha: f32) -> Self {
+        Self { kind, alpha }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < SMOOTH_ALPHA
+    }
+
+    pub fn d(&self, wh: Vector) -> f32 {
+        let cos2 = wh.z * wh.z;
+        if cos2 == 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        let a2 = self.alpha * self.alpha;
+        match self.kind {
+            MicrofacetType::Ggx => {
+                let e = 1.0 + tan2 / a2;
+                1.0 / (PI * a2 * cos2 * cos2 * e * e)
+            }
+            MicrofacetType::Beckmann => (-tan2 / a2).exp() / (PI * a2 * cos2 * cos2),
+        }
+    }
+
+    // Smith's auxiliary function, invisible over visible microfacet area towards w
+    pub fn lambda(&self, w: Vector) -> f32 {
+        let cos2 = w.z * w.z;
+        if cos2 == 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        match self.kind {
+            MicrofacetType::Ggx => ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0,
+            MicrofacetType::Beckmann => {
+                let a = 1.0 / (self.alpha * tan2.sqrt());
+                if a >= 1.6 {
+                    0.0
+                } else {
+                    (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+                }
+            }
+        }
+    }
+
+    pub fn g1(&self, w: Vector) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height correlated masking-shadowing
+    pub fn g(&self, wo: Vector, wi: Vector) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // density of sample_wh, the distribution of normals visible from wo
+    pub fn pdf(&self, wo: Vector, wh: Vector) -> f32 {
+        if wo.z == 0.0 {
+            return 0.0;
+        }
+        self.d(wh) * self.g1(wo) * wo.dot(wh).abs() / wo.z.abs()
+    }
+
+    // microfacet normal visible from wo, on the same side as wo
+    pub fn sample_wh(&self, wo: Vector, u: [f32; 2]) -> Vector {
+        let flip = wo.z < 0.0;
+        let wo = if flip { -1.0 * wo } else { wo };
+        let wh = match self.kind {
+            MicrofacetType::Ggx => self.sample_ggx_vndf(wo, u),
+            MicrofacetType::Beckmann => self.sample_beckmann_vndf(wo, u),
+        };
+        if flip {
+            -1.0 * wh
+        } else {
+            wh
+        }
+    }
+
+    fn sample_ggx_vndf(&self, wo: Vector, u: [f32; 2]) -> Vector {
+        // stretch to the hemisphere configuration and build an orthonormal basis
+        let mut vh = Vector::new(self.alpha * wo.x, self.alpha * wo.y, wo.z);
+        vh.normalize();
+        let len2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len2 > 0.0 {
+            Vector::new(-vh.y, vh.x, 0.0) / len2.sqrt()
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // uniform disk sample, warped towards the visible half of the projected hemisphere
+        let r = u[0].sqrt();
+        let phi = 2.0 * PI * u[1];
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        let mut wh = Vector::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6));
+        wh.normalize();
+        wh
+    }
+
+    // PBRT 3rd ed. BeckmannSample, slopes sampled for the unit roughness configuration
+    fn sample_beckmann_vndf(&self, wo: Vector, u: [f32; 2]) -> Vector {
+        let mut wi = Vector::new(self.alpha * wo.x, self.alpha * wo.y, wo.z);
+        wi.normalize();
+        let (mut slope_x, mut slope_y) = beckmann_sample_11(wi.z, u[0], u[1]);
+
+        let sin_theta = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+        let (cos_phi, sin_phi) = if sin_theta == 0.0 {
+            (1.0, 0.0)
+        } else {
+            (
+                (wi.x / sin_theta).clamp(-1.0, 1.0),
+                (wi.y / sin_theta).clamp(-1.0, 1.0),
+            )
+        };
+        let tmp = cos_phi * slope_x - sin_phi * slope_y;
+        slope_y = sin_phi * slope_x + cos_phi * slope_y;
+        slope_x = tmp;
+
+        let mut wh = Vector::new(-self.alpha * slope_x, -self.alpha * slope_y, 1.0);
+        wh.normalize();
+        wh
+    }
+}
+
+fn beckmann_sample_11(cos_theta: f32, u1: f32, u2: f32) -> (f32, f32) {
+    if cos_theta > 0.9999 {
+        let r = (-(1.0 - u1).ln()).sqrt();
+        let phi = 2.0 * PI * u2;
+        return (r * phi.cos(), r * phi.sin());
+    }
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let tan_theta = sin_theta / cos_theta;
+    let cot_theta = 1.0 / tan_theta;
+
+    // invert the slope cdf with a bisection safeguarded Newton search
+    let mut a = -1.0;
+    let mut c = erf(cot_theta);
+    let sample_x = u1.max(1e-6);
+    let theta = cos_theta.acos();
+    let fit = 1.0 + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1.0 + c) * (1.0 - sample_x).powf(fit);
+    let sqrt_pi_inv = 1.0 / PI.sqrt();
+    let normalization = 1.0 / (1.0 + c + sqrt_pi_inv * tan_theta * (-cot_theta * cot_theta).exp());
+
+    for _ in 0..9 {
+        if !(b >= a && b <= c) {
+            b = 0.5 * (a + c);
+        }
+        let inv_erf = erf_inv(b);
+        let value = normalization
+            * (1.0 + b + sqrt_pi_inv * tan_theta * (-inv_erf * inv_erf).exp())
+            - sample_x;
+        let derivative = normalization * (1.0 - inv_erf * tan_theta);
+        if value.abs() < 1e-5 {
+            break;
+        }
+        if value > 0.0 {
+            c = b;
+        } else {
+            a = b;
+        }
+        b -= value / derivative;
+    }
+    (erf_inv(b), erf_inv(2.0 * u2.max(1e-6) - 1.0))
+}
+
+// Abramowitz and Stegun 7.1.26
+fn erf(x: f32) -> f32 {
+    let sign = x.signum();
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = ((((1.061_405_4 * t - 1.453_152_1) * t + 1.421_413_8) * t - 0.284_496_72) * t
+        + 0.254_829_6)
+        * t;
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+// Giles, "Approximating the erfinv function"
+fn erf_inv(x: f32) -> f32 {
+    let x = x.clamp(-0.99999, 0.99999);
+    let mut w = -((1.0 - x) * (1.0 + x)).ln();
+    let p = if w < 5.0 {
+        w -= 2.5;
+        let mut p = 2.810_226_4e-8;
+        p = 3.432_739_4e-7 + p * w;
+        p = -3.523_387_7e-6 + p * w;
+        p = -4.391_506_5e-6 + p * w;
+        p = 0.000_218_580_87 + p * w;
+        p = -0.001_253_725 + p * w;
+        p = -0.004_177_681_6 + p * w;
+        p = 0.246_640_73 + p * w;
+        1.501_409_4 + p * w
+    } else {
+        w = w.sqrt() - 3.0;
+        let mut p = -0.000_200_214_26;
+        p = 0.000_100_950_56 + p * w;
+        p = 0.001_349_343_2 + p * w;
+        p = -0.003_673_428_4 + p * w;
+        p = 0.005_739_507_7 + p * w;
+        p = -0.007_622_461 + p * w;
+        p = 0.009_438_870_5 + p * w;
+        p = 1.001_674 + p * w;
+        2.832_976_8 + p * w
+    };
+    p * x
+}
@@ -0,0 +1,42 @@
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use super::{Bsdf, BsdfSample};
+
+// perfect specular reflection scaled by `ks`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MirrorBsdf {
+    pub ks: RGB,
+}
+
+impl Bsdf for MirrorBsdf {
+    fn eval(&self, _wo: Vector, _wi: Vector) -> RGB {
+        RGB::default()
+    }
+
+    fn sample(&self, wo: Vector, _uc: f32, _u: [f32; 2]) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let wi = Vector::new(-wo.x, -wo.y, wo.z);
+        Some(BsdfSample {
+            f: self.ks / wi.z.abs(),
+            wi,
+            pdf: 1.0,
+            is_specular: true,
+        })
+    }
+
+    fn pdf(&self, _wo: Vector, _wi: Vector) -> f32 {
+        0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, mut f: F) {
+        if let Some(smpl) = self.sample(wo, 0.0, [0.0, 0.0]) {
+            f(smpl);
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use self::{
+    conductor::ConductorBsdf, dielectric::DielectricBsdf, lambertian::LambertianBsdf,
+    mirror::MirrorBsdf, phong::PhongBsdf, rough_dielectric::RoughDielectricBsdf,
+};
+
+pub mod conductor;
+pub mod dielectric;
+pub mod lambertian;
+pub mod microfacet;
+pub mod mirror;
+pub mod phong;
+pub mod rough_dielectric;
+
+// based on PBRT's 3rd ed. book, chapters 8 and 14.1
+// directions are given in the local shading frame, where z is the shading normal and
+// both wo and wi point away from the surface
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BsdfSample {
+    pub f: RGB,
+    pub wi: Vector,
+    pub pdf: f32,
+    // sampled from a delta distribution, `f` and `pdf` are only meaningful as a ratio
+    pub is_specular: bool,
+}
+
+pub trait Bsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB;
+
+    // `uc` picks between discrete events such as lobes or reflection and refraction,
+    // `u` samples the direction
+    fn sample(&self, wo: Vector, uc: f32, u: [f32; 2]) -> Option<BsdfSample>;
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32;
+
+    // true when all of the scattering happens in delta directions
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // true when light can scatter through the surface, to the other side of wo
+    fn has_transmission(&self) -> bool {
+        false
+    }
+
+    // every delta direction the BSDF scatters into, for shaders that follow all of them
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, _wo: Vector, _f: F) {}
+}
+
+pub fn same_hemisphere(wo: Vector, wi: Vector) -> bool {
+    wo.z * wi.z > 0.0
+}
+
+// mirror direction of wo about n
+pub fn reflect(wo: Vector, n: Vector) -> Vector {
+    2.0 * wo.dot(n) * n - wo
+}
+
+// cosine weighted direction in the hemisphere around +z
+pub fn sample_cosine_hemisphere(u: [f32; 2]) -> Vector {
+    let r = u[1].sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u[0];
+    Vector::new(r * phi.cos(), r * phi.sin(), (1.0 - u[1]).max(0.0).sqrt())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AnyBsdf {
+    Lambertian(LambertianBsdf),
+    Mirror(MirrorBsdf),
+    Phong(PhongBsdf),
+    Dielectric(DielectricBsdf),
+    Conductor(ConductorBsdf),
+    RoughDielectric(RoughDielectricBsdf),
+}
+
+impl Bsdf for AnyBsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        match self {
+            AnyBsdf::Lambertian(b) => b.eval(wo, wi),
+            AnyBsdf::Mirror(b) => b.eval(wo, wi),
+            AnyBsdf::Phong(b) => b.eval(wo, wi),
+            AnyBsdf::Dielectric(b) => b.eval(wo, wi),
+            AnyBsdf::Conductor(b) => b.eval(wo, wi),
+            AnyBsdf::RoughDielectric(b) => b.eval(wo, wi),
+        }
+    }
+
+    fn sample(&self, wo: Vector, uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        match self {
+            AnyBsdf::Lambertian(b) => b.sample(wo, uc, u),
+            AnyBsdf::Mirror(b) => b.sample(wo, uc, u),
+            AnyBsdf::Phong(b) => b.sample(wo, uc, u),
+            AnyBsdf::Dielectric(b) => b.sample(wo, uc, u),
+            AnyBsdf::Conductor(b) => b.sample(wo, uc, u),
+            AnyBsdf::RoughDielectric(b) => b.sample(wo, uc, u),
+        }
+    }
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        match self {
+            AnyBsdf::Lambertian(b) => b.pdf(wo, wi),
+            AnyBsdf::Mirror(b) => b.pdf(wo, wi),
+            AnyBsdf::Phong(b) => b.pdf(wo, wi),
+            AnyBsdf::Dielectric(b) => b.pdf(wo, wi),
+            AnyBsdf::Conductor(b) => b.pdf(wo, wi),
+            AnyBsdf::RoughDielectric(b) => b.pdf(wo, wi),
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        match self {
+            AnyBsdf::Lambertian(b) => b.is_specular(),
+            AnyBsdf::Mirror(b) => b.is_specular(),
+            AnyBsdf::Phong(b) => b.is_specular(),
+            AnyBsdf::Dielectric(b) => b.is_specular(),
+            AnyBsdf::Conductor(b) => b.is_specular(),
+            AnyBsdf::RoughDielectric(b) => b.is_specular(),
+        }
+    }
+
+    fn has_transmission(&self) -> bool {
+        match self {
+            AnyBsdf::Lambertian(b) => b.has_transmission(),
+            AnyBsdf::Mirror(b) => b.has_transmission(),
+            AnyBsdf::Phong(b) => b.has_transmission(),
+            AnyBsdf::Dielectric(b) => b.has_transmission(),
+            AnyBsdf::Conductor(b) => b.has_transmission(),
+            AnyBsdf::RoughDielectric(b) => b.has_transmission(),
+        }
+    }
+
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, f: F) {
+        match self {
+            AnyBsdf::Lambertian(b) => b.for_each_specular(wo, f),
+            AnyBsdf::Mirror(b) => b.for_each_specular(wo, f),
+            AnyBsdf::Phong(b) => b.for_each_specular(wo, f),
+            AnyBsdf::Dielectric(b) => b.for_each_specular(wo, f),
+            AnyBsdf::Conductor(b) => b.for_each_specular(wo, f),
+            AnyBsdf::RoughDielectric(b) => b.for_each_specular(wo, f),
+        }
+    }
+}
+
+pub const MAX_LOBES: usize = 4;
+
+// sum of several lobes, one of them is picked per sample with probability `weights[i]`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MixBsdf {
+    pub lobes: [Option<AnyBsdf>; MAX_LOBES],
+    pub weights: [f32; MAX_LOBES],
+    pub count: usize,
+}
+
+impl MixBsdf {
+    // lobes with a zero selection weight are dropped
+    pub fn push(&mut self, lobe: AnyBsdf, weight: f32) {
+        if weight <= 0.0 || self.count == MAX_LOBES {
+            return;
+        }
+        self.lobes[self.count] = Some(lobe);
+        self.weights[self.count] = weight;
+        self.count += 1;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&AnyBsdf, f32)> {
+        self.lobes[..self.count]
+            .iter()
+            .zip(self.weights)
+            .filter_map(|(l, w)| l.as_ref().map(|l| (l, w)))
+    }
+
+    fn weight_sum(&self) -> f32 {
+        self.weights[..self.count].iter().sum()
+    }
+}
+
+impl Bsdf for MixBsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        let mut f = RGB::default();
+        for (lobe, _) in self.iter() {
+            f += lobe.eval(wo, wi);
+        }
+        f
+    }
+
+    fn sample(&self, wo: Vector, uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        // pick a lobe with uc and stretch the rest of its interval back to [0, 1)
+        let total = self.weight_sum();
+        let mut target = uc * total;
+        let mut picked = None;
+        for (i, (lobe, w)) in self.iter().enumerate() {
+            if target < w || i + 1 == self.count {
+                picked = Some((lobe, w));
+                break;
+            }
+            target -= w;
+        }
+        let (lobe, w) = picked?;
+        let uc_lobe = (target / w).min(1.0 - f32::EPSILON);
+
+        let mut smpl = lobe.sample(wo, uc_lobe, u)?;
+        if smpl.is_specular {
+            smpl.pdf *= w / total;
+            return Some(smpl);
+        }
+        // the other non delta lobes could have produced the same direction
+        smpl.f = self.eval(wo, smpl.wi);
+        smpl.pdf = self.pdf(wo, smpl.wi);
+        Some(smpl)
+    }
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        let total = self.weight_sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        self.iter()
+            .map(|(lobe, w)| lobe.pdf(wo, wi) * w)
+            .sum::<f32>()
+            / total
+    }
+
+    fn is_specular(&self) -> bool {
+        self.iter().all(|(lobe, _)| lobe.is_specular())
+    }
+
+    fn has_transmission(&self) -> bool {
+        self.iter().any(|(lobe, _)| lobe.has_transmission())
+    }
+
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, mut f: F) {
+        for (lobe, _) in self.iter() {
+            lobe.for_each_specular(wo, &mut f);
+        }
+    }
+}
+
+// BSDF placed at a hit point, converts world space directions to the shading frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SurfaceBsdf {
+    pub ss: Vector,
+    pub ts: Vector,
+    pub ns: Vector,
+    pub ng: Vector,
+    pub bsdf: MixBsdf,
+}
+
+impl SurfaceBsdf {
+    pub fn new(ns: Vector, ng: Vector, bsdf: MixBsdf) -> Self {
+        let (ss, ts) = ns.coordinate_system();
+        Self {
+            ss,
+            ts,
+            ns,
+            ng,
+            bsdf,
+        }
+    }
+
+    pub fn world_to_local(&self, v: Vector) -> Vector {
+        Vector::new(v.dot(self.ss), v.dot(self.ts), v.dot(self.ns))
+    }
+
+    pub fn local_to_world(&self, v: Vector) -> Vector {
+        v.rotate(self.ss, self.ts, self.ns)
+    }
+
+    pub fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        self.bsdf
+            .eval(self.world_to_local(wo), self.world_to_local(wi))
+    }
+
+    pub fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        self.bsdf
+            .pdf(self.world_to_local(wo), self.world_to_local(wi))
+    }
+
+    // samples are dropped when the shading frame disagrees with the geometry on which
+    // side of the surface the direction ends up
+    pub fn sample(&self, wo: Vector, uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        let wo_l = self.world_to_local(wo);
+        let mut smpl = self.bsdf.sample(wo_l, uc, u)?;
+        if smpl.pdf <= 0.0 || smpl.f.is_zero() {
+            return None;
+        }
+        let reflected = same_hemisphere(wo_l, smpl.wi);
+        smpl.wi = self.local_to_world(smpl.wi);
+        if reflected != (wo.dot(self.ng) * smpl.wi.dot(self.ng) > 0.0) {
+            return None;
+        }
+        Some(smpl)
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.bsdf.is_specular()
+    }
+
+    pub fn has_transmission(&self) -> bool {
+        self.bsdf.has_transmission()
+    }
+
+    pub fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, mut f: F) {
+        let wo_l = self.world_to_local(wo);
+        self.bsdf.for_each_specular(wo_l, |mut smpl| {
+            let reflected = same_hemisphere(wo_l, smpl.wi);
+            smpl.wi = self.local_to_world(smpl.wi);
+            if reflected == (wo.dot(self.ng) * smpl.wi.dot(self.ng) > 0.0) {
+                f(smpl);
+            }
+        });
+    }
+
+    // |cos| between a world space direction and the shading normal
+    pub fn abs_cos(&self, w: Vector) -> f32 {
+        w.dot(self.ns).abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{rgb::RGB, vector::Vector};
+
+    use super::{
+        conductor::{fr_conductor, ConductorBsdf, Fresnel},
+        lambertian::LambertianBsdf,
+        microfacet::{MicrofacetDistribution, MicrofacetType},
+        phong::PhongBsdf,
+        rough_dielectric::RoughDielectricBsdf,
+        AnyBsdf, Bsdf, MixBsdf,
+    };
+
+    // estimates the directional albedo of `bsdf` with a stratified grid of samples and
+    // checks that sample agrees with eval and pdf along the way
+    fn albedo(bsdf: &impl Bsdf, wo: Vector) -> f32 {
+        let n = 64;
+        let mut sum = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = [(i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32];
+                let uc = ((i * 7 + j * 13) % n) as f32 / n as f32;
+                let Some(s) = bsdf.sample(wo, uc, u) else {
+                    continue;
+                };
+                assert!((s.pdf - bsdf.pdf(wo, s.wi)).abs() <= 1e-3 * s.pdf);
+                assert!((s.f.r - bsdf.eval(wo, s.wi).r).abs() <= 1e-3 * s.f.r);
+                sum += s.f.r * s.wi.z.abs() / s.pdf;
+            }
+        }
+        sum / (n * n) as f32
+    }
+
+    #[test]
+    fn bsdf_sampling_matches_eval() {
+        let white = RGB::new(1.0, 1.0, 1.0);
+        let wo = Vector::new(0.3, 0.0, (1.0f32 - 0.09).sqrt());
+
+        let diffuse = LambertianBsdf { kd: white };
+        assert!((albedo(&diffuse, wo) - 1.0).abs() < 1e-2);
+        assert!((albedo(&diffuse, -1.0 * wo) - 1.0).abs() < 1e-2);
+
+        let glossy = PhongBsdf {
+            ks: white,
+            ns: 20.0,
+        };
+        let a = albedo(&glossy, wo);
+        assert!(a > 0.8 && a <= 1.0);
+
+        let mut mix = MixBsdf::default();
+        mix.push(AnyBsdf::Lambertian(LambertianBsdf { kd: white * 0.5 }), 0.5);
+        mix.push(
+            AnyBsdf::Phong(PhongBsdf {
+                ks: white * 0.5,
+                ns: 20.0,
+            }),
+            0.5,
+        );
+        let a = albedo(&mix, wo);
+        assert!(a > 0.9 && a <= 1.0);
+    }
+
+    #[test]
+    fn microfacet_sampling_matches_eval() {
+        let white = RGB::new(1.0, 1.0, 1.0);
+        let wo = Vector::new(0.5, 0.0, (1.0f32 - 0.25).sqrt());
+
+        // normal incidence reflectance of a conductor
+        let (eta, k) = (0.2f32, 3.9f32);
+        let f0 = ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+        assert!((fr_conductor(1.0, eta, k) - f0).abs() < 1e-5);
+
+        for kind in [MicrofacetType::Ggx, MicrofacetType::Beckmann] {
+            let distrib = MicrofacetDistribution::new(kind, 0.3);
+            // a white furnace only loses the energy of single scattering
+            let metal = ConductorBsdf {
+                distrib,
+                fresnel: Fresnel::Schlick { f0: white },
+                tint: white,
+            };
+            let a = albedo(&metal, wo);
+            assert!(a > 0.85 && a <= 1.0, "{:?} {}", kind, a);
+
+            let glass = RoughDielectricBsdf {
+                distrib,
+                kr: white,
+                kt: white,
+                eta: 1.5,
+            };
+            let a = albedo(&glass, wo);
+            assert!(a > 0.4 && a <= 1.0, "{:?} {}", kind, a);
+            let a = albedo(&glass, -1.0 * wo);
+            assert!(a > 0.4 && a <= 1.0 * 1.5 * 1.5, "{:?} {}", kind, a);
+        }
+    }
+}
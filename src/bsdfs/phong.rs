@@ -0,0 +1,62 @@
+use std::f32::consts::PI;
+
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use super::{same_hemisphere, Bsdf, BsdfSample};
+
+// energy normalized Phong lobe around the mirror direction, f = ks * (n + 2) / 2pi * cos^n,
+// sampled proportionally to cos^n (Lafortune and Willems, "Using the modified Phong
+// reflectance model for physically based rendering")
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhongBsdf {
+    pub ks: RGB,
+    pub ns: f32,
+}
+
+impl PhongBsdf {
+    fn reflect(wo: Vector) -> Vector {
+        Vector::new(-wo.x, -wo.y, wo.z)
+    }
+
+    fn cos_alpha(wo: Vector, wi: Vector) -> f32 {
+        Self::reflect(wo).dot(wi).max(0.0)
+    }
+}
+
+impl Bsdf for PhongBsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        if !same_hemisphere(wo, wi) {
+            return RGB::default();
+        }
+        let cos_a = Self::cos_alpha(wo, wi);
+        self.ks * ((self.ns + 2.0) / (2.0 * PI) * cos_a.powf(self.ns))
+    }
+
+    fn sample(&self, wo: Vector, _uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        let cos_a = u[1].powf(1.0 / (self.ns + 1.0));
+        let sin_a = (1.0 - cos_a * cos_a).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[0];
+        let local = Vector::new(sin_a * phi.cos(), sin_a * phi.sin(), cos_a);
+
+        let r = Self::reflect(wo);
+        let (rx, ry) = r.coordinate_system();
+        let wi = local.rotate(rx, ry, r);
+        // the part of the lobe below the surface is lost
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+        Some(BsdfSample {
+            f: self.eval(wo, wi),
+            wi,
+            pdf: self.pdf(wo, wi),
+            is_specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+        (self.ns + 1.0) / (2.0 * PI) * Self::cos_alpha(wo, wi).powf(self.ns)
+    }
+}
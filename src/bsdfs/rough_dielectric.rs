@@ -0,0 +1,143 @@
+use crate::utils::{rgb::RGB, vector::Vector};
+
+use super::{
+    dielectric::{fr_dielectric, refract, DielectricBsdf},
+    microfacet::MicrofacetDistribution,
+    reflect, same_hemisphere, Bsdf, BsdfSample,
+};
+
+// Walter et al. "Microfacet Models for Refraction through Rough Surfaces" (2007), in the
+// form of PBRT's 4th ed. sec 9.7, a perfectly smooth distribution falls back to
+// DielectricBsdf
+#[derive(Debug, Clone, Copy)]
+pub struct RoughDielectricBsdf {
+    pub distrib: MicrofacetDistribution,
+    pub kr: RGB,
+    pub kt: RGB,
+    pub eta: f32,
+}
+
+impl RoughDielectricBsdf {
+    fn smooth(&self) -> DielectricBsdf {
+        DielectricBsdf {
+            kr: self.kr,
+            kt: self.kt,
+            eta: self.eta,
+        }
+    }
+
+    // generalized half vector facing +z and the relative eta across the boundary,
+    // None for configurations no microfacet can produce
+    fn half_vector(&self, wo: Vector, wi: Vector) -> Option<(Vector, f32, bool)> {
+        if wo.z == 0.0 || wi.z == 0.0 {
+            return None;
+        }
+        let reflect = same_hemisphere(wo, wi);
+        let etap = if reflect {
+            1.0
+        } else if wo.z > 0.0 {
+            self.eta
+        } else {
+            1.0 / self.eta
+        };
+        let mut wm = wi * etap + wo;
+        let len = wm.norm();
+        if len == 0.0 {
+            return None;
+        }
+        wm = (wm / len).face_forward(Vector::new(0.0, 0.0, 1.0));
+        // microfacets seen from behind
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+        Some((wm, etap, reflect))
+    }
+}
+
+impl Bsdf for RoughDielectricBsdf {
+    fn eval(&self, wo: Vector, wi: Vector) -> RGB {
+        if self.distrib.is_smooth() {
+            return RGB::default();
+        }
+        let Some((wm, etap, reflect)) = self.half_vector(wo, wi) else {
+            return RGB::default();
+        };
+        let fr = fr_dielectric(wo.dot(wm), self.eta);
+        let d_g = self.distrib.d(wm) * self.distrib.g(wo, wi);
+        if reflect {
+            self.kr * (d_g * fr / (4.0 * wo.z * wi.z).abs())
+        } else {
+            let denom = wi.dot(wm) + wo.dot(wm) / etap;
+            let denom = denom * denom * wi.z * wo.z;
+            // radiance is compressed into the smaller solid angle on the denser side
+            let ft = d_g * (1.0 - fr) * (wi.dot(wm) * wo.dot(wm) / denom).abs();
+            self.kt * (ft / (etap * etap))
+        }
+    }
+
+    fn sample(&self, wo: Vector, uc: f32, u: [f32; 2]) -> Option<BsdfSample> {
+        if self.distrib.is_smooth() {
+            return self.smooth().sample(wo, uc, u);
+        }
+        if wo.z == 0.0 {
+            return None;
+        }
+        let wm = self
+            .distrib
+            .sample_wh(wo, u)
+            .face_forward(Vector::new(0.0, 0.0, 1.0));
+        let fr = fr_dielectric(wo.dot(wm), self.eta);
+        let wi = if uc < fr {
+            let wi = reflect(wo, wm);
+            if !same_hemisphere(wo, wi) {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract(wo, wm, self.eta)?;
+            if same_hemisphere(wo, wi) || wi.z == 0.0 {
+                return None;
+            }
+            wi
+        };
+        Some(BsdfSample {
+            f: self.eval(wo, wi),
+            wi,
+            pdf: self.pdf(wo, wi),
+            is_specular: false,
+        })
+    }
+
+    fn pdf(&self, wo: Vector, wi: Vector) -> f32 {
+        if self.distrib.is_smooth() {
+            return 0.0;
+        }
+        let Some((wm, etap, reflect)) = self.half_vector(wo, wi) else {
+            return 0.0;
+        };
+        let fr = fr_dielectric(wo.dot(wm), self.eta);
+        // the visible normal pdf is defined for normals on the side of wo
+        let pdf_wm = self.distrib.pdf(wo, wm.face_forward(wo));
+        if reflect {
+            pdf_wm / (4.0 * wo.dot(wm).abs()) * fr
+        } else {
+            let denom = wi.dot(wm) + wo.dot(wm) / etap;
+            let dwm_dwi = wi.dot(wm).abs() / (denom * denom);
+            pdf_wm * dwm_dwi * (1.0 - fr)
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distrib.is_smooth()
+    }
+
+    fn has_transmission(&self) -> bool {
+        true
+    }
+
+    fn for_each_specular<F: FnMut(BsdfSample)>(&self, wo: Vector, f: F) {
+        if self.distrib.is_smooth() {
+            self.smooth().for_each_specular(wo, f);
+        }
+    }
+}
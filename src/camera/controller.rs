@@ -0,0 +1,107 @@
+use std::f32::consts::PI;
+
+use crate::{
+    camera::CameraFrame,
+    utils::vector::{Point, Vector},
+};
+
+// look-at camera driven by the viewer's mouse and keyboard, `up` is the world up
+// direction orbits turn around and never the camera's own tilted up
+#[derive(Debug, Clone, Copy)]
+pub struct CameraController {
+    pub eye: Point,
+    pub at: Point,
+    pub up: Vector,
+}
+
+// keeps orbits from reaching the poles where the view and up directions align
+const MIN_POLAR: f32 = 1e-2;
+const MIN_DISTANCE: f32 = 1e-3;
+
+impl CameraController {
+    pub fn new(eye: Point, at: Point, up: Vector) -> Self {
+        let mut up = up;
+        up.normalize();
+        Self { eye, at, up }
+    }
+
+    pub fn frame(&self) -> CameraFrame {
+        CameraFrame::look_at(self.eye, self.at, self.up)
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.at.vec2point(self.eye).norm()
+    }
+
+    // turns the eye around the target, yaw around the world up and pitch towards it,
+    // both in radians
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let offset = self.at.vec2point(self.eye);
+        let r = offset.norm();
+        let (b1, b2) = self.up.coordinate_system();
+        let theta = (offset.dot(self.up) / r).clamp(-1.0, 1.0).acos();
+        let phi = offset.dot(b2).atan2(offset.dot(b1));
+
+        let theta = (theta - pitch).clamp(MIN_POLAR, PI - MIN_POLAR);
+        let phi = phi - yaw;
+        let dir =
+            theta.sin() * phi.cos() * b1 + theta.sin() * phi.sin() * b2 + theta.cos() * self.up;
+        self.eye = self.at + r * dir;
+    }
+
+    // moves eye and target across the view plane, in units of the target distance
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let frame = self.frame();
+        let delta = self.distance() * (dy * frame.up - dx * frame.right);
+        self.eye = self.eye + delta;
+        self.at = self.at + delta;
+    }
+
+    // dollies towards the target, each step closing a tenth of the distance
+    pub fn zoom(&mut self, steps: f32) {
+        let offset = self.at.vec2point(self.eye);
+        let r = offset.norm();
+        let scale = (r * 0.9f32.powf(steps)).max(MIN_DISTANCE) / r;
+        self.eye = self.at + scale * offset;
+    }
+
+    // moves eye and target together along the view direction, the camera's right
+    // and the world up, in scene units
+    pub fn fly(&mut self, forward: f32, right: f32, up: f32) {
+        let frame = self.frame();
+        let delta = -forward * frame.back + right * frame.right + up * self.up;
+        self.eye = self.eye + delta;
+        self.at = self.at + delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn controller_keeps_target_distance() {
+        let at = Point::new(1.0, 2.0, 3.0);
+        let mut controller =
+            CameraController::new(Point::new(1.0, 2.0, 13.0), at, Vector::new(0.0, 2.0, 0.0));
+
+        // orbits turn around the target without changing the distance, nor flipping
+        // over the poles
+        controller.orbit(0.7, -0.3);
+        assert!((controller.distance() - 10.0).abs() < 1e-3);
+        assert!(controller.at.vec2point(at).norm() < 1e-6);
+        controller.orbit(0.0, 10.0);
+        let back = controller.frame().back;
+        assert!(back.y < 1.0 && back.y > 0.99, "{:?}", back);
+
+        controller.zoom(1.0);
+        assert!((controller.distance() - 9.0).abs() < 1e-3);
+
+        // pans and flights carry the target along
+        let eye = controller.eye;
+        controller.pan(0.1, 0.0);
+        controller.fly(2.0, 0.0, 0.0);
+        assert!((controller.distance() - 9.0).abs() < 1e-3);
+        assert!((eye.vec2point(controller.eye).norm() - (0.81f32 + 4.0).sqrt()).abs() < 1e-3);
+    }
+}
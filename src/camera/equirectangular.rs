@@ -0,0 +1,45 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use crate::{
+    camera::{raster_to_ndc, Camera, CameraFrame, CameraSample, Shutter},
+    rays::ray::Ray,
+    utils::Extent2D,
+};
+
+// 360 degree latitude-longitude panorama, the image width spans the longitudes with the
+// viewing direction at its center and the height spans pole to pole, images are
+// usually twice as wide as they are high
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Equirectangular {
+    pub frame: CameraFrame,
+    pub extent: Extent2D,
+    pub shutter: Shutter,
+}
+
+impl Equirectangular {
+    pub fn new(frame: CameraFrame, extent: Extent2D) -> Self {
+        Self {
+            frame,
+            extent,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Camera for Equirectangular {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        if x >= self.extent.width || y >= self.extent.height {
+            return None;
+        }
+        let [xs, ys] = raster_to_ndc(self.extent, x, y, sample.p_film, false);
+        let lon = xs * PI;
+        let lat = ys * FRAC_PI_2;
+        let dir = [lat.cos() * lon.sin(), lat.sin(), -lat.cos() * lon.cos()];
+        let ray = Ray::new(self.frame.eye, self.frame.to_world(dir));
+        Some(ray.with_time(self.shutter.sample_time(sample.time)))
+    }
+
+    fn get_resolution(&self) -> Extent2D {
+        self.extent
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    camera::{raster_to_ndc, Camera, CameraFrame, CameraSample, Shutter},
+    rays::ray::Ray,
+    utils::Extent2D,
+};
+
+// equidistant fisheye, the angle from the viewing direction grows linearly with the
+// distance from the image center; the image circle touches the shorter image side and
+// pixels outside it get no rays
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fisheye {
+    pub frame: CameraFrame,
+    pub extent: Extent2D,
+    // angle across the image circle in radians, up to 2 pi
+    pub fov: f32,
+    pub shutter: Shutter,
+}
+
+impl Fisheye {
+    pub fn new(frame: CameraFrame, extent: Extent2D, fov: f32) -> Self {
+        Self {
+            frame,
+            extent,
+            fov,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Camera for Fisheye {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        if x >= self.extent.width || y >= self.extent.height {
+            return None;
+        }
+        let [xs, ys] = raster_to_ndc(self.extent, x, y, sample.p_film, true);
+        let r = (xs * xs + ys * ys).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = r * self.fov / 2.0;
+        let phi = ys.atan2(xs);
+        let dir = [
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            -theta.cos(),
+        ];
+        let ray = Ray::new(self.frame.eye, self.frame.to_world(dir));
+        Some(ray.with_time(self.shutter.sample_time(sample.time)))
+    }
+
+    fn get_resolution(&self) -> Extent2D {
+        self.extent
+    }
+}
@@ -1,8 +1,413 @@
-use crate::{rays::ray::Ray, utils::Extent2D};
+use std::f32::consts::PI;
 
+use crate::{
+    rays::ray::Ray,
+    samplers::{sample_uniform_disk_concentric, Sampler},
+    utils::{
+        vector::{Point, Vector},
+        Extent2D,
+    },
+};
+
+use self::{
+    equirectangular::Equirectangular, fisheye::Fisheye, orthographic::Orthographic,
+    perspective::Perspective,
+};
+
+pub mod controller;
+pub mod equirectangular;
+pub mod fisheye;
+pub mod orthographic;
 pub mod perspective;
 
+// sample values a camera ray is generated from, PBRT's CameraSample
+#[derive(Debug, Clone, Copy)]
+pub struct CameraSample {
+    // position inside the pixel, (0.5, 0.5) being its center
+    pub p_film: [f32; 2],
+    // uniform sample for the lens, in [0, 1)^2
+    pub p_lens: [f32; 2],
+    // uniform sample for the shutter interval, in [0, 1)
+    pub time: f32,
+}
+
+impl CameraSample {
+    // the film dimension is consumed even without jitter, so the dimensions that follow
+    // do not depend on it
+    pub fn new<S: Sampler>(sampler: &mut S, jitter: bool) -> Self {
+        let p_film = sampler.get_2d();
+        let p_lens = sampler.get_2d();
+        let time = sampler.get_1d();
+        Self {
+            p_film: if jitter { p_film } else { [0.5, 0.5] },
+            p_lens,
+            time,
+        }
+    }
+}
+
+// thin lens model, PBRT's 3rd ed. book, sec 6.2.3
+// rays leave from a point on the lens aperture and pass through the point the pinhole
+// ray would hit on the plane of focus, so only that plane is sharp
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThinLens {
+    // zero for a pinhole
+    pub radius: f32,
+    // distance of the plane of focus along the viewing direction
+    pub focal_distance: f32,
+    // sides of a polygonal aperture, giving polygonal bokeh, below 3 is a circle
+    pub blades: u32,
+    // rotation of the polygon in radians
+    pub rotation: f32,
+}
+
+impl ThinLens {
+    pub fn is_pinhole(&self) -> bool {
+        self.radius <= 0.0
+    }
+
+    // point on the aperture in camera space, z being 0
+    pub fn sample(&self, u: [f32; 2]) -> [f32; 2] {
+        let [x, y] = if self.blades < 3 {
+            sample_uniform_disk_concentric(u)
+        } else {
+            self.sample_polygon(u)
+        };
+        [x * self.radius, y * self.radius]
+    }
+
+    // uniform point in the regular polygon inscribed in the unit circle, the first
+    // dimension picks one of its triangles around the center and is reused inside it
+    fn sample_polygon(&self, u: [f32; 2]) -> [f32; 2] {
+        let n = self.blades as f32;
+        let blade = (u[0] * n).floor().min(n - 1.0);
+        let u0 = u[0] * n - blade;
+        let step = 2.0 * PI / n;
+        let a0 = self.rotation + blade * step;
+        let a1 = a0 + step;
+        // uniform point in the triangle (center, v0, v1)
+        let su = u0.sqrt();
+        let b0 = su * (1.0 - u[1]);
+        let b1 = su * u[1];
+        [b0 * a0.cos() + b1 * a1.cos(), b0 * a0.sin() + b1 * a1.sin()]
+    }
+}
+
 pub trait Camera {
-    fn generate_ray(&self, x: u32, y: u32, cam_jitter: Option<[f32; 2]>) -> Option<Ray>;
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray>;
     fn get_resolution(&self) -> Extent2D;
 }
+
+// orthonormal basis of a camera at eye looking at `at`, camera space has x to the
+// right, y up and the camera looking down -z
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CameraFrame {
+    pub eye: Point,
+    pub right: Vector,
+    pub up: Vector,
+    pub back: Vector,
+}
+
+impl CameraFrame {
+    pub fn look_at(eye: Point, at: Point, up: Vector) -> Self {
+        let mut back: Vector = (eye - at).into();
+        back.normalize();
+        let mut right = up.cross(back);
+        right.normalize();
+        let mut up = back.cross(right);
+        up.normalize();
+        Self {
+            eye,
+            right,
+            up,
+            back,
+        }
+    }
+
+    pub fn to_world(self, v: [f32; 3]) -> Vector {
+        v[0] * self.right + v[1] * self.up + v[2] * self.back
+    }
+}
+
+// position of a film sample in [-1, 1]^2, y pointing up, relative to the image's
+// shorter side when `square` is set so non-square images keep round shapes round
+pub fn raster_to_ndc(extent: Extent2D, x: u32, y: u32, p_film: [f32; 2], square: bool) -> [f32; 2] {
+    let (w, h) = (extent.width as f32, extent.height as f32);
+    let xs = 2.0 * (x as f32 + p_film[0]) / w - 1.0;
+    let ys = 1.0 - 2.0 * (y as f32 + p_film[1]) / h;
+    if square {
+        let side = w.min(h);
+        [xs * w / side, ys * h / side]
+    } else {
+        [xs, ys]
+    }
+}
+
+// interval the shutter stays open, in the time units geometry moves in, which go
+// from 0 to 1 over a frame; every ray samples an instant inside it, a closed shutter
+// takes everything at its opening time
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+}
+
+impl Shutter {
+    pub fn sample_time(&self, u: f32) -> f32 {
+        self.open + u * (self.close - self.open)
+    }
+}
+
+// camera picked at runtime, e.g. from a scene file, built once per render so the
+// size of the perspective transforms does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Copy)]
+pub enum AnyCamera {
+    Perspective(Perspective),
+    Orthographic(Orthographic),
+    Fisheye(Fisheye),
+    Equirectangular(Equirectangular),
+}
+
+impl AnyCamera {
+    pub fn set_shutter(&mut self, shutter: Shutter) {
+        match self {
+            AnyCamera::Perspective(c) => c.shutter = shutter,
+            AnyCamera::Orthographic(c) => c.shutter = shutter,
+            AnyCamera::Fisheye(c) => c.shutter = shutter,
+            AnyCamera::Equirectangular(c) => c.shutter = shutter,
+        }
+    }
+}
+
+impl Camera for AnyCamera {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        match self {
+            AnyCamera::Perspective(c) => c.generate_ray(x, y, sample),
+            AnyCamera::Orthographic(c) => c.generate_ray(x, y, sample),
+            AnyCamera::Fisheye(c) => c.generate_ray(x, y, sample),
+            AnyCamera::Equirectangular(c) => c.generate_ray(x, y, sample),
+        }
+    }
+
+    fn get_resolution(&self) -> Extent2D {
+        match self {
+            AnyCamera::Perspective(c) => c.get_resolution(),
+            AnyCamera::Orthographic(c) => c.get_resolution(),
+            AnyCamera::Fisheye(c) => c.get_resolution(),
+            AnyCamera::Equirectangular(c) => c.get_resolution(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{perspective::FovAxis, *};
+
+    #[test]
+    fn thin_lens_focuses_on_plane() {
+        // round and hexagonal apertures stay inside their shape
+        for blades in [0, 6] {
+            let lens = ThinLens {
+                radius: 2.0,
+                focal_distance: 10.0,
+                blades,
+                rotation: 0.3,
+            };
+            let apothem = 2.0 * (PI / 6.0).cos();
+            for i in 0..64 {
+                let u = [(i % 8) as f32 / 8.0 + 0.06, (i / 8) as f32 / 8.0 + 0.06];
+                let [x, y] = lens.sample(u);
+                let r = (x * x + y * y).sqrt();
+                assert!(r <= 2.0 + 1e-5);
+                if blades == 6 {
+                    // distance to every edge of the hexagon along its normal
+                    for k in 0..6 {
+                        let a = lens.rotation + (k as f32 + 0.5) * PI / 3.0;
+                        assert!(x * a.cos() + y * a.sin() <= apothem + 1e-4);
+                    }
+                }
+            }
+        }
+
+        // every lens sample of a pixel passes through the same point of the focal plane
+        let extent = Extent2D {
+            width: 8,
+            height: 8,
+        };
+        let lens = ThinLens {
+            radius: 0.5,
+            focal_distance: 10.0,
+            blades: 5,
+            rotation: 0.0,
+        };
+        let frame = CameraFrame::look_at(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        let camera = Perspective::new(frame, extent, 1.0, FovAxis::Horizontal, lens);
+        let mut focus_points = Vec::new();
+        for p_lens in [[0.1, 0.2], [0.9, 0.4], [0.5, 0.95]] {
+            let sample = CameraSample {
+                p_film: [0.3, 0.7],
+                p_lens,
+                time: 0.0,
+            };
+            let ray = camera.generate_ray(2, 5, &sample).unwrap();
+            assert!(ray.origin.x != 0.0 || ray.origin.y != 0.0);
+            let t = (-10.0 - ray.origin.z) / ray.direction.z;
+            focus_points.push(ray.origin + t * ray.direction);
+        }
+        for p in &focus_points[1..] {
+            let d: Vector = (*p - focus_points[0]).into();
+            assert!(d.norm() < 1e-3, "{:?}", focus_points);
+        }
+    }
+
+    fn ray_at(camera: &AnyCamera, x: u32, y: u32, p_film: [f32; 2]) -> Option<Ray> {
+        let sample = CameraSample {
+            p_film,
+            p_lens: [0.5, 0.5],
+            time: 0.0,
+        };
+        camera.generate_ray(x, y, &sample)
+    }
+
+    fn assert_dir(ray: Option<Ray>, expected: Vector) {
+        let d = ray.expect("no ray").direction;
+        assert!((d - expected).norm() < 1e-4, "{:?} != {:?}", d, expected);
+    }
+
+    #[test]
+    fn panoramic_and_orthographic_cameras_map_corners() {
+        // looking down +x with z up, so right is -y
+        let frame = CameraFrame::look_at(
+            Point::new(1.0, 2.0, 3.0),
+            Point::new(5.0, 2.0, 3.0),
+            Vector::new(0.0, 0.0, 1.0),
+        );
+        let forward = Vector::new(1.0, 0.0, 0.0);
+        let right = Vector::new(0.0, -1.0, 0.0);
+        let up = Vector::new(0.0, 0.0, 1.0);
+        let extent = Extent2D {
+            width: 8,
+            height: 4,
+        };
+
+        // rays parallel to the view direction, starting on a 4x2 view plane
+        let ortho = AnyCamera::Orthographic(Orthographic::new(frame, extent, 2.0));
+        let corner = ray_at(&ortho, 0, 0, [0.0, 0.0]).unwrap();
+        assert_dir(Some(corner), forward);
+        let expected = frame.eye + (-2.0 * right + up);
+        assert!(corner.origin.vec2point(expected).norm() < 1e-4);
+        let center = ray_at(&ortho, 4, 2, [0.0, 0.0]).unwrap();
+        assert!(center.origin.vec2point(frame.eye).norm() < 1e-4);
+
+        // 180 degree fisheye, the circle edge on the shorter side looks sideways
+        let fisheye = AnyCamera::Fisheye(Fisheye::new(frame, extent, PI));
+        assert_dir(ray_at(&fisheye, 4, 2, [0.0, 0.0]), forward);
+        assert_dir(ray_at(&fisheye, 4, 0, [0.0, 0.0]), up);
+        assert_dir(ray_at(&fisheye, 2, 2, [0.0, 0.0]), -1.0 * right);
+        assert!(ray_at(&fisheye, 0, 0, [0.0, 0.0]).is_none());
+        assert!(ray_at(&fisheye, 7, 3, [0.99, 0.99]).is_none());
+
+        // lat-long panorama, the vertical edges look backwards and the top at the pole
+        let equirect = AnyCamera::Equirectangular(Equirectangular::new(frame, extent));
+        assert_dir(ray_at(&equirect, 4, 2, [0.0, 0.0]), forward);
+        assert_dir(ray_at(&equirect, 0, 2, [0.0, 0.0]), -1.0 * forward);
+        assert_dir(ray_at(&equirect, 6, 2, [0.0, 0.0]), right);
+        assert_dir(ray_at(&equirect, 2, 2, [0.0, 0.0]), -1.0 * right);
+        assert_dir(ray_at(&equirect, 0, 0, [0.0, 0.0]), up);
+        assert_dir(ray_at(&equirect, 7, 3, [1.0, 1.0]), -1.0 * up);
+
+        // every camera rejects pixels outside the image
+        for camera in [ortho, fisheye, equirect] {
+            assert!(ray_at(&camera, 8, 0, [0.5, 0.5]).is_none());
+            assert_eq!(camera.get_resolution().width, 8);
+        }
+    }
+
+    #[test]
+    fn perspective_round_trips_raster_positions() {
+        let frame = CameraFrame::look_at(
+            Point::new(3.0, -1.0, 2.0),
+            Point::new(-2.0, 4.0, 7.0),
+            Vector::new(0.2, 1.0, 0.1),
+        );
+        let extent = Extent2D {
+            width: 320,
+            height: 180,
+        };
+        for axis in [FovAxis::Horizontal, FovAxis::Vertical] {
+            let camera = Perspective::new(frame, extent, 1.2, axis, ThinLens::default());
+            for (x, y, p_film) in [
+                (0, 0, [0.1, 0.2]),
+                (160, 90, [0.5, 0.5]),
+                (319, 17, [0.9, 0.3]),
+            ] {
+                let ray = camera
+                    .generate_ray(
+                        x,
+                        y,
+                        &CameraSample {
+                            p_film,
+                            p_lens: [0.5, 0.5],
+                            time: 0.0,
+                        },
+                    )
+                    .unwrap();
+                let p = camera
+                    .world_to_raster(ray.origin + 25.0 * ray.direction)
+                    .unwrap();
+                assert!((p[0] - x as f32 - p_film[0]).abs() < 1e-2, "{:?}", p);
+                assert!((p[1] - y as f32 - p_film[1]).abs() < 1e-2, "{:?}", p);
+            }
+            assert!(camera.world_to_raster(frame.eye + frame.back).is_none());
+        }
+
+        // the fov spans its axis whatever the aspect ratio, pixels staying square
+        let frame = CameraFrame::look_at(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        let fov = std::f32::consts::FRAC_PI_2;
+        let edge_slope = |camera: &Perspective, x: u32, y: u32| {
+            let d = camera
+                .generate_ray(
+                    x,
+                    y,
+                    &CameraSample {
+                        p_film: [0.0, 0.0],
+                        p_lens: [0.5, 0.5],
+                        time: 0.0,
+                    },
+                )
+                .unwrap()
+                .direction;
+            [d.x / -d.z, d.y / -d.z]
+        };
+        let wide = Extent2D {
+            width: 200,
+            height: 100,
+        };
+        let horizontal =
+            Perspective::new(frame, wide, fov, FovAxis::Horizontal, ThinLens::default());
+        let [sx, sy] = edge_slope(&horizontal, 0, 0);
+        assert!(
+            (sx + 1.0).abs() < 1e-4 && (sy - 0.5).abs() < 1e-4,
+            "{} {}",
+            sx,
+            sy
+        );
+        let vertical = Perspective::new(frame, wide, fov, FovAxis::Vertical, ThinLens::default());
+        let [sx, sy] = edge_slope(&vertical, 0, 0);
+        assert!(
+            (sx + 2.0).abs() < 1e-4 && (sy - 1.0).abs() < 1e-4,
+            "{} {}",
+            sx,
+            sy
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{
+    camera::{raster_to_ndc, Camera, CameraFrame, CameraSample, Shutter},
+    rays::ray::Ray,
+    utils::Extent2D,
+};
+
+// parallel projection, rays leave from a view plane through the eye and all travel
+// along the viewing direction
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Orthographic {
+    pub frame: CameraFrame,
+    pub extent: Extent2D,
+    // size of the view plane in scene units
+    pub view_width: f32,
+    pub view_height: f32,
+    pub shutter: Shutter,
+}
+
+impl Orthographic {
+    // the view plane width follows the image's aspect ratio
+    pub fn new(frame: CameraFrame, extent: Extent2D, view_height: f32) -> Self {
+        Self {
+            frame,
+            extent,
+            view_width: view_height * extent.width as f32 / extent.height as f32,
+            view_height,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Camera for Orthographic {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        if x >= self.extent.width || y >= self.extent.height {
+            return None;
+        }
+        let [xs, ys] = raster_to_ndc(self.extent, x, y, sample.p_film, false);
+        let offset = [xs * self.view_width / 2.0, ys * self.view_height / 2.0, 0.0];
+        let origin = self.frame.eye + self.frame.to_world(offset);
+        let ray = Ray::new(origin, self.frame.to_world([0.0, 0.0, -1.0]));
+        Some(ray.with_time(self.shutter.sample_time(sample.time)))
+    }
+
+    fn get_resolution(&self) -> Extent2D {
+        self.extent
+    }
+}
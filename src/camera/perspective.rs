@@ -1,83 +1,119 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    camera::Camera,
+    camera::{Camera, CameraFrame, CameraSample, Shutter, ThinLens},
     rays::ray::Ray,
     utils::{
+        transform::Transform,
         vector::{Point, Vector},
         Extent2D,
     },
 };
 
+// based on PBRT's 3rd ed. book, sec 6.2
+// the camera looks down -z, raster space has its origin at the top left corner of the
+// image with y pointing down
+
+// image axis the field of view spans, the other one follows from the aspect ratio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FovAxis {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+const NEAR: f32 = 1e-2;
+const FAR: f32 = 1000.0;
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Perspective {
-    eye: Point,
-    window_extent: Extent2D,
-    fov_width: f32,
-    fov_height: f32,
-    c2w: [[f32; 3]; 3],
+    pub frame: CameraFrame,
+    pub extent: Extent2D,
+    pub world_from_camera: Transform,
+    pub camera_from_raster: Transform,
+    pub lens: ThinLens,
+    pub shutter: Shutter,
 }
 
 impl Perspective {
+    // fov in radians
     pub fn new(
-        eye: Point,
-        at: Point,
-        up: Vector,
+        frame: CameraFrame,
         extent: Extent2D,
-        fov_width: f32,
-        fov_height: f32,
+        fov: f32,
+        fov_axis: FovAxis,
+        lens: ThinLens,
     ) -> Self {
-        let mut f: Vector = (eye - at).into();
-        f.normalize();
-
-        let mut r = up.cross(f);
-        r.normalize();
-
-        let mut up = f.cross(r);
-        up.normalize();
+        let (w, h) = (extent.width as f32, extent.height as f32);
+        let tan_half = (fov / 2.0).tan();
+        let (tan_x, tan_y) = match fov_axis {
+            FovAxis::Horizontal => (tan_half, tan_half * h / w),
+            FovAxis::Vertical => (tan_half * w / h, tan_half),
+        };
+        // screen space spans [-1, 1] over both image axes
+        let screen_from_camera =
+            Transform::scale(1.0 / tan_x, 1.0 / tan_y, 1.0) * Transform::perspective(NEAR, FAR);
+        let raster_from_screen = Transform::scale(w / 2.0, -h / 2.0, 1.0)
+            * Transform::translate(Vector::new(1.0, -1.0, 0.0));
+        let raster_from_camera = raster_from_screen * screen_from_camera;
 
         Self {
-            eye,
-            window_extent: extent,
-            fov_width,
-            fov_height,
-            c2w: [[r.x, r.y, r.z], [up.x, up.y, up.z], [f.x, f.y, f.z]],
+            frame,
+            extent,
+            world_from_camera: Transform::from_frame(frame.eye, frame.right, frame.up, frame.back),
+            camera_from_raster: raster_from_camera.inverse(),
+            lens,
+            shutter: Shutter::default(),
         }
     }
+
+    // raster position a world point projects to through the lens center, None when
+    // it lies behind the camera or outside the image, for light tracing and splatting
+    // samples onto the film
+    #[allow(dead_code)]
+    pub fn world_to_raster(&self, p: Point) -> Option<[f32; 2]> {
+        let p_camera = self.world_from_camera.inverse().apply_point(p);
+        if p_camera.z >= 0.0 {
+            return None;
+        }
+        let p_raster = self.camera_from_raster.inverse().apply_point(p_camera);
+        let inside = (0.0..self.extent.width as f32).contains(&p_raster.x)
+            && (0.0..self.extent.height as f32).contains(&p_raster.y);
+        inside.then_some([p_raster.x, p_raster.y])
+    }
 }
 
 impl Camera for Perspective {
-    fn generate_ray(&self, x: u32, y: u32, _cam_jitter: Option<[f32; 2]>) -> Option<Ray> {
-        if x >= self.window_extent.width || y >= self.window_extent.height {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        if x >= self.extent.width || y >= self.extent.height {
             return None;
         }
 
-        let (jitter_x, jitter_y) = match _cam_jitter {
-            Some(jitter) => (jitter[0], jitter[1]),
-            None => (0.5, 0.5),
-        };
-
-        let xs = (2.0 * (x as f32 + jitter_x) / self.window_extent.width as f32) - 1.0;
-        let ys = 2.0 * ((self.window_extent.height - y - 1) as f32 + jitter_y)
-            / self.window_extent.height as f32
-            - 1.0;
-
-        let xc = xs * (self.fov_width / 2.0).tan();
-        let yc = ys * (self.fov_height / 2.0).tan();
+        let p_raster = Point::new(
+            x as f32 + sample.p_film[0],
+            y as f32 + sample.p_film[1],
+            0.0,
+        );
+        let dir: Vector = self.camera_from_raster.apply_point(p_raster).into();
+        let time = self.shutter.sample_time(sample.time);
 
-        let mut dir: [f32; 3] = [0.0, 0.0, 0.0];
-        let coords = [xc, yc, -1.0];
-
-        for i in 0..self.c2w.len() {
-            for j in 0..self.c2w[i].len() {
-                dir[i] += self.c2w[i][j] * coords[j];
-            }
+        if self.lens.is_pinhole() {
+            let ray = Ray::new(self.frame.eye, self.world_from_camera.apply_vector(dir));
+            return Some(ray.with_time(time));
         }
-        let direction = Vector::new(dir[0], dir[1], dir[2]);
-
-        let ray = Ray::new(self.eye, direction);
-
-        Some(ray)
+        // the pinhole ray reaches the plane of focus at focal_distance along -z
+        let [lx, ly] = self.lens.sample(sample.p_lens);
+        let p_focus = dir * (self.lens.focal_distance / -dir.z);
+        let ray = Ray::new(
+            self.world_from_camera.apply_point(Point::new(lx, ly, 0.0)),
+            self.world_from_camera
+                .apply_vector(p_focus - Vector::new(lx, ly, 0.0)),
+        );
+        Some(ray.with_time(time))
     }
+
     fn get_resolution(&self) -> Extent2D {
-        self.window_extent
+        self.extent
     }
 }
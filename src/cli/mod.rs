@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+usage:
+    vi_renderer [view] [SCENE]
+        open the progressive viewer window (default scene: ./models/cornell_box_VI.toml)
+
+    vi_renderer render SCENE -o OUTPUT [options]
+        render without a window and save the image, the format follows the
+        extension of OUTPUT: .ppm (tonemapped, 8 bit), .pfm (linear, float) or
+        .hdr (linear, Radiance RGBE)
+
+options:
+    -o, --output PATH     image to write
+    -W, --width N         override the scene's image width
+    -H, --height N        override the scene's image height
+    -s, --spp N           override the scene's samples per pixel
+        --shader NAME     ambient | whitted | distributed | path_tracer
+        --sampler NAME    independent | stratified | halton | sobol
+        --light-sampler NAME
+                          uniform | power | bvh
+        --renderer NAME   incremental | parallel | standard, the same image; only
+                          incremental supports adaptive sampling and --heatmap
+        --seed N          override the scene's random seed
+        --heatmap PATH    also save the samples taken per pixel, .ppm, .pfm or .hdr
+    -q, --quiet           do not print progress
+    -h, --help            print this message";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderArgs {
+    pub scene: PathBuf,
+    pub output: PathBuf,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub spp: Option<u32>,
+    pub shader: Option<String>,
+    pub sampler: Option<String>,
+    pub light_sampler: Option<String>,
+    pub renderer: Option<String>,
+    pub seed: Option<u64>,
+    pub heatmap: Option<PathBuf>,
+    pub quiet: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    View { scene: PathBuf },
+    Render(RenderArgs),
+    Help,
+}
+
+const DEFAULT_SCENE: &str = "./models/cornell_box_VI.toml";
+
+// parses the arguments after the program name
+pub fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+
+    match args.peek().map(|a| a.as_str()) {
+        None => {
+            return Ok(Command::View {
+                scene: PathBuf::from(DEFAULT_SCENE),
+            })
+        }
+        Some("-h") | Some("--help") | Some("help") => return Ok(Command::Help),
+        Some("view") => {
+            args.next();
+            let scene = args.next().unwrap_or_else(|| DEFAULT_SCENE.to_string());
+            if let Some(extra) = args.next() {
+                return Err(format!("unexpected argument {:?}", extra));
+            }
+            return Ok(Command::View {
+                scene: PathBuf::from(scene),
+            });
+        }
+        Some("render") => {
+            args.next();
+        }
+        Some(_) => {
+            let scene = args.next().unwrap();
+            if let Some(extra) = args.next() {
+                return Err(format!("unexpected argument {:?}", extra));
+            }
+            return Ok(Command::View {
+                scene: PathBuf::from(scene),
+            });
+        }
+    }
+
+    let mut scene: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut width = None;
+    let mut height = None;
+    let mut spp = None;
+    let mut shader = None;
+    let mut sampler = None;
+    let mut light_sampler = None;
+    let mut renderer = None;
+    let mut seed = None;
+    let mut heatmap = None;
+    let mut quiet = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<String, String> {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "-W" | "--width" => width = Some(parse_positive(&arg, &value(&arg)?)?),
+            "-H" | "--height" => height = Some(parse_positive(&arg, &value(&arg)?)?),
+            "-s" | "--spp" => spp = Some(parse_positive(&arg, &value(&arg)?)?),
+            "--shader" => shader = Some(value(&arg)?),
+            "--sampler" => sampler = Some(value(&arg)?),
+            "--light-sampler" => light_sampler = Some(value(&arg)?),
+            "--renderer" => renderer = Some(value(&arg)?),
+            "--heatmap" => heatmap = Some(PathBuf::from(value(&arg)?)),
+            "--seed" => seed = Some(parse_seed(&arg, &value(&arg)?)?),
+            "-q" | "--quiet" => quiet = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {:?}", arg)),
+            _ if scene.is_none() => scene = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+
+    Ok(Command::Render(RenderArgs {
+        scene: scene.ok_or("render: missing scene file")?,
+        output: output.ok_or("render: missing output path (-o)")?,
+        width,
+        height,
+        spp,
+        shader,
+        sampler,
+        light_sampler,
+        renderer,
+        seed,
+        heatmap,
+        quiet,
+    }))
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(v) if v > 0 => Ok(v),
+        _ => Err(format!(
+            "{} expects a positive integer, got {:?}",
+            name, value
+        )),
+    }
+}
+
+fn parse_seed(name: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("{} expects a non-negative integer, got {:?}", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{parse_args, Command, RenderArgs};
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn cli_parse() {
+        assert!(matches!(parse_args(args("")), Ok(Command::View { .. })));
+        assert_eq!(
+            parse_args(args("a.toml")),
+            Ok(Command::View {
+                scene: PathBuf::from("a.toml")
+            })
+        );
+        assert_eq!(
+            parse_args(args(
+                "render a.toml -o out.pfm --spp 16 -W 320 -H 240 --shader whitted --sampler halton --light-sampler power --renderer parallel --seed 0 --heatmap h.ppm -q"
+            )),
+            Ok(Command::Render(RenderArgs {
+                scene: PathBuf::from("a.toml"),
+                output: PathBuf::from("out.pfm"),
+                width: Some(320),
+                height: Some(240),
+                spp: Some(16),
+                shader: Some("whitted".to_string()),
+                sampler: Some("halton".to_string()),
+                light_sampler: Some("power".to_string()),
+                renderer: Some("parallel".to_string()),
+                seed: Some(0),
+                heatmap: Some(PathBuf::from("h.ppm")),
+                quiet: true,
+            }))
+        );
+
+        assert!(parse_args(args("render a.toml")).is_err());
+        assert!(parse_args(args("render -o out.ppm")).is_err());
+        assert!(parse_args(args("render a.toml -o out.ppm --spp 0")).is_err());
+        assert!(parse_args(args("render a.toml -o out.ppm --spp")).is_err());
+        assert!(parse_args(args("render a.toml -o out.ppm --fast")).is_err());
+        assert!(parse_args(args("render a.toml -o out.ppm --seed -1")).is_err());
+    }
+}
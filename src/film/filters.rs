@@ -0,0 +1,174 @@
+use std::f32::consts::PI;
+
+// pixel reconstruction filters, PBRT's 3rd ed. book, sec 7.8
+// every filter is separable and evaluated at the offset between a sample and a pixel
+// center, in pixels; it is zero outside [-radius, radius)
+pub trait Filter {
+    fn radius(&self) -> f32;
+    fn eval(&self, x: f32, y: f32) -> f32;
+}
+
+// the default, radius 0.5 averages the samples inside each pixel
+#[derive(Debug, Clone, Copy)]
+pub struct BoxFilter {
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TentFilter {
+    pub radius: f32,
+}
+
+// shifted down so it reaches zero at the radius
+#[derive(Debug, Clone, Copy)]
+pub struct GaussianFilter {
+    pub radius: f32,
+    pub sigma: f32,
+}
+
+// Mitchell and Netravali's cubic, (b, c) = (1/3, 1/3) is their recommendation
+#[derive(Debug, Clone, Copy)]
+pub struct MitchellFilter {
+    pub radius: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+// sinc windowed by a wider sinc, with as many lobes as pixels of radius
+#[derive(Debug, Clone, Copy)]
+pub struct LanczosFilter {
+    pub radius: f32,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, _x: f32, _y: f32) -> f32 {
+        1.0
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+impl GaussianFilter {
+    fn gaussian_1d(&self, x: f32) -> f32 {
+        let g = |x: f32| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.gaussian_1d(x) * self.gaussian_1d(y)
+    }
+}
+
+impl MitchellFilter {
+    // the cubic is defined over [-2, 2]
+    fn mitchell_1d(&self, x: f32) -> f32 {
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        let v = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        };
+        v / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.mitchell_1d(x) * self.mitchell_1d(y)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl LanczosFilter {
+    fn lanczos_1d(&self, x: f32) -> f32 {
+        if x.abs() >= self.radius {
+            return 0.0;
+        }
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.lanczos_1d(x) * self.lanczos_1d(y)
+    }
+}
+
+// filter picked at runtime, e.g. from a scene file
+#[derive(Debug, Clone, Copy)]
+pub enum AnyFilter {
+    Box(BoxFilter),
+    Tent(TentFilter),
+    Gaussian(GaussianFilter),
+    Mitchell(MitchellFilter),
+    Lanczos(LanczosFilter),
+}
+
+impl Default for AnyFilter {
+    fn default() -> Self {
+        AnyFilter::Box(BoxFilter { radius: 0.5 })
+    }
+}
+
+impl Filter for AnyFilter {
+    fn radius(&self) -> f32 {
+        match self {
+            AnyFilter::Box(f) => f.radius(),
+            AnyFilter::Tent(f) => f.radius(),
+            AnyFilter::Gaussian(f) => f.radius(),
+            AnyFilter::Mitchell(f) => f.radius(),
+            AnyFilter::Lanczos(f) => f.radius(),
+        }
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        match self {
+            AnyFilter::Box(f) => f.eval(x, y),
+            AnyFilter::Tent(f) => f.eval(x, y),
+            AnyFilter::Gaussian(f) => f.eval(x, y),
+            AnyFilter::Mitchell(f) => f.eval(x, y),
+            AnyFilter::Lanczos(f) => f.eval(x, y),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{images::image_rgb::ImageRGB, utils::rgb::RGB};
+
+use self::filters::{AnyFilter, Filter};
+
+pub mod filters;
+
+// based on PBRT's 3rd ed. book, sec 7.9
+// each pixel keeps the filter weighted sum of the radiance samples around it plus the
+// sum of the weights, the final value being their ratio
+
+// radiance carried by a camera ray, p_film being the ray's position inside its pixel
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilmSample {
+    pub p_film: [f32; 2],
+    pub l: RGB,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Film {
+    pub filter: AnyFilter,
+    pub sums: ImageRGB,
+    pub weights: Vec<f32>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: AnyFilter) -> Self {
+        Self {
+            filter,
+            sums: ImageRGB::new(width, height),
+            weights: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.sums.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.sums.height
+    }
+
+    // splats one sample per pixel, None for pixels without a sample
+    // every pixel gathers the samples of its neighbours in a fixed order instead of
+    // samples scattering into their neighbours, so threads never write the same pixel
+    // and the sums do not depend on scheduling
+    pub fn add_samples(&mut self, samples: &[Option<FilmSample>]) {
+        assert_eq!(samples.len(), self.weights.len());
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        let filter = self.filter;
+        let radius = filter.radius();
+        // farthest neighbour whose samples can reach a pixel center
+        let reach = (radius + 0.5).ceil() as i32;
+
+        self.sums
+            .data
+            .par_iter_mut()
+            .zip(self.weights.par_iter_mut())
+            .enumerate()
+            .for_each(|(i, (sum, weight))| {
+                let px = i as i32 % width;
+                let py = i as i32 / width;
+                for qy in (py - reach).max(0)..=(py + reach).min(height - 1) {
+                    for qx in (px - reach).max(0)..=(px + reach).min(width - 1) {
+                        let Some(s) = samples[(qy * width + qx) as usize] else {
+                            continue;
+                        };
+                        // sample offset from the pixel center
+                        let dx = (qx - px) as f32 + s.p_film[0] - 0.5;
+                        let dy = (qy - py) as f32 + s.p_film[1] - 0.5;
+                        if !(-radius..radius).contains(&dx) || !(-radius..radius).contains(&dy) {
+                            continue;
+                        }
+                        let w = filter.eval(dx, dy);
+                        if w != 0.0 {
+                            *sum += s.l * w;
+                            *weight += w;
+                        }
+                    }
+                }
+            });
+    }
+
+    // weighted averages, pixels no sample reached stay black
+    pub fn write_image(&self, image: &mut ImageRGB) {
+        assert_eq!(image.data.len(), self.weights.len());
+        for ((rgb, sum), &weight) in image
+            .data
+            .iter_mut()
+            .zip(self.sums.data.iter())
+            .zip(self.weights.iter())
+        {
+            *rgb = if weight > 0.0 {
+                let mut color = *sum;
+                color /= weight;
+                color
+            } else {
+                RGB::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        filters::{BoxFilter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter},
+        *,
+    };
+
+    #[test]
+    fn filters_reconstruct_constant_film() {
+        let filters = [
+            AnyFilter::default(),
+            AnyFilter::Box(BoxFilter { radius: 1.0 }),
+            AnyFilter::Tent(TentFilter { radius: 1.5 }),
+            AnyFilter::Gaussian(GaussianFilter {
+                radius: 1.5,
+                sigma: 0.5,
+            }),
+            AnyFilter::Mitchell(MitchellFilter {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            AnyFilter::Lanczos(LanczosFilter { radius: 2.0 }),
+        ];
+        for filter in filters {
+            assert!(filter.eval(0.0, 0.0) > 0.0);
+            // continuous filters fade out towards the radius
+            if !matches!(filter, AnyFilter::Box(_)) {
+                assert!(
+                    filter.eval(filter.radius(), 0.0).abs() < 1e-6,
+                    "{:?}",
+                    filter
+                );
+            }
+
+            // normalization by the weights leaves a uniformly lit film unchanged
+            let mut film = Film::new(8, 6, filter);
+            for k in 0..4 {
+                let off = 0.125 + 0.25 * k as f32;
+                let samples: Vec<_> = (0..48)
+                    .map(|_| {
+                        Some(FilmSample {
+                            p_film: [off, 1.0 - off],
+                            l: RGB::new(2.0, 1.0, 0.5),
+                        })
+                    })
+                    .collect();
+                film.add_samples(&samples);
+            }
+            let mut image = ImageRGB::new(8, 6);
+            film.write_image(&mut image);
+            for c in image.data.iter() {
+                assert!(
+                    (c.r - 2.0).abs() < 1e-4 && (c.b - 0.5).abs() < 1e-4,
+                    "{:?}",
+                    filter
+                );
+            }
+        }
+
+        // a wide filter spreads a single sample over its neighbours
+        let mut film = Film::new(3, 3, AnyFilter::Tent(TentFilter { radius: 1.5 }));
+        let mut samples = vec![None; 9];
+        samples[4] = Some(FilmSample {
+            p_film: [0.5, 0.5],
+            l: RGB::new(1.0, 1.0, 1.0),
+        });
+        film.add_samples(&samples);
+        assert!(film.weights.iter().all(|&w| w > 0.0));
+        assert!(film.weights[4] > film.weights[0]);
+    }
+}
@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::utils::rgb::RGB;
+
+use super::image_rgb::ImageRGB;
+
+// Radiance RGBE image, see https://www.graphics.cornell.edu/~bjw/rgbe.html
+// a text header ending in an empty line, the resolution line and then the scanlines
+// top to bottom, each pixel an 8 bit mantissa per channel sharing one exponent
+// scanlines are either flat, run-length encoded per channel or in the old format
+// that repeats the previous pixel; only the usual -Y height +X width layout is read
+
+pub fn load(path: &Path) -> io::Result<ImageRGB> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+    let mut pos = 0;
+    let mut next_line = || -> io::Result<String> {
+        let len = bytes[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid_data("unexpected end of HDR header".to_string()))?;
+        let line = String::from_utf8_lossy(&bytes[pos..pos + len]).into_owned();
+        pos += len + 1;
+        Ok(line)
+    };
+
+    let magic = next_line()?;
+    if !magic.starts_with("#?") {
+        return Err(invalid_data(format!(
+            "unsupported HDR magic number {:?}",
+            magic
+        )));
+    }
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data(format!("unsupported HDR format {:?}", format)));
+            }
+        }
+    }
+    let resolution = next_line()?;
+    let tokens: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match tokens[..] {
+        ["-Y", h, "+X", w] => (h, w),
+        _ => {
+            return Err(invalid_data(format!(
+                "unsupported HDR resolution {:?}",
+                resolution
+            )))
+        }
+    };
+    let width = parse_token::<u32>(width, "width")?;
+    let height = parse_token::<u32>(height, "height")?;
+
+    let mut data = &bytes[pos..];
+    let mut image = ImageRGB::new(width, height);
+    let mut scanline = vec![[0u8; 4]; width as usize];
+    for y in 0..height {
+        read_scanline(&mut data, &mut scanline)?;
+        for (x, rgbe) in scanline.iter().enumerate() {
+            image.set(x as u32, y, &rgbe_to_rgb(*rgbe));
+        }
+    }
+    Ok(image)
+}
+
+pub fn save(image: &ImageRGB, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(
+        file,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height, image.width
+    )?;
+
+    let width = image.width as usize;
+    for y in 0..image.height {
+        let row = &image.data[y as usize * width..(y as usize + 1) * width];
+        let rgbe: Vec<[u8; 4]> = row.iter().map(rgb_to_rgbe).collect();
+        // run-length encoding can only describe scanlines of this width
+        if !(8..0x8000).contains(&width) {
+            for px in &rgbe {
+                file.write_all(px)?;
+            }
+            continue;
+        }
+        file.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for c in 0..4 {
+            let channel: Vec<u8> = rgbe.iter().map(|px| px[c]).collect();
+            write_rle(&mut file, &channel)?;
+        }
+    }
+    file.flush()
+}
+
+fn read_scanline(data: &mut &[u8], scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut take = |n: usize| -> io::Result<&[u8]> {
+        if data.len() < n {
+            return Err(invalid_data("HDR raster is truncated".to_string()));
+        }
+        let (head, tail) = data.split_at(n);
+        *data = tail;
+        Ok(head)
+    };
+
+    let head = take(4)?;
+    let rle = (8..0x8000).contains(&width)
+        && head[0] == 2
+        && head[1] == 2
+        && head[2] & 0x80 == 0
+        && ((head[2] as usize) << 8 | head[3] as usize) == width;
+    if rle {
+        for c in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = take(1)?[0] as usize;
+                let (run, count) = if count > 128 {
+                    (true, count - 128)
+                } else {
+                    (false, count)
+                };
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("bad HDR scanline run".to_string()));
+                }
+                if run {
+                    let value = take(1)?[0];
+                    scanline[x..x + count]
+                        .iter_mut()
+                        .for_each(|px| px[c] = value);
+                } else {
+                    for (px, &value) in scanline[x..x + count].iter_mut().zip(take(count)?) {
+                        px[c] = value;
+                    }
+                }
+                x += count;
+            }
+        }
+        return Ok(());
+    }
+
+    // flat pixels, where 1 1 1 e repeats the previous pixel, consecutive repeats
+    // counting in higher bytes
+    let mut px = [head[0], head[1], head[2], head[3]];
+    let (mut x, mut shift) = (0, 0);
+    loop {
+        if px[0] == 1 && px[1] == 1 && px[2] == 1 {
+            let count = (px[3] as usize) << shift;
+            if x == 0 || x + count > width {
+                return Err(invalid_data("bad HDR scanline run".to_string()));
+            }
+            let prev = scanline[x - 1];
+            scanline[x..x + count].fill(prev);
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = px;
+            x += 1;
+            shift = 0;
+        }
+        if x == width {
+            return Ok(());
+        }
+        let next = take(4)?;
+        px = [next[0], next[1], next[2], next[3]];
+    }
+}
+
+// runs of at least 4 equal bytes are stored as a run, anything else as literals
+fn write_rle<W: Write>(file: &mut W, channel: &[u8]) -> io::Result<()> {
+    let mut x = 0;
+    while x < channel.len() {
+        let run = channel[x..]
+            .iter()
+            .take(127)
+            .take_while(|&&b| b == channel[x])
+            .count();
+        if run >= 4 {
+            file.write_all(&[128 + run as u8, channel[x]])?;
+            x += run;
+            continue;
+        }
+        // literals up to the start of the next long run
+        let mut end = x;
+        while end < channel.len() && end - x < 128 {
+            let ahead = &channel[end..channel.len().min(end + 4)];
+            if ahead.len() == 4 && ahead.iter().all(|&b| b == ahead[0]) {
+                break;
+            }
+            end += 1;
+        }
+        file.write_all(&[(end - x) as u8])?;
+        file.write_all(&channel[x..end])?;
+        x = end;
+    }
+    Ok(())
+}
+
+fn rgbe_to_rgb(px: [u8; 4]) -> RGB {
+    if px[3] == 0 {
+        return RGB::default();
+    }
+    let f = 2f32.powi(px[3] as i32 - (128 + 8));
+    RGB::new(px[0] as f32 * f, px[1] as f32 * f, px[2] as f32 * f)
+}
+
+fn rgb_to_rgbe(rgb: &RGB) -> [u8; 4] {
+    let v = rgb.r.max(rgb.g).max(rgb.b);
+    if v.is_nan() || v < 1e-32 {
+        return [0; 4];
+    }
+    // v = m 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f32.powi(e) >= 1.0 {
+        e += 1;
+    } else if v / 2f32.powi(e) < 0.5 {
+        e -= 1;
+    }
+    let e = e.clamp(-127, 127);
+    let scale = 256.0 / 2f32.powi(e);
+    let mantissa = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        mantissa(rgb.r),
+        mantissa(rgb.g),
+        mantissa(rgb.b),
+        (e + 128) as u8,
+    ]
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_token<T: std::str::FromStr>(token: &str, what: &str) -> io::Result<T> {
+    token
+        .parse::<T>()
+        .map_err(|_| invalid_data(format!("invalid HDR {}: {:?}", what, token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        images::{image_rgb::ImageRGB, load_image},
+        utils::rgb::RGB,
+    };
+
+    #[test]
+    fn hdr_roundtrip() {
+        // wide enough for run-length encoded scanlines, with runs and literals
+        let mut image = ImageRGB::new(20, 3);
+        for x in 0..20 {
+            image.set(x, 0, &RGB::new(1000.0, 0.5, 0.0));
+            image.set(x, 1, &RGB::new(x as f32 * 0.1, 1.0, 2.0 + (x % 3) as f32));
+        }
+        image.set(3, 2, &RGB::new(0.25, 6.0e4, 1e-3));
+
+        let path = std::env::temp_dir().join("vi_renderer_roundtrip.hdr");
+        super::save(&image, &path).unwrap();
+        let hdr = load_image(&path).unwrap();
+        assert_eq!((hdr.width, hdr.height), (20, 3));
+        for (px, orig) in hdr.data.iter().zip(&image.data) {
+            // 8 bit mantissas relative to the largest channel
+            let tol = orig.r.max(orig.g).max(orig.b) / 128.0;
+            assert!((px.r - orig.r).abs() <= tol, "{:?} {:?}", px, orig);
+            assert!((px.g - orig.g).abs() <= tol, "{:?} {:?}", px, orig);
+            assert!((px.b - orig.b).abs() <= tol, "{:?} {:?}", px, orig);
+        }
+
+        // a flat scanline repeating its first pixel in the old run format
+        let mut bytes = b"#?RGBE\n\n-Y 1 +X 4\n".to_vec();
+        bytes.extend_from_slice(&[128, 64, 32, 129, 1, 1, 1, 3]);
+        std::fs::write(&path, bytes).unwrap();
+        let old = load_image(&path).unwrap();
+        assert_eq!(old.width, 4);
+        for px in &old.data {
+            assert_eq!((px.r, px.g, px.b), (1.0, 0.5, 0.25));
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
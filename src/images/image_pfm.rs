@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::utils::rgb::RGB;
+
+use super::image_rgb::ImageRGB;
+
+// Portable float map, see https://www.pauldebevec.com/Research/HDR/PFM/
+// scanlines are stored bottom to top, a negative scale means little endian samples
+
+pub fn load(path: &Path) -> io::Result<ImageRGB> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+    let mut pos = 0;
+    let mut next_token = || -> io::Result<String> {
+        while bytes.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        let start = pos;
+        while bytes.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(invalid_data("unexpected end of PFM header".to_string()));
+        }
+        Ok(String::from_utf8_lossy(&bytes[start..pos]).into_owned())
+    };
+
+    let magic = next_token()?;
+    let channels = match magic.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => {
+            return Err(invalid_data(format!(
+                "unsupported PFM magic number {:?}",
+                magic
+            )))
+        }
+    };
+    let width = parse_token::<u32>(&next_token()?, "width")?;
+    let height = parse_token::<u32>(&next_token()?, "height")?;
+    let scale = parse_token::<f32>(&next_token()?, "scale")?;
+    let little_endian = scale < 0.0;
+    // single whitespace before the raster
+    pos += 1;
+
+    // sizes come from the file, a hostile header must not overflow them
+    let raster_end = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(channels * 4))
+        .and_then(|n| n.checked_add(pos))
+        .ok_or_else(|| invalid_data(format!("PFM size {}x{} is too large", width, height)))?;
+    let raster = bytes
+        .get(pos..raster_end)
+        .ok_or_else(|| invalid_data("PFM raster is truncated".to_string()))?;
+    let samples: Vec<f32> = raster
+        .chunks_exact(4)
+        .map(|b| {
+            let b = [b[0], b[1], b[2], b[3]];
+            if little_endian {
+                f32::from_le_bytes(b)
+            } else {
+                f32::from_be_bytes(b)
+            }
+        })
+        .collect();
+
+    let mut image = ImageRGB::new(width, height);
+    for (i, px) in samples.chunks_exact(channels).enumerate() {
+        let x = i as u32 % width;
+        let y = height - 1 - i as u32 / width;
+        let rgb = if channels == 3 {
+            RGB::new(px[0], px[1], px[2])
+        } else {
+            RGB::new(px[0], px[0], px[0])
+        };
+        image.set(x, y, &rgb);
+    }
+    Ok(image)
+}
+
+pub fn save(image: &ImageRGB, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+
+    for y in (0..image.height).rev() {
+        let row = &image.data[(y * image.width) as usize..((y + 1) * image.width) as usize];
+        for pixel in row {
+            file.write_all(&pixel.r.to_le_bytes())?;
+            file.write_all(&pixel.g.to_le_bytes())?;
+            file.write_all(&pixel.b.to_le_bytes())?;
+        }
+    }
+    file.flush()
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_token<T: std::str::FromStr>(token: &str, what: &str) -> io::Result<T> {
+    token
+        .parse::<T>()
+        .map_err(|_| invalid_data(format!("invalid PFM {}: {:?}", what, token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        images::{image_ppm::ImagePPM, image_rgb::ImageRGB, load_image},
+        utils::rgb::RGB,
+    };
+
+    #[test]
+    fn pfm_ppm_roundtrip() {
+        let mut image = ImageRGB::new(3, 2);
+        image.set(0, 0, &RGB::new(0.25, 0.5, 1.0));
+        image.set(2, 1, &RGB::new(1.0, 0.0, 0.5));
+
+        let dir = std::env::temp_dir();
+        let pfm_path = dir.join("vi_renderer_roundtrip.pfm");
+        let ppm_path = dir.join("vi_renderer_roundtrip.ppm");
+        super::save(&image, &pfm_path).unwrap();
+        ImagePPM::from(image.clone()).save(&ppm_path).unwrap();
+
+        let pfm = load_image(&pfm_path).unwrap();
+        let ppm = load_image(&ppm_path).unwrap();
+        assert_eq!((pfm.width, pfm.height), (3, 2));
+        assert_eq!((ppm.width, ppm.height), (3, 2));
+        for (i, px) in image.data.iter().enumerate() {
+            assert_eq!(pfm.data[i].r, px.r);
+            assert_eq!(pfm.data[i].b, px.b);
+            assert!((ppm.data[i].g - px.g).abs() < 1.0 / 255.0);
+        }
+
+        std::fs::remove_file(pfm_path).unwrap();
+        std::fs::remove_file(ppm_path).unwrap();
+    }
+
+    #[test]
+    fn oversized_headers_are_invalid() {
+        let dir = std::env::temp_dir();
+        let huge = "4294967295 4294967295";
+        for (name, src) in [
+            ("vi_renderer_huge.pfm", format!("PF\n{}\n-1.0\n", huge)),
+            ("vi_renderer_huge.ppm", format!("P6\n{}\n255\n", huge)),
+            (
+                "vi_renderer_truncated.pfm",
+                "PF\n2 2\n-1.0\n\0\0\0\0".to_string(),
+            ),
+        ] {
+            let path = dir.join(name);
+            std::fs::write(&path, src).unwrap();
+            let err = load_image(&path).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{}", name);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, BufReader, Read, Write};
 use std::path::Path;
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -35,4 +35,99 @@ impl ImagePPM {
 
         Ok(())
     }
+
+    // reads binary (P6) and ascii (P3) files, 16 bit samples are scaled down to 8 bits
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+        let mut pos = 0;
+        let magic = next_header_token(&bytes, &mut pos)?;
+        if magic != "P6" && magic != "P3" {
+            return Err(invalid_data(format!(
+                "unsupported PPM magic number {:?}",
+                magic
+            )));
+        }
+        let width = parse_header_token(&bytes, &mut pos, "width")?;
+        let height = parse_header_token(&bytes, &mut pos, "height")?;
+        let max_val = parse_header_token(&bytes, &mut pos, "max value")?;
+        if max_val == 0 || max_val > 65535 {
+            return Err(invalid_data(format!("invalid PPM max value {}", max_val)));
+        }
+
+        // sizes come from the file, a hostile header must not overflow them
+        let too_large = || invalid_data(format!("PPM size {}x{} is too large", width, height));
+        let sample_count = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(3))
+            .ok_or_else(too_large)?;
+        let mut samples: Vec<u32> = Vec::new();
+        if magic == "P6" {
+            // a single whitespace separates the header from the raster
+            pos += 1;
+            let sample_size = if max_val < 256 { 1 } else { 2 };
+            let raster_end = sample_count
+                .checked_mul(sample_size)
+                .and_then(|n| n.checked_add(pos))
+                .ok_or_else(too_large)?;
+            let raster = bytes
+                .get(pos..raster_end)
+                .ok_or_else(|| invalid_data("PPM raster is truncated".to_string()))?;
+            if sample_size == 1 {
+                samples.extend(raster.iter().map(|b| *b as u32));
+            } else {
+                samples.extend(
+                    raster
+                        .chunks_exact(2)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32),
+                );
+            }
+        } else {
+            for _ in 0..sample_count {
+                samples.push(parse_header_token(&bytes, &mut pos, "sample")?);
+            }
+        }
+
+        let mut image = Self::new(width, height);
+        for (pixel, rgb) in image.data.iter_mut().zip(samples.chunks_exact(3)) {
+            for (c, sample) in pixel.rgb.iter_mut().zip(rgb) {
+                *c = (sample.min(&max_val) * 255 / max_val) as u8;
+            }
+        }
+        Ok(image)
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// header tokens are separated by whitespace, '#' starts a comment until the end of the line
+fn next_header_token<'a>(bytes: &'a [u8], pos: &mut usize) -> io::Result<&'a str> {
+    loop {
+        match bytes.get(*pos) {
+            Some(b'#') => {
+                while bytes.get(*pos).is_some_and(|b| *b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return Err(invalid_data("unexpected end of PPM header".to_string())),
+        }
+    }
+    let start = *pos;
+    while bytes.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&bytes[start..*pos])
+        .map_err(|_| invalid_data("PPM header is not ascii".to_string()))
+}
+
+fn parse_header_token(bytes: &[u8], pos: &mut usize, what: &str) -> io::Result<u32> {
+    let token = next_header_token(bytes, pos)?;
+    token
+        .parse::<u32>()
+        .map_err(|_| invalid_data(format!("invalid PPM {}: {:?}", what, token)))
 }
@@ -28,6 +28,10 @@ impl ImageRGB {
         true
     }
 
+    pub fn get(&self, x: u32, y: u32) -> RGB {
+        self.data[(y * self.width + x) as usize]
+    }
+
     pub fn add(&mut self, x: u32, y: u32, rgb: &RGB) -> bool {
         if x >= self.width || y >= self.height {
             return false;
@@ -48,12 +52,35 @@ impl ImageRGB {
             out[i] = 0u32 | r << 16 | g << 8 | b;
         }
     }
+
+    pub fn to_ppm<F>(&self, tonemapper: F) -> ImagePPM
+    where
+        F: Fn(f32) -> f32,
+    {
+        let mut ppm = ImagePPM::new(self.width, self.height);
+        for (i, pixel) in self.data.iter().enumerate() {
+            let r = (tonemapper(pixel.r) * 255.0).min(255.0) as u8;
+            let g = (tonemapper(pixel.g) * 255.0).min(255.0) as u8;
+            let b = (tonemapper(pixel.b) * 255.0).min(255.0) as u8;
+            ppm.data[i].rgb = [r, g, b];
+        }
+        ppm
+    }
 }
 
 pub fn tonemap_reinhard(val: f32) -> f32 {
     val / (val + 1.0)
 }
 
+// inverse of the sRGB transfer curve, from an encoded value in [0, 1] to a linear one
+pub fn srgb_to_linear(val: f32) -> f32 {
+    if val <= 0.04045 {
+        val / 12.92
+    } else {
+        ((val + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 impl From<ImageRGB> for ImagePPM {
     fn from(value: ImageRGB) -> Self {
         let mut ppm = ImagePPM::new(value.width, value.height);
@@ -67,6 +94,20 @@ impl From<ImageRGB> for ImagePPM {
     }
 }
 
+impl From<ImagePPM> for ImageRGB {
+    fn from(value: ImagePPM) -> Self {
+        let mut image = ImageRGB::new(value.width, value.height);
+        for (i, pixel) in value.data.iter().enumerate() {
+            image.data[i] = RGB::new(
+                pixel.rgb[0] as f32 / 255.0,
+                pixel.rgb[1] as f32 / 255.0,
+                pixel.rgb[2] as f32 / 255.0,
+            );
+        }
+        image
+    }
+}
+
 impl std::ops::DivAssign<f32> for RGB {
     fn div_assign(&mut self, other: f32) {
         self.r /= other;
@@ -1,2 +1,47 @@
+use std::{io, path::Path};
+
+use image_ppm::ImagePPM;
+use image_rgb::{srgb_to_linear, ImageRGB};
+
+use crate::utils::rgb::RGB;
+
+pub mod image_hdr;
+pub mod image_pfm;
 pub mod image_ppm;
 pub mod image_rgb;
+
+// picks the reader from the file extension
+pub fn load_image(path: &Path) -> io::Result<ImageRGB> {
+    match extension(path).as_deref() {
+        Some("ppm") => Ok(ImagePPM::load(path)?.into()),
+        Some("pfm") => image_pfm::load(path),
+        Some("hdr") => image_hdr::load(path),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported image format: {}", path.display()),
+        )),
+    }
+}
+
+// like load_image, for images holding colors such as albedo maps: 8 bit PPMs store
+// sRGB encoded values, as image editors save them, and are decoded to linear ones;
+// the float formats are linear already
+pub fn load_color_image(path: &Path) -> io::Result<ImageRGB> {
+    let mut image = load_image(path)?;
+    if extension(path).as_deref() == Some("ppm") {
+        for c in image.data.iter_mut() {
+            *c = RGB::new(
+                srgb_to_linear(c.r),
+                srgb_to_linear(c.g),
+                srgb_to_linear(c.b),
+            );
+        }
+    }
+    Ok(image)
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
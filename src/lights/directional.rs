@@ -0,0 +1,86 @@
+use std::f32::consts::PI;
+
+use crate::{
+    samplers::{sample_uniform_cone, uniform_cone_pdf},
+    utils::{
+        rgb::RGB,
+        vector::{Point, Vector},
+    },
+};
+
+use super::LightSample;
+
+// based on PBRT's 3rd ed. book, sec 12.4
+// light arriving from infinitely far away, travelling along `direction`; `color` is
+// the irradiance it delivers to a surface facing it
+// with a non-zero angular size the light comes from a disc of the sky, like the sun's,
+// which softens its shadows and makes it visible to rays that escape towards it
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub color: RGB,
+    pub direction: Vector,
+    // cosine of the disc's angular radius, 1 for a single direction
+    pub cos_max: f32,
+}
+
+impl DirectionalLight {
+    // `angular_diameter` in radians
+    pub fn new(color: RGB, direction: Vector, angular_diameter: f32) -> Self {
+        let mut direction = direction;
+        direction.normalize();
+        Self {
+            color,
+            direction,
+            cos_max: (0.5 * angular_diameter).cos(),
+        }
+    }
+
+    pub fn is_delta(&self) -> bool {
+        self.cos_max >= 1.0
+    }
+
+    // radiance of the disc, whose cosine weighted solid angle is pi sin^2 of its
+    // angular radius
+    pub fn radiance(&self) -> RGB {
+        self.color / (PI * (1.0 - self.cos_max * self.cos_max))
+    }
+
+    // radiance arriving from direction `w`, i.e. carried by a ray escaping along it
+    pub fn le(&self, w: Vector) -> RGB {
+        if self.is_delta() || w.dot(-1.0 * self.direction) < self.cos_max {
+            return RGB::default();
+        }
+        self.radiance()
+    }
+
+    pub fn sample_li(&self, _p: Point, r: &[f32; 2]) -> Option<LightSample> {
+        let to_light = -1.0 * self.direction;
+        if self.is_delta() {
+            return Some(LightSample {
+                li: self.color,
+                wi: to_light,
+                dist: f32::INFINITY,
+                pdf: 1.0,
+                is_delta: true,
+            });
+        }
+        let (x, y) = to_light.coordinate_system();
+        let mut wi = sample_uniform_cone(*r, self.cos_max).rotate(x, y, to_light);
+        wi.normalize();
+        Some(LightSample {
+            li: self.radiance(),
+            wi,
+            dist: f32::INFINITY,
+            pdf: uniform_cone_pdf(self.cos_max),
+            is_delta: false,
+        })
+    }
+
+    // solid angle pdf of sample_li picking direction `w`
+    pub fn pdf_dir(&self, w: Vector) -> f32 {
+        if self.is_delta() || w.dot(-1.0 * self.direction) < self.cos_max {
+            return 0.0;
+        }
+        uniform_cone_pdf(self.cos_max)
+    }
+}
@@ -0,0 +1,164 @@
+use std::f32::consts::PI;
+
+use crate::{
+    images::image_rgb::ImageRGB,
+    utils::{
+        distribution::Distribution2D,
+        rgb::RGB,
+        transform::Transform,
+        vector::{Point, Vector},
+    },
+};
+
+use super::LightSample;
+
+// based on PBRT's 3rd ed. book, sec 12.6 and 14.2.4
+// infinitely far away light given by a latitude-longitude map, laid out like the
+// equirectangular camera: the top row looks along +y, the center of the image along
+// -z and longitudes grow towards +x; the map is rotated into the world by
+// `world_from_light`
+// directions are importance sampled by a piecewise-constant distribution over the
+// pixels' luminance, weighted by the solid angle each row covers
+#[derive(Debug, Clone)]
+pub struct EnvironmentLight {
+    pub image: ImageRGB,
+    pub scale: f32,
+    pub world_from_light: Transform,
+    pub distribution: Distribution2D,
+}
+
+impl EnvironmentLight {
+    pub fn new(image: ImageRGB, scale: f32, world_from_light: Transform) -> Self {
+        let (width, height) = (image.width as usize, image.height as usize);
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+            for x in 0..width {
+                func.push(image.get(x as u32, y as u32).y().max(0.0) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+        Self {
+            image,
+            scale,
+            world_from_light,
+            distribution,
+        }
+    }
+
+    // radiance arriving from direction `w`, i.e. carried by a ray escaping along it
+    pub fn le(&self, w: Vector) -> RGB {
+        self.lookup(uv_from_dir(self.world_from_light.inverse().apply_vector(w)))
+    }
+
+    fn lookup(&self, uv: [f32; 2]) -> RGB {
+        let x = ((uv[0] * self.image.width as f32) as u32).min(self.image.width - 1);
+        let y = ((uv[1] * self.image.height as f32) as u32).min(self.image.height - 1);
+        self.image.get(x, y) * self.scale
+    }
+
+    pub fn sample_li(&self, _p: Point, r: &[f32; 2]) -> Option<LightSample> {
+        let (uv, map_pdf) = self.distribution.sample_continuous(*r);
+        if map_pdf == 0.0 {
+            return None;
+        }
+        let (w, sin_theta) = dir_from_uv(uv);
+        if sin_theta == 0.0 {
+            return None;
+        }
+        let mut wi = self.world_from_light.apply_vector(w);
+        wi.normalize();
+        Some(LightSample {
+            li: self.lookup(uv),
+            wi,
+            dist: f32::INFINITY,
+            pdf: map_pdf / (2.0 * PI * PI * sin_theta),
+            is_delta: false,
+        })
+    }
+
+    // solid angle pdf of sample_li picking direction `w`
+    pub fn pdf_dir(&self, w: Vector) -> f32 {
+        let uv = uv_from_dir(self.world_from_light.inverse().apply_vector(w));
+        let sin_theta = (PI * uv[1]).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// the map spans 2 pi in longitude over u and pi in polar angle over v, so a unit of
+// uv area covers 2 pi^2 sin(theta) of solid angle
+fn dir_from_uv(uv: [f32; 2]) -> (Vector, f32) {
+    let phi = (uv[0] - 0.5) * 2.0 * PI;
+    let theta = uv[1] * PI;
+    let sin_theta = theta.sin();
+    (
+        Vector::new(sin_theta * phi.sin(), theta.cos(), -sin_theta * phi.cos()),
+        sin_theta,
+    )
+}
+
+fn uv_from_dir(w: Vector) -> [f32; 2] {
+    let mut w = w;
+    w.normalize();
+    let theta = w.y.clamp(-1.0, 1.0).acos();
+    let phi = w.x.atan2(-w.z);
+    [phi / (2.0 * PI) + 0.5, theta / PI]
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        images::image_rgb::ImageRGB,
+        utils::{
+            rgb::RGB,
+            transform::Transform,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::EnvironmentLight;
+
+    #[test]
+    fn environment_pdf_matches_samples() {
+        // dim sky with a small bright sun
+        let mut image = ImageRGB::new(16, 8);
+        for y in 0..8 {
+            for x in 0..16 {
+                image.set(x, y, &RGB::new(0.2, 0.3, 0.5));
+            }
+        }
+        image.set(11, 2, &RGB::new(500.0, 450.0, 400.0));
+        let rotation = Transform::rotate(0.7, Vector::new(1.0, 2.0, 0.5));
+        let light = EnvironmentLight::new(image, 2.0, rotation);
+
+        let p = Point::new(0.0, 0.0, 0.0);
+        let mut rng = StdRng::seed_from_u64(5);
+        let n = 50_000;
+        let (mut sun, mut solid_angle) = (0, 0.0f64);
+        for _ in 0..n {
+            let ls = light.sample_li(p, &[rng.gen(), rng.gen()]).unwrap();
+            assert!((ls.wi.norm() - 1.0).abs() < 1e-4);
+            // the polar angle loses a few digits through acos near the poles
+            let pdf = light.pdf_dir(ls.wi);
+            assert!((pdf - ls.pdf).abs() < 1e-2 * ls.pdf, "{} {}", pdf, ls.pdf);
+            let le = light.le(ls.wi);
+            assert!((le.r - ls.li.r).abs() < 1e-4 * ls.li.r);
+            if ls.li.r > 100.0 {
+                sun += 1;
+            }
+            // only directions the map covers are sampled, so this estimates 4 pi
+            solid_angle += 1.0 / ls.pdf as f64;
+        }
+        // the sun's luminance dwarfs the sky's, most samples go there
+        assert!(sun as f32 > 0.8 * n as f32);
+        let solid_angle = solid_angle / n as f64;
+        assert!(
+            (solid_angle - 4.0 * std::f64::consts::PI).abs() < 0.05 * 4.0 * std::f64::consts::PI
+        );
+    }
+}
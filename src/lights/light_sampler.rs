@@ -0,0 +1,721 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use serde::Deserialize;
+
+use crate::utils::{
+    aabb::AABB,
+    distribution::AliasTable,
+    transform::Transform,
+    vector::{Point, Vector},
+};
+
+use super::Light;
+
+// based on PBRT's 4th ed. book, sec 12.6
+// picks the light a shading point takes its direct lighting sample from; ambient
+// lights are never picked, shaders add them at every hit instead
+pub trait LightSampler {
+    // light index and the probability of having picked it for a point `p` on a surface
+    // with normal `n`, which is zero away from surfaces
+    fn sample(&self, p: Point, n: Vector, u: f32) -> Option<(u32, f32)>;
+    fn pmf(&self, p: Point, n: Vector, light: u32) -> f32;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightSamplerType {
+    Uniform,
+    Power,
+    #[default]
+    Bvh,
+}
+
+impl LightSamplerType {
+    pub const NAMES: [&'static str; 3] = ["uniform", "power", "bvh"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "uniform" => Some(LightSamplerType::Uniform),
+            "power" => Some(LightSamplerType::Power),
+            "bvh" => Some(LightSamplerType::Bvh),
+            _ => None,
+        }
+    }
+}
+
+// light sampler picked at runtime, e.g. from a scene file
+#[derive(Debug, Clone)]
+pub enum AnyLightSampler {
+    Uniform(UniformLightSampler),
+    Power(PowerLightSampler),
+    Bvh(BvhLightSampler),
+}
+
+impl Default for AnyLightSampler {
+    fn default() -> Self {
+        AnyLightSampler::Uniform(UniformLightSampler::default())
+    }
+}
+
+impl AnyLightSampler {
+    // `scene_bb` bounds the scene's geometry, lights at infinity deliver their power
+    // over the disc it casts
+    pub fn new(kind: LightSamplerType, lights: &[Light], scene_bb: &AABB) -> Self {
+        match kind {
+            LightSamplerType::Uniform => AnyLightSampler::Uniform(UniformLightSampler::new(lights)),
+            LightSamplerType::Power => {
+                AnyLightSampler::Power(PowerLightSampler::new(lights, scene_radius(scene_bb)))
+            }
+            LightSamplerType::Bvh => AnyLightSampler::Bvh(BvhLightSampler::new(lights)),
+        }
+    }
+}
+
+impl LightSampler for AnyLightSampler {
+    fn sample(&self, p: Point, n: Vector, u: f32) -> Option<(u32, f32)> {
+        match self {
+            AnyLightSampler::Uniform(s) => s.sample(p, n, u),
+            AnyLightSampler::Power(s) => s.sample(p, n, u),
+            AnyLightSampler::Bvh(s) => s.sample(p, n, u),
+        }
+    }
+
+    fn pmf(&self, p: Point, n: Vector, light: u32) -> f32 {
+        match self {
+            AnyLightSampler::Uniform(s) => s.pmf(p, n, light),
+            AnyLightSampler::Power(s) => s.pmf(p, n, light),
+            AnyLightSampler::Bvh(s) => s.pmf(p, n, light),
+        }
+    }
+}
+
+fn scene_radius(scene_bb: &AABB) -> f32 {
+    // scenes without bounded geometry get a unit radius
+    if scene_bb.is_empty() {
+        return 1.0;
+    }
+    0.5 * scene_bb.diagonal().norm()
+}
+
+// every light that can be sampled, with the same probability
+#[derive(Debug, Clone, Default)]
+pub struct UniformLightSampler {
+    pub lights: Vec<u32>,
+    // for every scene light, whether it is in `lights`
+    pub sampled: Vec<bool>,
+}
+
+impl UniformLightSampler {
+    pub fn new(lights: &[Light]) -> Self {
+        let sampled: Vec<bool> = lights
+            .iter()
+            .map(|l| !matches!(l, Light::Ambient(_)))
+            .collect();
+        Self {
+            lights: (0..lights.len() as u32)
+                .filter(|&i| sampled[i as usize])
+                .collect(),
+            sampled,
+        }
+    }
+}
+
+impl LightSampler for UniformLightSampler {
+    fn sample(&self, _p: Point, _n: Vector, u: f32) -> Option<(u32, f32)> {
+        let n = self.lights.len();
+        if n == 0 {
+            return None;
+        }
+        let ind = ((u * n as f32) as usize).min(n - 1);
+        Some((self.lights[ind], 1.0 / n as f32))
+    }
+
+    fn pmf(&self, _p: Point, _n: Vector, light: u32) -> f32 {
+        if self.sampled.get(light as usize).copied().unwrap_or(false) {
+            1.0 / self.lights.len() as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+// lights picked in proportion to the power they emit, regardless of where the
+// shading point is
+#[derive(Debug, Clone, Default)]
+pub struct PowerLightSampler {
+    pub lights: Vec<u32>,
+    pub alias: AliasTable,
+    // for every scene light, its entry in `lights`
+    pub entry: Vec<Option<u32>>,
+}
+
+impl PowerLightSampler {
+    pub fn new(lights: &[Light], scene_radius: f32) -> Self {
+        let sampled = UniformLightSampler::new(lights).lights;
+        let mut power: Vec<f32> = sampled
+            .iter()
+            .map(|&l| lights[l as usize].phi(scene_radius).max(0.0))
+            .collect();
+        // nothing emits, fall back to picking uniformly
+        if power.iter().all(|&p| p == 0.0) {
+            power.iter_mut().for_each(|p| *p = 1.0);
+        }
+        let mut entry = vec![None; lights.len()];
+        for (i, &l) in sampled.iter().enumerate() {
+            entry[l as usize] = Some(i as u32);
+        }
+        Self {
+            alias: if sampled.is_empty() {
+                AliasTable::default()
+            } else {
+                AliasTable::new(&power)
+            },
+            lights: sampled,
+            entry,
+        }
+    }
+}
+
+impl LightSampler for PowerLightSampler {
+    fn sample(&self, _p: Point, _n: Vector, u: f32) -> Option<(u32, f32)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let (ind, pmf) = self.alias.sample(u);
+        Some((self.lights[ind], pmf))
+    }
+
+    fn pmf(&self, _p: Point, _n: Vector, light: u32) -> f32 {
+        match self.entry.get(light as usize) {
+            Some(Some(ind)) => self.alias.pmf(*ind as usize),
+            _ => 0.0,
+        }
+    }
+}
+
+// based on PBRT's 4th ed. book, sec 12.6.3
+// spatial and directional bounds of the light emitted by one or more lights: emission
+// leaves along directions within theta_o of `w`, spreading up to theta_e further
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    pub bb: AABB,
+    pub phi: f32,
+    pub w: Vector,
+    pub cos_theta_o: f32,
+    pub cos_theta_e: f32,
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    pub fn union(&self, other: &LightBounds) -> LightBounds {
+        if self.phi == 0.0 {
+            return *other;
+        }
+        if other.phi == 0.0 {
+            return *self;
+        }
+        let (w, cos_theta_o) = cone_union((self.w, self.cos_theta_o), (other.w, other.cos_theta_o));
+        LightBounds {
+            bb: self.bb.union(&other.bb),
+            phi: self.phi + other.phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    // how much light the bounded lights may deliver to a point `p` on a surface with
+    // normal `n`, conservative in the angles it considers
+    pub fn importance(&self, p: Point, n: Vector) -> f32 {
+        let pc = self.bb.centroid();
+        let mut wp = pc.vec2point(p);
+        let d2 = wp.dot(wp).max(0.5 * self.bb.diagonal().norm());
+        if wp.dot(wp) > 0.0 {
+            wp.normalize();
+        }
+
+        // angle between the emission axis and the point, less the emission cone and
+        // the angle the bounds cover as seen from the point
+        let mut cos_theta_w = self.w.dot(wp);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+        let cos_theta_b = self.cos_subtended(p);
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+        let mut importance = self.phi * cos_theta_p / d2;
+
+        // cosine at the receiving surface, as large as the bounds allow
+        if n.dot(n) > 0.0 {
+            let cos_theta_i = (-1.0 * wp).dot(n).abs();
+            let sin_theta_i = safe_sqrt(1.0 - cos_theta_i * cos_theta_i);
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+
+    // cosine of the half angle of the cone around the direction to the bounds' center
+    // that contains them as seen from `p`, -1 from inside
+    fn cos_subtended(&self, p: Point) -> f32 {
+        let center = self.bb.centroid();
+        let r2 = {
+            let d = center.vec2point(self.bb.max);
+            d.dot(d)
+        };
+        let dc = center.vec2point(p);
+        let dist2 = dc.dot(dc);
+        if dist2 < r2 {
+            return -1.0;
+        }
+        safe_sqrt(1.0 - r2 / dist2)
+    }
+}
+
+// cos(a - b), 1 when a < b
+fn cos_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+// sin(a - b), 0 when a < b
+fn sin_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+fn safe_sqrt(x: f32) -> f32 {
+    x.max(0.0).sqrt()
+}
+
+// based on PBRT's 4th ed. book, sec 3.8.4
+// smallest cone around both cones of directions, given by axis and cosine of the
+// half angle
+fn cone_union(a: (Vector, f32), b: (Vector, f32)) -> (Vector, f32) {
+    let entire_sphere = (Vector::new(0.0, 0.0, 1.0), -1.0);
+    let theta_a = a.1.clamp(-1.0, 1.0).acos();
+    let theta_b = b.1.clamp(-1.0, 1.0).acos();
+    let theta_d = a.0.dot(b.0).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    if theta_o >= PI {
+        return entire_sphere;
+    }
+    // turn a's axis towards b's until the cone reaches both
+    let theta_r = theta_o - theta_a;
+    let wr = a.0.cross(b.0);
+    if wr.dot(wr) == 0.0 {
+        return entire_sphere;
+    }
+    let mut w = Transform::rotate(theta_r, wr).apply_vector(a.0);
+    w.normalize();
+    (w, theta_o.cos())
+}
+
+const LIGHT_BVH_BUCKETS: usize = 12;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LightBvhNode {
+    pub bounds: LightBounds,
+    // leaf: index of the light in the scene
+    // interior: index of the second child (the first one is right after this node)
+    pub offset: u32,
+    pub is_leaf: bool,
+}
+
+// based on PBRT's 4th ed. book, sec 12.6.3
+// lights with bounds are organized in a BVH whose nodes carry the LightBounds of their
+// lights; sampling walks down from the root, picking either child in proportion to its
+// importance for the shading point, so nearby lights facing the point are favoured
+// lights at infinity are picked uniformly next to the BVH as a whole
+#[derive(Debug, Clone, Default)]
+pub struct BvhLightSampler {
+    pub nodes: Vec<LightBvhNode>,
+    pub infinite_lights: Vec<u32>,
+    // for every scene light in the BVH, the path from the root to its leaf, bit i
+    // telling which child to take at depth i
+    pub bit_trails: Vec<Option<u64>>,
+}
+
+impl BvhLightSampler {
+    pub fn new(lights: &[Light]) -> Self {
+        let mut sampler = Self {
+            nodes: Vec::new(),
+            infinite_lights: Vec::new(),
+            bit_trails: vec![None; lights.len()],
+        };
+        let mut bvh_lights = Vec::new();
+        for (i, light) in lights.iter().enumerate() {
+            match light {
+                Light::Ambient(_) => {}
+                Light::Environment(_) | Light::Directional(_) => {
+                    sampler.infinite_lights.push(i as u32)
+                }
+                _ => {
+                    // lights that emit nothing are never picked
+                    if let Some(bounds) = light.bounds().filter(|b| b.phi > 0.0) {
+                        bvh_lights.push((i as u32, bounds));
+                    }
+                }
+            }
+        }
+        if !bvh_lights.is_empty() {
+            sampler.build(&mut bvh_lights, 0, 0);
+        }
+        sampler
+    }
+
+    // returns the index of the new node
+    fn build(&mut self, lights: &mut [(u32, LightBounds)], bit_trail: u64, depth: u32) -> usize {
+        let node_ind = self.nodes.len();
+        if lights.len() == 1 {
+            let (light, bounds) = lights[0];
+            self.nodes.push(LightBvhNode {
+                bounds,
+                offset: light,
+                is_leaf: true,
+            });
+            self.bit_trails[light as usize] = Some(bit_trail);
+            return node_ind;
+        }
+
+        let bb = lights
+            .iter()
+            .fold(AABB::default(), |acc, (_, b)| acc.union(&b.bb));
+        let mut centroid_bb = AABB::default();
+        for (_, b) in lights.iter() {
+            centroid_bb.update(&b.bb.centroid());
+        }
+
+        // split minimizing the cost of both halves over the buckets of every axis
+        let mut best = None;
+        let mut best_cost = f32::MAX;
+        for dim in 0..3 {
+            if centroid_bb.max[dim] == centroid_bb.min[dim] {
+                continue;
+            }
+            let bucket_of = |b: &LightBounds| -> usize {
+                let x = centroid_bb.offset(&b.bb.centroid())[dim];
+                ((LIGHT_BVH_BUCKETS as f32 * x) as usize).min(LIGHT_BVH_BUCKETS - 1)
+            };
+            let mut buckets: [Option<LightBounds>; LIGHT_BVH_BUCKETS] = [None; LIGHT_BVH_BUCKETS];
+            for (_, b) in lights.iter() {
+                let bucket = &mut buckets[bucket_of(b)];
+                *bucket = Some(bucket.map_or(*b, |acc| acc.union(b)));
+            }
+            let union_of = |range: &[Option<LightBounds>]| {
+                range
+                    .iter()
+                    .flatten()
+                    .fold(None, |acc: Option<LightBounds>, b| {
+                        Some(acc.map_or(*b, |acc| acc.union(b)))
+                    })
+            };
+            for split in 0..LIGHT_BVH_BUCKETS - 1 {
+                let (Some(below), Some(above)) = (
+                    union_of(&buckets[..=split]),
+                    union_of(&buckets[split + 1..]),
+                ) else {
+                    continue;
+                };
+                let cost = split_cost(&below, &bb, dim) + split_cost(&above, &bb, dim);
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((dim, split));
+                }
+            }
+        }
+
+        let mut mid = lights.len() / 2;
+        if let Some((dim, split)) = best {
+            let mut count = 0;
+            for i in 0..lights.len() {
+                let x = centroid_bb.offset(&lights[i].1.bb.centroid())[dim];
+                let bucket = ((LIGHT_BVH_BUCKETS as f32 * x) as usize).min(LIGHT_BVH_BUCKETS - 1);
+                if bucket <= split {
+                    lights.swap(i, count);
+                    count += 1;
+                }
+            }
+            if count > 0 && count < lights.len() {
+                mid = count;
+            }
+        }
+
+        // deeper trees would not fit the bit trails
+        assert!(depth < 64, "light BVH is too deep");
+        self.nodes.push(LightBvhNode {
+            bounds: lights[0].1,
+            offset: 0,
+            is_leaf: false,
+        });
+        let (left, right) = lights.split_at_mut(mid);
+        self.build(left, bit_trail, depth + 1);
+        let second = self.build(right, bit_trail | (1 << depth), depth + 1);
+        let bounds = self.nodes[node_ind + 1]
+            .bounds
+            .union(&self.nodes[second].bounds);
+        self.nodes[node_ind] = LightBvhNode {
+            bounds,
+            offset: second as u32,
+            is_leaf: false,
+        };
+        node_ind
+    }
+
+    // probability of picking the BVH rather than one of the lights at infinity
+    fn p_bvh(&self) -> f32 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        1.0 / (self.infinite_lights.len() + 1) as f32
+    }
+
+    fn child_importances(&self, node_ind: usize, p: Point, n: Vector) -> (usize, usize, f32, f32) {
+        let node = &self.nodes[node_ind];
+        let (c0, c1) = (node_ind + 1, node.offset as usize);
+        (
+            c0,
+            c1,
+            self.nodes[c0].bounds.importance(p, n),
+            self.nodes[c1].bounds.importance(p, n),
+        )
+    }
+}
+
+// PBRT's EvaluateCost, the light's power times the solid angle its emission spreads
+// over and the surface area of its bounds, penalizing boxes thin along `dim`
+fn split_cost(b: &LightBounds, node_bb: &AABB, dim: usize) -> f32 {
+    let theta_o = b.cos_theta_o.clamp(-1.0, 1.0).acos();
+    let theta_e = b.cos_theta_e.clamp(-1.0, 1.0).acos();
+    let theta_w = (theta_o + theta_e).min(PI);
+    let sin_theta_o = safe_sqrt(1.0 - b.cos_theta_o * b.cos_theta_o);
+    let m_omega = 2.0 * PI * (1.0 - b.cos_theta_o)
+        + FRAC_PI_2
+            * (2.0 * theta_w * sin_theta_o
+                - (theta_o - 2.0 * theta_w).cos()
+                - 2.0 * theta_o * sin_theta_o
+                + b.cos_theta_o);
+    let diag = node_bb.diagonal();
+    let kr = diag[diag.max_dimension()] / diag[dim];
+    b.phi * m_omega * kr * b.bb.surface_area()
+}
+
+impl LightSampler for BvhLightSampler {
+    fn sample(&self, p: Point, n: Vector, u: f32) -> Option<(u32, f32)> {
+        let p_bvh = self.p_bvh();
+        let n_infinite = self.infinite_lights.len();
+        if u >= p_bvh {
+            if n_infinite == 0 {
+                return None;
+            }
+            let u = (u - p_bvh) / (1.0 - p_bvh);
+            let ind = ((u * n_infinite as f32) as usize).min(n_infinite - 1);
+            return Some((self.infinite_lights[ind], (1.0 - p_bvh) / n_infinite as f32));
+        }
+
+        let mut u = (u / p_bvh).min(1.0 - f32::EPSILON);
+        let mut pmf = p_bvh;
+        let mut node_ind = 0;
+        loop {
+            let node = &self.nodes[node_ind];
+            if node.is_leaf {
+                // a lone light still has to be able to reach the point
+                if node_ind > 0 || node.bounds.importance(p, n) > 0.0 {
+                    return Some((node.offset, pmf));
+                }
+                return None;
+            }
+            let (c0, c1, i0, i1) = self.child_importances(node_ind, p, n);
+            if i0 == 0.0 && i1 == 0.0 {
+                return None;
+            }
+            let p0 = i0 / (i0 + i1);
+            if u < p0 {
+                node_ind = c0;
+                u /= p0;
+                pmf *= p0;
+            } else {
+                node_ind = c1;
+                u = (u - p0) / (1.0 - p0);
+                pmf *= 1.0 - p0;
+            }
+            u = u.min(1.0 - f32::EPSILON);
+        }
+    }
+
+    fn pmf(&self, p: Point, n: Vector, light: u32) -> f32 {
+        if self.infinite_lights.contains(&light) {
+            return (1.0 - self.p_bvh()) / self.infinite_lights.len() as f32;
+        }
+        let Some(Some(mut bit_trail)) = self.bit_trails.get(light as usize).copied() else {
+            return 0.0;
+        };
+        let mut pmf = self.p_bvh();
+        let mut node_ind = 0;
+        loop {
+            let node = &self.nodes[node_ind];
+            if node.is_leaf {
+                // same check as in sample, a lone light that can't reach the point isn't picked
+                if node_ind > 0 || node.bounds.importance(p, n) > 0.0 {
+                    return pmf;
+                }
+                return 0.0;
+            }
+            let (c0, c1, i0, i1) = self.child_importances(node_ind, p, n);
+            if i0 == 0.0 && i1 == 0.0 {
+                return 0.0;
+            }
+            let (child, importance) = if bit_trail & 1 == 0 {
+                (c0, i0)
+            } else {
+                (c1, i1)
+            };
+            pmf *= importance / (i0 + i1);
+            node_ind = child;
+            bit_trail >>= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        lights::{
+            directional::DirectionalLight, spot::SpotLight, AmbientLight, AreaLight, Light,
+            PointLight,
+        },
+        primitives::{triangle::Triangle, AnyShape},
+        utils::{
+            aabb::AABB,
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::{AnyLightSampler, LightSampler, LightSamplerType};
+
+    fn lights() -> Vec<Light> {
+        let mut lights = vec![Light::Ambient(AmbientLight {
+            color: RGB::new(0.1, 0.1, 0.1),
+        })];
+        // a row of dim point lights along x
+        for i in 0..8 {
+            lights.push(Light::Point(PointLight {
+                color: RGB::new(1.0, 1.0, 1.0),
+                position: Point::new(i as f32 * 2.0, 3.0, 0.0),
+            }));
+        }
+        // a bright ceiling panel facing down and a spot aiming at the floor
+        let tri = Triangle::new(
+            Point::new(-1.0, 4.0, -1.0),
+            Point::new(1.0, 4.0, -1.0),
+            Point::new(-1.0, 4.0, 1.0),
+            Vector::new(0.0, -1.0, 0.0),
+        );
+        lights.push(Light::Area(AreaLight::new(
+            RGB::new(50.0, 50.0, 50.0),
+            AnyShape::Triangle(tri),
+        )));
+        lights.push(Light::Spot(SpotLight::new(
+            RGB::new(5.0, 5.0, 5.0),
+            Point::new(8.0, 5.0, 4.0),
+            Vector::new(0.0, -1.0, 0.0),
+            0.5,
+            0.1,
+        )));
+        lights.push(Light::Directional(DirectionalLight::new(
+            RGB::new(1.0, 1.0, 1.0),
+            Vector::new(0.2, -1.0, 0.1),
+            0.01,
+        )));
+        lights
+    }
+
+    #[test]
+    fn light_samplers_pmf_matches_samples() {
+        let lights = lights();
+        let mut scene_bb = AABB::default();
+        scene_bb.update(&Point::new(-5.0, 0.0, -5.0));
+        scene_bb.update(&Point::new(20.0, 6.0, 5.0));
+
+        let points = [
+            (Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
+            (Point::new(14.0, 0.5, 0.0), Vector::new(0.0, 1.0, 0.0)),
+            (Point::new(8.0, 2.0, 4.0), Vector::new(-1.0, 0.0, 0.0)),
+        ];
+        let mut rng = StdRng::seed_from_u64(3);
+        for kind in [
+            LightSamplerType::Uniform,
+            LightSamplerType::Power,
+            LightSamplerType::Bvh,
+        ] {
+            let sampler = AnyLightSampler::new(kind, &lights, &scene_bb);
+            for (p, n) in points {
+                // ambient lights are never picked and the pmfs add up to one
+                assert_eq!(sampler.pmf(p, n, 0), 0.0);
+                let total: f32 = (0..lights.len() as u32).map(|l| sampler.pmf(p, n, l)).sum();
+                assert!((total - 1.0).abs() < 1e-4, "{:?} {}", kind, total);
+
+                let mut counts = vec![0; lights.len()];
+                let samples = 20_000;
+                for _ in 0..samples {
+                    let (l, pmf) = sampler.sample(p, n, rng.gen()).unwrap();
+                    assert!((sampler.pmf(p, n, l) - pmf).abs() < 1e-5 * pmf.max(1.0));
+                    counts[l as usize] += 1;
+                }
+                for (l, &count) in counts.iter().enumerate() {
+                    let pmf = sampler.pmf(p, n, l as u32);
+                    let freq = count as f32 / samples as f32;
+                    assert!(
+                        (freq - pmf).abs() < 0.02,
+                        "{:?} {} {} {}",
+                        kind,
+                        l,
+                        freq,
+                        pmf
+                    );
+                }
+            }
+        }
+
+        // the BVH favours the point lights above the shading point over distant ones,
+        // which the power sampler treats alike
+        let (p, n) = points[1];
+        let bvh = AnyLightSampler::new(LightSamplerType::Bvh, &lights, &scene_bb);
+        let power = AnyLightSampler::new(LightSamplerType::Power, &lights, &scene_bb);
+        assert!(bvh.pmf(p, n, 8) > 5.0 * bvh.pmf(p, n, 1));
+        assert_eq!(power.pmf(p, n, 8), power.pmf(p, n, 1));
+        // and ignores the panel, which faces away from a point above it
+        let above = Point::new(0.0, 10.0, 0.0);
+        assert_eq!(bvh.pmf(above, n, 9), 0.0);
+
+        // with the panel as the only light, the BVH is a single leaf
+        let lone = vec![lights[0].clone(), lights[9].clone()];
+        let bvh = AnyLightSampler::new(LightSamplerType::Bvh, &lone, &scene_bb);
+        assert!(bvh.sample(above, n, 0.5).is_none());
+        assert_eq!(bvh.pmf(above, n, 1), 0.0);
+        let (p, n) = points[0];
+        assert_eq!(bvh.sample(p, n, 0.5), Some((1, bvh.pmf(p, n, 1))));
+        assert_eq!(bvh.pmf(p, n, 1), 1.0);
+    }
+}
@@ -0,0 +1,336 @@
+use std::f32::consts::PI;
+
+use crate::{
+    primitives::{
+        mesh::Mesh,
+        triangle::{Face, Triangle},
+        Shape,
+    },
+    utils::{
+        distribution::Distribution1D,
+        rgb::RGB,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{light_sampler::LightBounds, LightSample};
+
+// based on PBRT's 3rd ed. book, sec 12.5 and 14.2
+// one luminaire made of many triangles, all emitting the same radiance towards the
+// side of their normal; a triangle is picked in proportion to its area and a point
+// uniformly on it, so the points are uniform over the whole surface
+#[derive(Debug, Clone)]
+pub struct MeshLight {
+    pub triangles: Vec<Triangle>,
+    pub power: RGB,
+    pub area: f32,
+    // over the triangles' areas
+    pub distribution: Distribution1D,
+    // the triangles are the faces of an emissive scene mesh, rays hit the mesh instead
+    pub from_mesh: bool,
+    // that mesh when it moves, its faces are then sampled where they are at the time
+    // of the ray, `triangles` holding them at time 0
+    pub mesh: Option<Mesh>,
+}
+
+impl MeshLight {
+    // `power` is spread over the triangles by their area
+    pub fn new(power: RGB, triangles: Vec<Triangle>) -> Self {
+        let areas: Vec<f32> = triangles.iter().map(|t| t.area()).collect();
+        let area = areas.iter().sum();
+        Self {
+            distribution: Distribution1D::new(&areas),
+            triangles,
+            power,
+            area,
+            from_mesh: false,
+            mesh: None,
+        }
+    }
+
+    // the faces of a mesh emitting radiance `le`, a moving mesh must keep every face's
+    // share of its area all along the motion, as faces are picked by their area at
+    // time 0
+    pub fn from_mesh(mesh: &Mesh, le: RGB) -> Self {
+        let triangles: Vec<Triangle> = (0..mesh.face_count())
+            .map(|face_ind| face_triangle(&mesh.face_at(face_ind, 0.0)))
+            .collect();
+        let area: f32 = triangles.iter().map(|t| t.area()).sum();
+        // radiance le over a one sided lambertian emitter is pi * area * le watts
+        let moves = mesh.rigid_motion.is_some() || !mesh.motion.is_empty();
+        Self {
+            from_mesh: true,
+            mesh: moves.then(|| mesh.clone()),
+            ..Self::new(le * (PI * area), triangles)
+        }
+    }
+
+    // area pdf of the sampled points, the pmf of a triangle times the uniform pdf on
+    // it, area_i / area * 1 / area_i; 0 for a light without area
+    pub fn pdf_area(&self) -> f32 {
+        if self.area > 0.0 && self.area.is_finite() {
+            1.0 / self.area
+        } else {
+            0.0
+        }
+    }
+
+    // area pdf at the given time, the surface of a moving mesh may have grown since
+    // time 0
+    fn pdf_area_at(&self, time: f32) -> f32 {
+        match self.mesh.as_ref().and_then(|m| m.rigid_motion) {
+            Some(xf) => self.pdf_area() / xf.area_scale(time),
+            None => self.pdf_area(),
+        }
+    }
+
+    pub fn radiance(&self) -> RGB {
+        self.power * (self.pdf_area() / PI)
+    }
+
+    // radiance leaving the light towards `w` from a point with normal `n`
+    pub fn le(&self, n: Vector, w: Vector) -> RGB {
+        if w.dot(n) > 0.0 {
+            self.radiance()
+        } else {
+            RGB::default()
+        }
+    }
+
+    pub fn sample_li(&self, p: Point, time: f32, r: &[f32; 2]) -> Option<LightSample> {
+        let pdf_area = self.pdf_area_at(time);
+        if pdf_area == 0.0 {
+            return None;
+        }
+        let (tri_ind, _, u0) = self.distribution.sample_discrete(r[0]);
+        let (l_point, l_normal) = match &self.mesh {
+            Some(mesh) => face_triangle(&mesh.face_at(tri_ind, time)).sample([u0, r[1]]),
+            None => self.triangles[tri_ind].sample([u0, r[1]]),
+        };
+        let mut wi: Vector = (l_point - p).into();
+        let dist = wi.norm();
+        if dist == 0.0 {
+            return None;
+        }
+        wi = wi / dist;
+
+        let cos_l = -wi.dot(l_normal);
+        if cos_l <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            li: self.radiance(),
+            wi,
+            dist,
+            pdf: pdf_area * dist * dist / cos_l,
+            is_delta: false,
+        })
+    }
+
+    // solid angle pdf of sample_li picking `l_point` at the given time, on a triangle
+    // with normal `l_normal`, as seen from `p`; the normal may face either way, like
+    // the geometric normal of a hit, as nothing leaves through the back of the light
+    // anyway
+    pub fn pdf_li(&self, p: Point, l_point: Point, l_normal: Vector, time: f32) -> f32 {
+        let mut wi: Vector = (l_point - p).into();
+        let dist2 = wi.dot(wi);
+        wi.normalize();
+        let cos_l = wi.dot(l_normal).abs();
+        if cos_l == 0.0 {
+            return 0.0;
+        }
+        self.pdf_area_at(time) * dist2 / cos_l
+    }
+
+    // union of the bounds of every triangle, each emitting its share of the power; a
+    // moving mesh may be anywhere along its motion and face any way
+    pub fn bounds(&self) -> Option<LightBounds> {
+        if self.pdf_area() == 0.0 {
+            return None;
+        }
+        if let Some(mesh) = &self.mesh {
+            return Some(LightBounds {
+                bb: mesh.aabb,
+                phi: self.power.y(),
+                w: Vector::new(0.0, 0.0, 1.0),
+                cos_theta_o: -1.0,
+                cos_theta_e: 0.0,
+                two_sided: false,
+            });
+        }
+        let phi = self.power.y() * self.pdf_area();
+        self.triangles
+            .iter()
+            .filter(|tri| tri.area() > 0.0)
+            .map(|tri| LightBounds {
+                bb: tri.bb,
+                phi: phi * tri.area(),
+                w: tri.normal,
+                cos_theta_o: 1.0,
+                cos_theta_e: 0.0,
+                two_sided: false,
+            })
+            .reduce(|acc, b| acc.union(&b))
+    }
+}
+
+// triangle of a mesh face, facing like its vertex normals when it has them and
+// otherwise following the winding
+pub fn face_triangle(face: &Face) -> Triangle {
+    let [a, b, c] = face.positions;
+    let e1: Vector = (b - a).into();
+    let e2: Vector = (c - a).into();
+    let mut normal = e1.cross(e2);
+    if let Some(ns) = face.normals {
+        if normal.dot(ns[0] + ns[1] + ns[2]) < 0.0 {
+            normal = -1.0 * normal;
+        }
+    }
+    normal.normalize();
+    Triangle::new(a, b, c, normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        primitives::{mesh::Mesh, triangle::Triangle, Intersectable},
+        rays::ray::Ray,
+        utils::{
+            rgb::RGB,
+            transform::AnimatedTransform,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::{face_triangle, MeshLight};
+
+    #[test]
+    fn mesh_light_samples_by_area() {
+        // a 2x1 panel split into two triangles and a small one next to it, all facing
+        // down from y = 1
+        let down = Vector::new(0.0, -1.0, 0.0);
+        let tris = vec![
+            Triangle::new(
+                Point::new(0.0, 1.0, 0.0),
+                Point::new(2.0, 1.0, 0.0),
+                Point::new(2.0, 1.0, 1.0),
+                down,
+            ),
+            Triangle::new(
+                Point::new(0.0, 1.0, 0.0),
+                Point::new(2.0, 1.0, 1.0),
+                Point::new(0.0, 1.0, 1.0),
+                down,
+            ),
+            Triangle::new(
+                Point::new(3.0, 1.0, 0.0),
+                Point::new(3.5, 1.0, 0.0),
+                Point::new(3.0, 1.0, 0.5),
+                down,
+            ),
+        ];
+        let light = MeshLight::new(RGB::new(10.0, 10.0, 10.0), tris);
+        assert!((light.area - 2.125).abs() < 1e-5);
+        // the power is spread over the whole surface
+        let radiance = light.radiance().r;
+        assert!((radiance * std::f32::consts::PI * light.area - 10.0).abs() < 1e-4);
+
+        let p = Point::new(1.0, 0.0, 0.5);
+        let mut rng = StdRng::seed_from_u64(7);
+        let n = 20_000;
+        let (mut small, mut solid_angle) = (0, 0.0);
+        for _ in 0..n {
+            let ls = light.sample_li(p, 0.0, &[rng.gen(), rng.gen()]).unwrap();
+            let l_point = p + ls.wi * ls.dist;
+            assert!((l_point.y - 1.0).abs() < 1e-4);
+            let pdf = light.pdf_li(p, l_point, down, 0.0);
+            assert!((pdf - ls.pdf).abs() < 1e-3 * ls.pdf);
+            if l_point.x > 2.5 {
+                small += 1;
+            }
+            // estimates the solid angle the light covers
+            solid_angle += 1.0 / ls.pdf;
+        }
+        // the small triangle holds 0.125 / 2.125 of the area
+        let frac = small as f32 / n as f32;
+        assert!((frac - 0.125 / 2.125).abs() < 5e-3, "{}", frac);
+
+        // against the solid angle found by integrating over the surface directly
+        let mut expected = 0.0;
+        let steps = 400;
+        for tri in &light.triangles {
+            for _ in 0..steps {
+                let (q, _) =
+                    crate::primitives::Shape::sample(tri, [rng.gen::<f32>(), rng.gen::<f32>()]);
+                let d = p.vec2point(q);
+                let dist2 = d.dot(d);
+                expected += tri.area() / steps as f32 * (d.y / dist2.sqrt()) / dist2;
+            }
+        }
+        let solid_angle = solid_angle / n as f32;
+        assert!((solid_angle - expected).abs() < 0.03 * expected);
+
+        // the light emits downwards only
+        assert!(light
+            .sample_li(Point::new(1.0, 2.0, 0.5), 0.0, &[0.5, 0.5])
+            .is_none());
+        let bounds = light.bounds().unwrap();
+        assert!((bounds.phi - 10.0).abs() < 1e-4);
+        assert!(bounds.w.dot(down) > 0.9999);
+    }
+
+    #[test]
+    fn moving_mesh_light_samples_at_ray_time() {
+        // a 2x1 panel facing down that turns a quarter around y, doubles in size and
+        // moves along x
+        let mut mesh = Mesh::new(
+            vec![
+                Point::new(0.0, 1.0, 0.0),
+                Point::new(2.0, 1.0, 0.0),
+                Point::new(2.0, 1.0, 1.0),
+                Point::new(0.0, 1.0, 1.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![0, 1, 2, 0, 2, 3],
+            Vec::new(),
+            Vec::new(),
+        );
+        let up = Vector::new(0.0, 1.0, 0.0);
+        mesh.set_rigid_motion(AnimatedTransform {
+            scales: [Vector::new(1.0, 1.0, 1.0), Vector::new(2.0, 2.0, 2.0)],
+            rotations: [(up, 0.0), (up, 90f32.to_radians())],
+            translations: [Vector::new(0.0, 0.0, 0.0), Vector::new(3.0, 0.0, 0.0)],
+            pivot: Vector::new(1.0, 1.0, 0.5),
+        });
+        let light = MeshLight::from_mesh(&mesh, RGB::new(1.0, 1.0, 1.0));
+        assert!((light.area - 2.0).abs() < 1e-5);
+
+        let p = Point::new(2.0, -2.0, 0.5);
+        let time = 0.6;
+        // the same faces held still where they are at that time
+        let faces: Vec<Triangle> = (0..2)
+            .map(|i| face_triangle(&mesh.face_at(i, time)))
+            .collect();
+        let down = faces[0].normal;
+        assert!(down.y < -0.999);
+        let still = MeshLight::new(light.power, faces);
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..1000 {
+            let ls = light.sample_li(p, time, &[rng.gen(), rng.gen()]).unwrap();
+            // the point is on the mesh as rays of that time find it
+            let ray = Ray::new(p, ls.wi).with_time(time);
+            let depth = mesh.intersect(&ray).unwrap().depth;
+            assert!((depth - ls.dist).abs() < 1e-3, "{} {}", depth, ls.dist);
+            let l_point = p + ls.wi * ls.dist;
+            assert!((light.pdf_li(p, l_point, down, time) - ls.pdf).abs() < 1e-3 * ls.pdf);
+            assert!((still.pdf_li(p, l_point, down, 0.0) - ls.pdf).abs() < 1e-3 * ls.pdf);
+        }
+
+        // the light bounds cover the whole motion
+        let bb = light.bounds().unwrap().bb;
+        assert_eq!(bb, mesh.aabb);
+    }
+}
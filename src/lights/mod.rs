@@ -1,10 +1,38 @@
-use crate::primitives::triangle::Triangle;
+use std::f32::consts::PI;
+
 use crate::{
-    primitives::Intersectable,
+    primitives::{AnyShape, Intersectable, Shape},
     rays::{intersection::IntersectionData, ray::Ray},
-    utils::{rgb::RGB, vector::Point},
+    utils::{
+        aabb::AABB,
+        rgb::RGB,
+        vector::{Point, Vector},
+    },
+};
+
+use self::{
+    directional::DirectionalLight, environment::EnvironmentLight, light_sampler::LightBounds,
+    mesh_light::MeshLight, spot::SpotLight,
 };
 
+pub mod directional;
+pub mod environment;
+pub mod light_sampler;
+pub mod mesh_light;
+pub mod spot;
+
+// incident radiance from a light towards a point, `pdf` is in solid angle measure
+// and already accounts for the distance and the cosine at the light
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LightSample {
+    pub li: RGB,
+    pub wi: Vector,
+    // infinite for lights at infinity
+    pub dist: f32,
+    pub pdf: f32,
+    pub is_delta: bool,
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct AmbientLight {
     pub color: RGB,
@@ -16,55 +44,351 @@ pub struct PointLight {
     pub position: Point,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+// emitting surface sampled uniformly by area
+#[derive(Debug, Clone, Copy)]
 pub struct AreaLight {
     pub intensity: RGB,
     pub power: RGB,
-    pub tri: Triangle,
+    pub shape: AnyShape,
     pub pdf: f32,
 }
 
 impl AreaLight {
-    pub fn new(power: RGB, tri: Triangle) -> Self {
-        let pdf = 1.0 / tri.area();
+    pub fn new(power: RGB, shape: AnyShape) -> Self {
+        // degenerate and unbounded shapes stay registered but never emit
+        let area = shape.area();
+        let pdf = if area > 0.0 && area.is_finite() {
+            1.0 / area
+        } else {
+            0.0
+        };
         let intensity = power * pdf;
         Self {
             intensity,
             power,
-            tri,
+            shape,
             pdf,
         }
     }
 
-    pub fn stochastic_radiance(&self, r: &[f32; 2]) -> (RGB, Point) {
-        let sqrt_r0 = r[0].sqrt();
-        let alpha = 1.0 - sqrt_r0;
-        let beta = (1.0 - r[1]) * sqrt_r0;
-        let gamma = r[1] * sqrt_r0;
-        let mut p = Point::default();
-        p.x = alpha * self.tri.v1.x + beta * self.tri.v2.x + gamma * self.tri.v3.x;
-        p.y = alpha * self.tri.v1.y + beta * self.tri.v2.y + gamma * self.tri.v3.y;
-        p.z = alpha * self.tri.v1.z + beta * self.tri.v2.z + gamma * self.tri.v3.z;
-        return (self.intensity / self.pdf, p);
+    // emitted radiance of a one sided lambertian emitter, power = pi * area * radiance
+    pub fn radiance(&self) -> RGB {
+        self.intensity / PI
+    }
+
+    // radiance leaving the light at `p` towards `w`
+    pub fn le(&self, p: Point, w: Vector) -> RGB {
+        if w.dot(self.shape.normal_at(p)) > 0.0 {
+            self.radiance()
+        } else {
+            RGB::default()
+        }
+    }
+
+    pub fn sample_li(&self, p: Point, r: &[f32; 2]) -> Option<LightSample> {
+        if self.pdf == 0.0 {
+            return None;
+        }
+        // spheres seen from outside are sampled within the cone they subtend
+        if let AnyShape::Sphere(sphere) = &self.shape {
+            if let Some((l_point, _, pdf)) = sphere.sample_cone(p, *r) {
+                let mut wi: Vector = (l_point - p).into();
+                let dist = wi.norm();
+                wi = wi / dist;
+                return Some(LightSample {
+                    li: self.radiance(),
+                    wi,
+                    dist,
+                    pdf,
+                    is_delta: false,
+                });
+            }
+        }
+        let (l_point, l_normal) = self.shape.sample(*r);
+        let mut wi: Vector = (l_point - p).into();
+        let dist = wi.norm();
+        if dist == 0.0 {
+            return None;
+        }
+        wi = wi / dist;
+
+        let cos_l = -wi.dot(l_normal);
+        if cos_l <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            li: self.radiance(),
+            wi,
+            dist,
+            pdf: self.pdf * dist * dist / cos_l,
+            is_delta: false,
+        })
+    }
+
+    // solid angle pdf of sample_li picking `l_point` as seen from `p`
+    pub fn pdf_li(&self, p: Point, l_point: Point) -> f32 {
+        if let AnyShape::Sphere(sphere) = &self.shape {
+            if let Some(pdf) = sphere.cone_pdf(p) {
+                return pdf;
+            }
+        }
+        let mut wi: Vector = (l_point - p).into();
+        let dist2 = wi.dot(wi);
+        wi.normalize();
+        let cos_l = -wi.dot(self.shape.normal_at(l_point));
+        if cos_l <= 0.0 {
+            return 0.0;
+        }
+        self.pdf * dist2 / cos_l
     }
 }
 
 impl Intersectable for AreaLight {
     fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
-        return self.tri.intersect(ray);
+        return self.shape.intersect(ray);
     }
 
     fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
-        return self
-            .tri
-            .intersect(ray)
-            .is_some_and(|isect| isect.depth < depth);
+        return self.shape.test_line_intersect(ray, depth);
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Light {
     Ambient(AmbientLight),
     Point(PointLight),
     Area(AreaLight),
+    Mesh(MeshLight),
+    Environment(EnvironmentLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+impl Light {
+    // None for lights that cannot be sampled by direction, i.e. ambient lights; `time`
+    // is that of the ray reaching `p`, moving lights are sampled where they are then
+    pub fn sample_li(&self, p: Point, time: f32, r: &[f32; 2]) -> Option<LightSample> {
+        match self {
+            Light::Ambient(_) => None,
+            Light::Point(pl) => {
+                let mut wi: Vector = (pl.position - p).into();
+                let dist = wi.norm();
+                if dist == 0.0 {
+                    return None;
+                }
+                wi = wi / dist;
+                Some(LightSample {
+                    li: pl.color / (dist * dist),
+                    wi,
+                    dist,
+                    pdf: 1.0,
+                    is_delta: true,
+                })
+            }
+            Light::Area(al) => al.sample_li(p, r),
+            Light::Mesh(ml) => ml.sample_li(p, time, r),
+            Light::Environment(el) => el.sample_li(p, r),
+            Light::Spot(sl) => sl.sample_li(p, r),
+            Light::Directional(dl) => dl.sample_li(p, r),
+        }
+    }
+
+    // solid angle pdf of sample_li picking `l_point` at the given time, where the
+    // light's surface has normal `l_normal`
+    pub fn pdf_li(&self, p: Point, l_point: Point, l_normal: Vector, time: f32) -> f32 {
+        match self {
+            Light::Area(al) => al.pdf_li(p, l_point),
+            Light::Mesh(ml) => ml.pdf_li(p, l_point, l_normal, time),
+            _ => 0.0,
+        }
+    }
+
+    // whether rays escaping the scene can reach the light
+    pub fn is_infinite(&self) -> bool {
+        match self {
+            Light::Environment(_) => true,
+            Light::Directional(dl) => !dl.is_delta(),
+            _ => false,
+        }
+    }
+
+    // radiance a light at infinity sends along a ray escaping in direction `w`
+    pub fn le_dir(&self, w: Vector) -> RGB {
+        match self {
+            Light::Environment(el) => el.le(w),
+            Light::Directional(dl) => dl.le(w),
+            _ => RGB::default(),
+        }
+    }
+
+    // solid angle pdf of sample_li picking direction `w`, for lights at infinity
+    pub fn pdf_dir(&self, w: Vector) -> f32 {
+        match self {
+            Light::Environment(el) => el.pdf_dir(w),
+            Light::Directional(dl) => dl.pdf_dir(w),
+            _ => 0.0,
+        }
+    }
+
+    // luminance of the emitted power, lights at infinity deliver it over the disc cast
+    // by a scene of radius `scene_radius`
+    pub fn phi(&self, scene_radius: f32) -> f32 {
+        let disc = PI * scene_radius * scene_radius;
+        match self {
+            Light::Ambient(_) => 0.0,
+            Light::Point(pl) => 4.0 * PI * pl.color.y(),
+            // full intensity inside the inner cone and half of it over the falloff
+            Light::Spot(sl) => {
+                2.0 * PI * sl.color.y() * (1.0 - 0.5 * (sl.cos_falloff_start + sl.cos_total_width))
+            }
+            Light::Area(al) if al.pdf > 0.0 => al.power.y(),
+            Light::Area(_) => 0.0,
+            Light::Mesh(ml) if ml.pdf_area() > 0.0 => ml.power.y(),
+            Light::Mesh(_) => 0.0,
+            // the map's average over uv times the 2 pi^2 of solid angle per unit of uv
+            Light::Environment(el) => {
+                disc * 2.0 * PI * PI * el.scale * el.distribution.marginal.func_int
+            }
+            Light::Directional(dl) => disc * dl.color.y(),
+        }
+    }
+
+    // based on PBRT's 4th ed. book, sec 12.6.3
+    // where the light is and where it emits to, None for lights at infinity and
+    // ambient lights
+    pub fn bounds(&self) -> Option<LightBounds> {
+        let point_bb = |p: Point| {
+            let mut bb = AABB::default();
+            bb.update(&p);
+            bb
+        };
+        match self {
+            Light::Point(pl) => Some(LightBounds {
+                bb: point_bb(pl.position),
+                phi: 4.0 * PI * pl.color.y(),
+                w: Vector::new(0.0, 0.0, 1.0),
+                cos_theta_o: -1.0,
+                cos_theta_e: 0.0,
+                two_sided: false,
+            }),
+            Light::Spot(sl) => Some(LightBounds {
+                bb: point_bb(sl.position),
+                phi: 4.0 * PI * sl.color.y(),
+                w: sl.direction,
+                cos_theta_o: sl.cos_falloff_start,
+                cos_theta_e: (sl.cos_total_width.acos() - sl.cos_falloff_start.acos()).cos(),
+                two_sided: false,
+            }),
+            Light::Area(al) => {
+                if al.pdf == 0.0 {
+                    return None;
+                }
+                let bb = al.shape.bounds()?;
+                // flat emitters light the half space in front of them, curved ones
+                // every direction
+                let (w, cos_theta_o) = match &al.shape {
+                    AnyShape::Triangle(_) | AnyShape::Disk(_) | AnyShape::Quad(_) => {
+                        (al.shape.normal_at(bb.centroid()), 1.0)
+                    }
+                    _ => (Vector::new(0.0, 0.0, 1.0), -1.0),
+                };
+                Some(LightBounds {
+                    bb,
+                    phi: al.power.y(),
+                    w,
+                    cos_theta_o,
+                    cos_theta_e: 0.0,
+                    two_sided: false,
+                })
+            }
+            Light::Mesh(ml) => ml.bounds(),
+            Light::Ambient(_) | Light::Environment(_) | Light::Directional(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        primitives::{triangle::Triangle, AnyShape},
+        utils::{
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::{directional::DirectionalLight, spot::SpotLight, AreaLight};
+
+    #[test]
+    fn area_light_pdf_matches_sample() {
+        let tri = Triangle::new(
+            Point::new(0.0, 1.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(0.0, 1.0, 1.0),
+            Vector::new(0.0, -1.0, 0.0),
+        );
+        let light = AreaLight::new(RGB::new(1.0, 1.0, 1.0), AnyShape::Triangle(tri));
+        let p = Point::new(0.2, 0.0, 0.3);
+
+        for r in [[0.1, 0.9], [0.5, 0.5], [0.8, 0.2]] {
+            let ls = light.sample_li(p, &r).unwrap();
+            let l_point = p + ls.wi * ls.dist;
+            assert!((light.pdf_li(p, l_point) - ls.pdf).abs() < 1e-4 * ls.pdf);
+        }
+        // the light only emits below its plane
+        let above = Point::new(0.2, 2.0, 0.3);
+        assert!(light.sample_li(above, &[0.5, 0.5]).is_none());
+    }
+
+    #[test]
+    fn spot_and_directional_lights() {
+        let spot = SpotLight::new(
+            RGB::new(4.0, 4.0, 4.0),
+            Point::new(0.0, 2.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            30f32.to_radians(),
+            10f32.to_radians(),
+        );
+        // full intensity in the inner cone, fading in the outer 10 degrees, dark outside
+        let below = spot
+            .sample_li(Point::new(0.0, 0.0, 0.0), &[0.5, 0.5])
+            .unwrap();
+        assert!(below.is_delta && (below.li.r - 1.0).abs() < 1e-5);
+        let at_angle = |deg: f32| {
+            let p = Point::new(2.0 * deg.to_radians().tan(), 0.0, 0.0);
+            spot.sample_li(p, &[0.5, 0.5])
+                .map_or(0.0, |ls| spot.falloff(-1.0 * ls.wi))
+        };
+        assert_eq!(at_angle(15.0), 1.0);
+        assert!(at_angle(25.0) > 0.0 && at_angle(25.0) < 1.0);
+        assert!(at_angle(24.0) > at_angle(26.0));
+        assert_eq!(at_angle(35.0), 0.0);
+
+        // the sun's disc delivers `color` as irradiance to a surface facing it
+        let dir = Vector::new(0.3, -1.0, 0.2);
+        let mut n = -1.0 * dir;
+        n.normalize();
+        let sun = DirectionalLight::new(RGB::new(3.0, 3.0, 3.0), dir, 5f32.to_radians());
+        let p = Point::new(1.0, 0.0, 0.0);
+        let mut rng = StdRng::seed_from_u64(9);
+        let samples = 10_000;
+        let mut irradiance = 0.0;
+        for _ in 0..samples {
+            let ls = sun.sample_li(p, &[rng.gen(), rng.gen()]).unwrap();
+            assert!(!ls.is_delta && ls.dist.is_infinite());
+            assert!((sun.pdf_dir(ls.wi) - ls.pdf).abs() < 1e-3 * ls.pdf);
+            assert!((sun.le(ls.wi).g - ls.li.g).abs() < 1e-3 * ls.li.g);
+            irradiance += ls.li.r * ls.wi.dot(n) / ls.pdf;
+        }
+        let irradiance = irradiance / samples as f32;
+        assert!((irradiance - 3.0).abs() < 1e-2, "{}", irradiance);
+        assert!(sun.le(Vector::new(0.0, 1.0, 0.0)).is_zero());
+
+        let sharp = DirectionalLight::new(RGB::new(3.0, 3.0, 3.0), dir, 0.0);
+        let ls = sharp.sample_li(p, &[0.5, 0.5]).unwrap();
+        assert!(ls.is_delta && ls.wi.dot(n) > 0.9999);
+        assert!(sharp.le(ls.wi).is_zero());
+    }
 }
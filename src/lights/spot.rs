@@ -0,0 +1,74 @@
+use crate::utils::{
+    rgb::RGB,
+    vector::{Point, Vector},
+};
+
+use super::LightSample;
+
+// based on PBRT's 3rd ed. book, sec 12.3
+// point light shining into a cone around `direction`, at full intensity up to
+// `cos_falloff_start` and fading smoothly to nothing at `cos_total_width`
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub color: RGB,
+    pub position: Point,
+    pub direction: Vector,
+    pub cos_total_width: f32,
+    pub cos_falloff_start: f32,
+}
+
+impl SpotLight {
+    // `cone_angle` is the half angle of the lit cone and the light fades over its
+    // outer `cone_delta`, both in radians
+    pub fn new(
+        color: RGB,
+        position: Point,
+        direction: Vector,
+        cone_angle: f32,
+        cone_delta: f32,
+    ) -> Self {
+        let mut direction = direction;
+        direction.normalize();
+        Self {
+            color,
+            position,
+            direction,
+            cos_total_width: cone_angle.cos(),
+            cos_falloff_start: (cone_angle - cone_delta).max(0.0).cos(),
+        }
+    }
+
+    // fraction of the intensity sent along `w`
+    pub fn falloff(&self, w: Vector) -> f32 {
+        let cos_theta = w.dot(self.direction);
+        if cos_theta < self.cos_total_width {
+            return 0.0;
+        }
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        let delta =
+            (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        (delta * delta) * (delta * delta)
+    }
+
+    pub fn sample_li(&self, p: Point, _r: &[f32; 2]) -> Option<LightSample> {
+        let mut wi: Vector = (self.position - p).into();
+        let dist = wi.norm();
+        if dist == 0.0 {
+            return None;
+        }
+        wi = wi / dist;
+        let falloff = self.falloff(-1.0 * wi);
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            li: self.color * falloff / (dist * dist),
+            wi,
+            dist,
+            pdf: 1.0,
+            is_delta: true,
+        })
+    }
+}
@@ -1,98 +1,101 @@
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::{path::Path, thread, time::Instant};
-
-use camera::{perspective::Perspective, Camera};
-use images::image_rgb::{self, ImageRGB};
-use lights::Light;
-use minifb::{Key, Window, WindowOptions};
-use render::standard_render;
-use scene::Scene;
+use std::f32::consts::PI;
+use std::io::Write;
+use std::process::ExitCode;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use std::{
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use camera::{controller::CameraController, AnyCamera, Camera};
+use cli::{Command, RenderArgs};
+use film::Film;
+use images::{
+    image_hdr, image_pfm,
+    image_rgb::{self, ImageRGB},
+};
+use lights::light_sampler::LightSamplerType;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use render::{parallel_render, standard_render, RendererType};
+use samplers::SamplerType;
+use scene::{
+    scene_file::{CameraDesc, LoadedScene, RenderDesc, SceneFile, ShaderDesc},
+    Scene,
+};
 use shaders::Shader;
 use utils::{
-    rgb::RGB,
     vector::{Point, Vector},
     Extent2D,
 };
 
-use crate::{
-    lights::AreaLight, primitives::triangle::Triangle, render::IncrementalRenderer,
-    shaders::path_tracer_shader::PathTracerShader, swapchain::DoubleBufferSwapChain,
-};
+use crate::{render::IncrementalRenderer, swapchain::DoubleBufferSwapChain};
 
+mod accel;
+mod bsdfs;
 mod camera;
+mod cli;
+mod film;
 mod images;
 mod lights;
 mod primitives;
 mod rays;
 mod render;
+mod samplers;
 mod scene;
 mod shaders;
 mod swapchain;
+mod textures;
 mod utils;
 
-fn main() {
-    let height = 800;
-    let width = 800;
-
-    let eye = Point::new(280.0, 375.0, -800.0);
-    let at = Point::new(280.0, 300.0, 280.0);
-    let up = Vector::new(0.0, 1.0, 0.0);
-    let fov_w = 60f32;
-    let fov_h = fov_w * width as f32 / height as f32;
-    let fov_w_rad = fov_w * 3.14 / 180.0;
-    let fov_h_rad = fov_h * 3.14 / 180.0;
-
-    let camera = Perspective::new(
-        eye,
-        at,
-        up,
-        Extent2D { width, height },
-        fov_w_rad,
-        fov_h_rad,
-    );
-    let mut scene = Scene::new();
-    scene.load_obj_file(Path::new("./models/cornell_box_VI.obj"));
-
-    let b_light1 = Light::Area(AreaLight::new(
-        RGB::new(1.0, 1.0, 1.0),
-        Triangle::new(
-            Point::new(343.0, 548.0, 227.0),
-            Point::new(343.0, 548.0, 332.0),
-            Point::new(213.0, 548.0, 332.0),
-            Vector::new(0.0, -1.0, 0.0),
-        ),
-    ));
-    let b_light2 = Light::Area(AreaLight::new(
-        RGB::new(1.0, 1.0, 1.0),
-        Triangle::new(
-            Point::new(213.0, 548.0, 332.0),
-            Point::new(213.0, 548.0, 227.0),
-            Point::new(343.0, 548.0, 227.0),
-            Vector::new(0.0, -1.0, 0.0),
-        ),
-    ));
-
-    scene.add_light(b_light1);
-    scene.add_light(b_light2);
-
-    let shader = PathTracerShader {
-        background: RGB {
-            r: 0.05,
-            g: 0.05,
-            b: 0.55,
-        },
-        collision_bias: 0.001f32,
-        reflection_depth: 2,
-        continue_prob: 0.5,
+fn main() -> ExitCode {
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+        Command::View { scene } => view(&scene),
+        Command::Render(args) => render_headless(&args),
     };
 
-    //let shader = DistributedShader{
-    //    background: RGB { r: 0.05, g: 0.05, b: 0.55 },
-    //    shadow_bias: 0.001f32,
-    //    reflection_depth: 2,
-    //};
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let renderer = IncrementalRenderer::new(1, Some(64), true);
+fn view(scene_path: &Path) -> Result<(), String> {
+    let LoadedScene {
+        mut scene,
+        camera,
+        shader,
+        render,
+    } = SceneFile::load(scene_path).map_err(|e| e.to_string())?;
+    // the controller orbits around the camera as the file describes it
+    let camera_desc = SceneFile::read(scene_path)
+        .map_err(|e| e.to_string())?
+        .camera;
+    let Extent2D { width, height } = camera.get_resolution();
+
+    let bvh_stats = scene.build_accel();
+    println!("bvh: {}", bvh_stats);
+    println!("{}", VIEWER_CONTROLS);
+
+    let renderer = render.build_renderer();
 
     let mut window = Window::new(
         "yep",
@@ -100,21 +103,286 @@ fn main() {
         height as usize,
         WindowOptions::default(),
     )
-    .unwrap();
+    .map_err(|e| format!("cannot open window: {}", e))?;
 
     window.set_target_fps(60);
 
     let inst = Instant::now();
 
-    render_loop_with_swapchain(camera, scene, shader, window, width, height, renderer);
+    render_loop_with_swapchain(camera_desc, scene, shader, window, width, height, renderer);
     //render_loop(camera, scene, shader, window, width, height, renderer);
-    //render_loop_sequential(camera, scene, shader, window, width, height, 128, true);
+    //render_loop_sequential(camera, scene, shader, window, width, height, render);
 
     println!(
         "total time: {}.{} seconds",
         inst.elapsed().as_secs(),
         inst.elapsed().subsec_millis()
     );
+    Ok(())
+}
+
+const VIEWER_CONTROLS: &str = "\
+controls:
+  left drag     orbit around the target
+  right drag    pan
+  scroll        zoom
+  W A S D       fly, Q E down and up, hold shift to go faster
+  R             back to the scene file's camera
+  P             print the camera as a scene file snippet
+  Escape        quit";
+
+// applies the mouse and keyboard input of the last frame to the controller, returns
+// whether the camera moved
+fn navigate(
+    window: &Window,
+    controller: &mut CameraController,
+    last_mouse: &mut Option<(f32, f32)>,
+    dt: f32,
+    height: u32,
+) -> bool {
+    let mut moved = false;
+
+    let mouse = window.get_mouse_pos(MouseMode::Discard);
+    if let (Some((x, y)), Some((last_x, last_y))) = (mouse, *last_mouse) {
+        // drags are measured in image heights
+        let dx = (x - last_x) / height as f32;
+        let dy = (y - last_y) / height as f32;
+        if dx != 0.0 || dy != 0.0 {
+            if window.get_mouse_down(MouseButton::Left) {
+                controller.orbit(dx * PI, dy * PI);
+                moved = true;
+            } else if window.get_mouse_down(MouseButton::Right) {
+                controller.pan(dx, dy);
+                moved = true;
+            }
+        }
+    }
+    *last_mouse = mouse;
+
+    if let Some((_, scroll)) = window.get_scroll_wheel() {
+        if scroll != 0.0 {
+            controller.zoom(scroll.clamp(-3.0, 3.0));
+            moved = true;
+        }
+    }
+
+    let axis = |pos: Key, neg: Key| {
+        window.is_key_down(pos) as i32 as f32 - window.is_key_down(neg) as i32 as f32
+    };
+    let forward = axis(Key::W, Key::S);
+    let right = axis(Key::D, Key::A);
+    let up = axis(Key::E, Key::Q);
+    if forward != 0.0 || right != 0.0 || up != 0.0 {
+        // half the target distance per second
+        let mut speed = 0.5 * controller.distance() * dt;
+        if window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift) {
+            speed *= 4.0;
+        }
+        controller.fly(forward * speed, right * speed, up * speed);
+        moved = true;
+    }
+    moved
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Ppm,
+    Pfm,
+    Hdr,
+}
+
+// format the image at path is saved in, from its extension
+fn output_format(path: &Path) -> Result<OutputFormat, String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("pfm") => Ok(OutputFormat::Pfm),
+        Some("hdr") => Ok(OutputFormat::Hdr),
+        Some("ppm") => Ok(OutputFormat::Ppm),
+        _ => Err(format!(
+            "unsupported output format {}, use .ppm, .pfm or .hdr",
+            path.display()
+        )),
+    }
+}
+
+fn save_image<F>(image: &ImageRGB, path: &Path, tonemapper: F) -> Result<(), String>
+where
+    F: Fn(f32) -> f32,
+{
+    let saved = match output_format(path)? {
+        OutputFormat::Pfm => image_pfm::save(image, path),
+        OutputFormat::Hdr => image_hdr::save(image, path),
+        OutputFormat::Ppm => image.to_ppm(tonemapper).save(path),
+    };
+    saved.map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+fn render_headless(args: &RenderArgs) -> Result<(), String> {
+    output_format(&args.output)?;
+    if let Some(heatmap) = &args.heatmap {
+        output_format(heatmap)?;
+    }
+
+    let load_inst = Instant::now();
+    let mut desc = SceneFile::read(&args.scene).map_err(|e| e.to_string())?;
+    if let Some(width) = args.width {
+        desc.camera.width = width;
+    }
+    if let Some(height) = args.height {
+        desc.camera.height = height;
+    }
+    if let Some(spp) = args.spp {
+        desc.render.spp = spp;
+    }
+    if let Some(name) = &args.shader {
+        desc.shader = desc.shader.with_type(name).ok_or_else(|| {
+            format!(
+                "unknown shader {:?}, expected one of {}",
+                name,
+                ShaderDesc::NAMES.join(", ")
+            )
+        })?;
+    }
+    if let Some(seed) = args.seed {
+        desc.render.seed = seed;
+    }
+    if let Some(name) = &args.sampler {
+        desc.render.sampler = SamplerType::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown sampler {:?}, expected one of {}",
+                name,
+                SamplerType::NAMES.join(", ")
+            )
+        })?;
+    }
+    if let Some(name) = &args.light_sampler {
+        desc.render.light_sampler = LightSamplerType::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown light sampler {:?}, expected one of {}",
+                name,
+                LightSamplerType::NAMES.join(", ")
+            )
+        })?;
+    }
+    let renderer_type = match &args.renderer {
+        Some(name) => RendererType::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown renderer {:?}, expected one of {}",
+                name,
+                RendererType::NAMES.join(", ")
+            )
+        })?,
+        None => RendererType::default(),
+    };
+    if renderer_type != RendererType::Incremental
+        && (args.heatmap.is_some() || desc.render.adaptive.is_some())
+    {
+        return Err("adaptive sampling and --heatmap need the incremental renderer".to_string());
+    }
+    let dir = args.scene.parent().unwrap_or(Path::new(""));
+    let LoadedScene {
+        mut scene,
+        camera,
+        shader,
+        render,
+    } = desc.build(dir).map_err(|e| e.to_string())?;
+    let bvh_stats = scene.build_accel();
+    let Extent2D { width, height } = camera.get_resolution();
+
+    if !args.quiet {
+        println!(
+            "loaded {} in {} ms",
+            args.scene.display(),
+            load_inst.elapsed().as_millis()
+        );
+        println!("bvh: {}", bvh_stats);
+        println!("rendering {}x{} at {} spp", width, height, render.spp);
+    }
+
+    let mut image = ImageRGB::new(width, height);
+    let render_inst = Instant::now();
+    if renderer_type != RendererType::Incremental {
+        let mut film = Film::new(width, height, render.filter.build());
+        let sampler = render.build_sampler();
+        let spp = render.spp as usize;
+        if renderer_type == RendererType::Parallel {
+            parallel_render(
+                &camera,
+                &scene,
+                &shader,
+                &sampler,
+                &mut film,
+                spp,
+                render.jitter,
+            );
+        } else {
+            standard_render(
+                &camera,
+                &scene,
+                &shader,
+                &sampler,
+                &mut film,
+                spp,
+                render.jitter,
+            );
+        }
+        film.write_image(&mut image);
+        if !args.quiet {
+            println!("rendered in {:.2} s", render_inst.elapsed().as_secs_f32());
+        }
+        save_image(&image, &args.output, image_rgb::tonemap_reinhard)?;
+        if !args.quiet {
+            println!("saved {}", args.output.display());
+        }
+        return Ok(());
+    }
+
+    let mut renderer = render.build_renderer();
+    while !renderer.has_finished() {
+        renderer.render(&camera, &scene, &shader, &mut image);
+        if !args.quiet {
+            let elapsed = render_inst.elapsed().as_secs_f32();
+            let done = renderer.spp_current as f32 / render.spp as f32;
+            print!(
+                "\r[{:>3.0}%] {}/{} spp | {:.1} s elapsed | ~{:.1} s left ",
+                done * 100.0,
+                renderer.spp_current,
+                render.spp,
+                elapsed,
+                elapsed / done - elapsed
+            );
+            if renderer.adaptive.is_some() {
+                print!("| {} pixels active ", renderer.active_pixels());
+            }
+            let _ = std::io::stdout().flush();
+        }
+    }
+    if !args.quiet {
+        println!("\nrendered in {:.2} s", render_inst.elapsed().as_secs_f32());
+        if renderer.adaptive.is_some() {
+            let total: u64 = renderer.stats.iter().map(|s| s.count as u64).sum();
+            println!(
+                "adaptive: {:.1} spp per pixel on average",
+                total as f32 / renderer.stats.len() as f32
+            );
+        }
+    }
+
+    save_image(&image, &args.output, image_rgb::tonemap_reinhard)?;
+    if !args.quiet {
+        println!("saved {}", args.output.display());
+    }
+    if let Some(path) = &args.heatmap {
+        // the heatmap is already in display range
+        save_image(&renderer.heatmap(), path, |v| v)?;
+        if !args.quiet {
+            println!("saved {}", path.display());
+        }
+    }
+    Ok(())
 }
 
 fn render_loop_sequential<C, S>(
@@ -124,19 +392,28 @@ fn render_loop_sequential<C, S>(
     mut window: Window,
     width: u32,
     height: u32,
-    spp: usize,
-    jitter: bool,
+    render: RenderDesc,
 ) where
     C: Camera + std::marker::Sync,
     S: Shader + std::marker::Sync,
 {
-    let mut image = ImageRGB::new(height, width);
+    let mut image = ImageRGB::new(width, height);
+    let mut film = Film::new(width, height, render.filter.build());
 
     let mut buf: Vec<u32> = std::iter::repeat(0)
         .take((width * height) as usize)
         .collect();
 
-    standard_render(&camera, &scene, &shader, &mut image, spp, jitter);
+    standard_render(
+        &camera,
+        &scene,
+        &shader,
+        &render.build_sampler(),
+        &mut film,
+        render.spp as usize,
+        render.jitter,
+    );
+    film.write_image(&mut image);
     image.write_to_0rgb_u32(&mut buf, image_rgb::tonemap_reinhard);
 
     window
@@ -160,7 +437,7 @@ fn render_loop<C, S>(
     C: Camera + std::marker::Sync,
     S: Shader + std::marker::Sync,
 {
-    let mut image = ImageRGB::new(height, width);
+    let mut image = ImageRGB::new(width, height);
 
     let mut buf: Vec<u32> = std::iter::repeat(0)
         .take((width * height) as usize)
@@ -193,8 +470,8 @@ fn render_loop<C, S>(
     }
 }
 
-fn render_loop_with_swapchain<C, S>(
-    camera: C,
+fn render_loop_with_swapchain<S>(
+    mut camera_desc: CameraDesc,
     scene: Scene,
     shader: S,
     mut window: Window,
@@ -202,18 +479,35 @@ fn render_loop_with_swapchain<C, S>(
     height: u32,
     mut renderer: IncrementalRenderer,
 ) where
-    C: Camera + std::marker::Sync,
     S: Shader + std::marker::Sync,
 {
     let swpchain = DoubleBufferSwapChain::new(width, height);
 
     let frame_number = AtomicU64::new(0);
+    // camera the event loop moved to, picked up by the renderer before its next pass
+    let moved_camera: Mutex<Option<AnyCamera>> = Mutex::new(None);
+    let initial = CameraController::new(
+        Point::new(camera_desc.eye[0], camera_desc.eye[1], camera_desc.eye[2]),
+        Point::new(camera_desc.at[0], camera_desc.at[1], camera_desc.at[2]),
+        Vector::new(camera_desc.up[0], camera_desc.up[1], camera_desc.up[2]),
+    );
+    let mut camera = camera_desc.build();
 
     thread::scope(|s| {
         s.spawn(|| {
-            let mut image = ImageRGB::new(height, width);
+            let mut image = ImageRGB::new(width, height);
+
+            while !swpchain.is_closed() {
+                let moved = moved_camera.lock().unwrap().take();
+                if let Some(moved) = moved {
+                    camera = moved;
+                    renderer.reset();
+                }
+                if renderer.has_finished() {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
 
-            while !renderer.has_finished() {
                 let inst = Instant::now();
                 renderer.render(&camera, &scene, &shader, &mut image);
 
@@ -229,22 +523,42 @@ fn render_loop_with_swapchain<C, S>(
                 frame_number.fetch_add(1, Ordering::Relaxed);
                 println!("([renderer] frame-gen: {} ms) ", inst.elapsed().as_millis());
             }
-            swpchain.close();
             println!("[renderer] closing");
         });
 
+        let mut controller = initial;
+        let mut last_mouse = None;
+        let mut last_frame = Instant::now();
         while window.is_open() && !window.is_key_down(Key::Escape) {
             let inst = Instant::now();
-            let is_open = swpchain.wait_use_front(|buffer| {
+            let dt = last_frame.elapsed().as_secs_f32();
+            last_frame = inst;
+
+            let mut moved = navigate(&window, &mut controller, &mut last_mouse, dt, height);
+            if window.is_key_pressed(Key::R, KeyRepeat::No) {
+                controller = initial;
+                moved = true;
+            }
+            if moved {
+                camera_desc.eye = [controller.eye.x, controller.eye.y, controller.eye.z];
+                camera_desc.at = [controller.at.x, controller.at.y, controller.at.z];
+                *moved_camera.lock().unwrap() = Some(camera_desc.build());
+            }
+            if window.is_key_pressed(Key::P, KeyRepeat::No) {
+                println!("{}", camera_desc.to_toml());
+            }
+
+            let presented = swpchain.try_use_front(|buffer| {
                 //let upd_inst = Instant::now();
                 window
                     .update_with_buffer(buffer, width as usize, height as usize)
                     .unwrap();
                 //print!("[event loop] upd: {} micros | ", upd_inst.elapsed().as_micros());
             });
-            if !is_open {
-                println!("[event loop] swpchain closed");
+            if !presented {
+                // keeps polling input while the renderer works or idles
                 window.update();
+                continue;
             }
             println!(
                 "[event loop] frame: {} | elapsed: {} ms",
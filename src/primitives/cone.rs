@@ -0,0 +1,108 @@
+use std::f32::consts::PI;
+
+use crate::{
+    rays::{intersection::IntersectionData, ray::Ray},
+    utils::{
+        aabb::AABB,
+        transform::Transform,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{
+    azimuth, cylinder::axis_frame, first_root, solve_quadratic, surface_hit, Intersectable, Shape,
+};
+
+// based on PBRT's 3rd ed. book, sec 3.5
+// open cone around the z axis of its own frame, `radius` wide at the base and closing
+// at the apex `height` above it; u runs around the axis and v up to the apex, normals
+// point outwards
+#[derive(Debug, Clone, Copy)]
+pub struct Cone {
+    pub world_from_object: Transform,
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Cone {
+    // `axis` goes from the center of the base to the apex
+    pub fn new(base: Point, axis: Vector, radius: f32) -> Self {
+        let (world_from_object, height) = axis_frame(base, axis);
+        Self {
+            world_from_object,
+            radius,
+            height,
+        }
+    }
+
+    // outward normal at azimuth phi, the same all along a line from base to apex
+    fn normal(&self, phi: f32) -> Vector {
+        let mut n = Vector::new(phi.cos(), phi.sin(), self.radius / self.height);
+        n.normalize();
+        n
+    }
+}
+
+impl Intersectable for Cone {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        // x^2 + y^2 = k (h - z)^2 with k the squared slope
+        let r = self.world_from_object.inverse().apply_ray(ray);
+        let (o, d) = (r.origin, r.direction);
+        let h = self.height;
+        let k = (self.radius / h) * (self.radius / h);
+        let a = d.x * d.x + d.y * d.y - k * d.z * d.z;
+        let b = 2.0 * (d.x * o.x + d.y * o.y + k * d.z * (h - o.z));
+        let c = o.x * o.x + o.y * o.y - k * (h - o.z) * (h - o.z);
+        // the equation also holds on the mirrored cone above the apex
+        let t = first_root(solve_quadratic(a, b, c)?, |t| {
+            (0.0..=h).contains(&(o.z + t * d.z))
+        })?;
+
+        let p = r.origin + r.direction * t;
+        let phi = azimuth(p.x, p.y);
+        let uv = [phi / (2.0 * PI), p.z / h];
+        let xf = &self.world_from_object;
+        Some(surface_hit(
+            ray,
+            t,
+            xf.apply_point(p),
+            xf.apply_vector(self.normal(phi)),
+            uv,
+        ))
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        self.intersect(ray).is_some_and(|isect| isect.depth < depth)
+    }
+}
+
+impl Shape for Cone {
+    fn bounds(&self) -> Option<AABB> {
+        let mut bb = AABB::default();
+        bb.update(&Point::new(-self.radius, -self.radius, 0.0));
+        bb.update(&Point::new(self.radius, self.radius, self.height));
+        Some(self.world_from_object.apply_aabb(&bb))
+    }
+
+    // lateral surface, the slant height times half the base perimeter
+    fn area(&self) -> f32 {
+        PI * self.radius * (self.radius * self.radius + self.height * self.height).sqrt()
+    }
+
+    // the circle at distance s from the apex has length proportional to s, so s goes
+    // with the square root of u
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        let f = u[0].sqrt();
+        let phi = 2.0 * PI * u[1];
+        let rho = f * self.radius;
+        let p = Point::new(rho * phi.cos(), rho * phi.sin(), (1.0 - f) * self.height);
+        let xf = &self.world_from_object;
+        (xf.apply_point(p), xf.apply_vector(self.normal(phi)))
+    }
+
+    fn normal_at(&self, p: Point) -> Vector {
+        let p = self.world_from_object.inverse().apply_point(p);
+        self.world_from_object
+            .apply_vector(self.normal(azimuth(p.x, p.y)))
+    }
+}
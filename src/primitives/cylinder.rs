@@ -0,0 +1,101 @@
+use std::f32::consts::PI;
+
+use crate::{
+    rays::{intersection::IntersectionData, ray::Ray},
+    utils::{
+        aabb::AABB,
+        transform::Transform,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{azimuth, first_root, solve_quadratic, surface_hit, Intersectable, Shape};
+
+// based on PBRT's 3rd ed. book, sec 3.3
+// open tube around the z axis of its own frame, from the base up to `height`; u runs
+// around the axis and v along it, normals point outwards
+#[derive(Debug, Clone, Copy)]
+pub struct Cylinder {
+    pub world_from_object: Transform,
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Cylinder {
+    // `axis` goes from the center of the base to the center of the top
+    pub fn new(base: Point, axis: Vector, radius: f32) -> Self {
+        let (world_from_object, height) = axis_frame(base, axis);
+        Self {
+            world_from_object,
+            radius,
+            height,
+        }
+    }
+}
+
+// rigid frame with z along the axis, distances are the same in both spaces
+pub fn axis_frame(base: Point, axis: Vector) -> (Transform, f32) {
+    let height = axis.norm();
+    let z = axis / height;
+    let (x, y) = z.coordinate_system();
+    (Transform::from_frame(base, x, y, z), height)
+}
+
+impl Intersectable for Cylinder {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        let r = self.world_from_object.inverse().apply_ray(ray);
+        let (o, d) = (r.origin, r.direction);
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (d.x * o.x + d.y * o.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        let t = first_root(solve_quadratic(a, b, c)?, |t| {
+            (0.0..=self.height).contains(&(o.z + t * d.z))
+        })?;
+
+        let p = r.origin + r.direction * t;
+        let phi = azimuth(p.x, p.y);
+        let n = Vector::new(phi.cos(), phi.sin(), 0.0);
+        let point = Point::new(self.radius * n.x, self.radius * n.y, p.z);
+        let uv = [phi / (2.0 * PI), p.z / self.height];
+        let xf = &self.world_from_object;
+        Some(surface_hit(
+            ray,
+            t,
+            xf.apply_point(point),
+            xf.apply_vector(n),
+            uv,
+        ))
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        self.intersect(ray).is_some_and(|isect| isect.depth < depth)
+    }
+}
+
+impl Shape for Cylinder {
+    fn bounds(&self) -> Option<AABB> {
+        let mut bb = AABB::default();
+        bb.update(&Point::new(-self.radius, -self.radius, 0.0));
+        bb.update(&Point::new(self.radius, self.radius, self.height));
+        Some(self.world_from_object.apply_aabb(&bb))
+    }
+
+    fn area(&self) -> f32 {
+        2.0 * PI * self.radius * self.height
+    }
+
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        let phi = 2.0 * PI * u[1];
+        let n = Vector::new(phi.cos(), phi.sin(), 0.0);
+        let p = Point::new(self.radius * n.x, self.radius * n.y, u[0] * self.height);
+        let xf = &self.world_from_object;
+        (xf.apply_point(p), xf.apply_vector(n))
+    }
+
+    fn normal_at(&self, p: Point) -> Vector {
+        let p = self.world_from_object.inverse().apply_point(p);
+        let mut n = Vector::new(p.x, p.y, 0.0);
+        n.normalize();
+        self.world_from_object.apply_vector(n)
+    }
+}
@@ -0,0 +1,89 @@
+use std::f32::consts::PI;
+
+use crate::{
+    rays::{intersection::IntersectionData, ray::Ray},
+    samplers::sample_uniform_disk_concentric,
+    utils::{
+        aabb::AABB,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{azimuth, plane::plane_hit, surface_hit, Intersectable, Shape};
+
+// based on PBRT's 3rd ed. book, sec 3.4
+// u runs around the center and v from the center to the rim
+#[derive(Debug, Clone, Copy)]
+pub struct Disk {
+    pub center: Point,
+    pub normal: Vector,
+    pub radius: f32,
+    pub u_axis: Vector,
+    pub v_axis: Vector,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vector, radius: f32) -> Self {
+        let mut normal = normal;
+        normal.normalize();
+        let (u_axis, v_axis) = normal.coordinate_system();
+        Self {
+            center,
+            normal,
+            radius,
+            u_axis,
+            v_axis,
+        }
+    }
+}
+
+impl Intersectable for Disk {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        let t = plane_hit(ray, self.center, self.normal)?;
+        let point = ray.origin + ray.direction * t;
+        let w = self.center.vec2point(point);
+        let (x, y) = (w.dot(self.u_axis), w.dot(self.v_axis));
+        let dist2 = x * x + y * y;
+        if dist2 > self.radius * self.radius {
+            return None;
+        }
+        let uv = [azimuth(x, y) / (2.0 * PI), dist2.sqrt() / self.radius];
+        Some(surface_hit(ray, t, point, self.normal, uv))
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        self.intersect(ray).is_some_and(|isect| isect.depth < depth)
+    }
+}
+
+impl Shape for Disk {
+    fn bounds(&self) -> Option<AABB> {
+        // the rim extends along each world axis by the radius times the sine of the
+        // angle between the axis and the normal
+        let n = self.normal;
+        let e = self.radius
+            * Vector::new(
+                (1.0 - n.x * n.x).max(0.0).sqrt(),
+                (1.0 - n.y * n.y).max(0.0).sqrt(),
+                (1.0 - n.z * n.z).max(0.0).sqrt(),
+            );
+        let mut bb = AABB::default();
+        bb.update(&(self.center + -1.0 * e));
+        bb.update(&(self.center + e));
+        Some(bb)
+    }
+
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        let [x, y] = sample_uniform_disk_concentric(u);
+        let p = self.center + self.radius * (x * self.u_axis + y * self.v_axis);
+        (p, self.normal)
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        self.normal
+    }
+}
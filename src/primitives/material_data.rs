@@ -1,4 +1,24 @@
-use crate::utils::rgb::RGB;
+use crate::{
+    bsdfs::{
+        conductor::{ConductorBsdf, ConductorPreset, Fresnel},
+        dielectric::DielectricBsdf,
+        lambertian::LambertianBsdf,
+        microfacet::{phong_to_alpha, roughness_to_alpha, MicrofacetDistribution, MicrofacetType},
+        mirror::MirrorBsdf,
+        phong::PhongBsdf,
+        rough_dielectric::RoughDielectricBsdf,
+        AnyBsdf, MixBsdf, SurfaceBsdf,
+    },
+    rays::intersection::IntersectionData,
+    utils::rgb::RGB,
+};
+
+// MTL exponents at or above this, and materials without one, reflect as a perfect mirror
+pub const MIRROR_NS: f32 = 1000.0;
+// index of refraction for transmissive materials that do not give one
+pub const DEFAULT_IOR: f32 = 1.5;
+// normal incidence reflectance of the specular layer of non metals in the PBR workflow
+pub const DIELECTRIC_F0: f32 = 0.04;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MaterialData {
@@ -8,4 +28,185 @@ pub struct MaterialData {
     pub kt: RGB,
     pub le: Option<RGB>,
     pub ns: f32,
+    // index of refraction for kt, 0 when not given
+    pub ior: f32,
+    // PBR extension (MTL Pr and Pm), setting either of these or a conductor preset
+    // replaces the Phong and smooth lobes with microfacet ones, the roughness then
+    // comes from ns when it is not given
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+    pub distribution: MicrofacetType,
+    pub conductor: Option<ConductorPreset>,
+    // indices into Scene::textures, each map is multiplied with its constant
+    pub ka_map: Option<u32>,
+    pub kd_map: Option<u32>,
+    pub ks_map: Option<u32>,
+}
+
+impl MaterialData {
+    // lambertian diffuse with a Phong or mirror specular lobe, or glass when kt is set
+    fn classic_lobes(&self) -> MixBsdf {
+        let mut bsdf = MixBsdf::default();
+        if !self.kd.is_zero() {
+            let lobe = AnyBsdf::Lambertian(LambertianBsdf { kd: self.kd });
+            bsdf.push(lobe, self.kd.y());
+        }
+        if !self.kt.is_zero() {
+            let kr = self.kr();
+            let lobe = AnyBsdf::Dielectric(DielectricBsdf {
+                kr,
+                kt: self.kt,
+                eta: self.eta(),
+            });
+            bsdf.push(lobe, kr.y().max(self.kt.y()));
+        } else if !self.ks.is_zero() {
+            let lobe = if self.ns <= 0.0 || self.ns >= MIRROR_NS {
+                AnyBsdf::Mirror(MirrorBsdf { ks: self.ks })
+            } else {
+                AnyBsdf::Phong(PhongBsdf {
+                    ks: self.ks,
+                    ns: self.ns,
+                })
+            };
+            bsdf.push(lobe, self.ks.y());
+        }
+        bsdf
+    }
+
+    // metallic-roughness workflow: the diffuse base fades out as the surface becomes
+    // metallic while the specular color moves from ks, or 4% when there is none, to kd
+    fn microfacet_lobes(&self) -> MixBsdf {
+        let alpha = match self.roughness {
+            Some(r) => roughness_to_alpha(r),
+            None => phong_to_alpha(self.ns),
+        };
+        let distrib = MicrofacetDistribution::new(self.distribution, alpha);
+        let metallic = self.metallic.unwrap_or(0.0).clamp(0.0, 1.0);
+
+        let mut bsdf = MixBsdf::default();
+        let kd = self.kd * (1.0 - metallic);
+        if !kd.is_zero() {
+            bsdf.push(AnyBsdf::Lambertian(LambertianBsdf { kd }), kd.y());
+        }
+        if !self.kt.is_zero() {
+            let kr = self.kr();
+            let lobe = AnyBsdf::RoughDielectric(RoughDielectricBsdf {
+                distrib,
+                kr,
+                kt: self.kt,
+                eta: self.eta(),
+            });
+            bsdf.push(lobe, kr.y().max(self.kt.y()));
+        } else if let Some(preset) = self.conductor {
+            let (eta, k) = preset.eta_k();
+            let tint = self.kr();
+            let lobe = AnyBsdf::Conductor(ConductorBsdf {
+                distrib,
+                fresnel: Fresnel::Conductor { eta, k },
+                tint,
+            });
+            bsdf.push(lobe, tint.y());
+        } else {
+            let spec = if self.ks.is_zero() {
+                RGB::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0)
+            } else {
+                self.ks
+            };
+            let f0 = spec * (1.0 - metallic) + self.kd * metallic;
+            let lobe = AnyBsdf::Conductor(ConductorBsdf {
+                distrib,
+                fresnel: Fresnel::Schlick { f0 },
+                tint: RGB::new(1.0, 1.0, 1.0),
+            });
+            bsdf.push(lobe, f0.y());
+        }
+        bsdf
+    }
+
+    // reflection tint of glass and conductors, white unless ks is given
+    fn kr(&self) -> RGB {
+        if self.ks.is_zero() {
+            RGB::new(1.0, 1.0, 1.0)
+        } else {
+            self.ks
+        }
+    }
+
+    pub fn is_microfacet(&self) -> bool {
+        self.roughness.is_some() || self.metallic.is_some() || self.conductor.is_some()
+    }
+
+    fn eta(&self) -> f32 {
+        if self.ior > 0.0 {
+            self.ior
+        } else {
+            DEFAULT_IOR
+        }
+    }
+
+    // scattering at a hit point, lobes are picked proportionally to their luminance
+    pub fn bsdf(&self, isect: &IntersectionData) -> SurfaceBsdf {
+        let bsdf = if self.is_microfacet() {
+            self.microfacet_lobes()
+        } else {
+            self.classic_lobes()
+        };
+
+        // whether the ray enters or leaves is decided by the geometric normal, an
+        // interpolated normal that puts wo on the other side would flip that decision
+        let wo = isect.wo;
+        let ns = if wo.dot(isect.sh_normal) * wo.dot(isect.geo_normal) > 0.0 {
+            isect.sh_normal
+        } else {
+            isect.geo_normal
+        };
+        SurfaceBsdf::new(ns, isect.geo_normal, bsdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        rays::intersection::IntersectionData,
+        utils::{rgb::RGB, vector::Vector},
+    };
+
+    use super::MaterialData;
+
+    #[test]
+    fn glass_refraction_and_tir() {
+        let glass = MaterialData {
+            kt: RGB::new(1.0, 0.5, 0.5),
+            ior: 1.5,
+            ..Default::default()
+        };
+        let n = Vector::new(0.0, 1.0, 0.0);
+        let isect = |wo: Vector| IntersectionData {
+            geo_normal: n,
+            sh_normal: n,
+            wo,
+            ..Default::default()
+        };
+
+        // entering at 30 degrees, reflected and refracted weights add up to the tint
+        let wo = Vector::new(0.5, (0.75f32).sqrt(), 0.0);
+        let bsdf = glass.bsdf(&isect(wo));
+        let mut dirs = Vec::new();
+        bsdf.for_each_specular(wo, |s| dirs.push((s.f * bsdf.abs_cos(s.wi), s.wi)));
+        assert_eq!(dirs.len(), 2);
+        let (refl, refr) = (dirs[0], dirs[1]);
+        assert!(refl.1.y > 0.0 && refr.1.y < 0.0);
+        assert!((refr.1.x + 0.5 / 1.5).abs() < 1e-5);
+        // the tinted transmission is scaled by 1 / eta^2 for radiance
+        assert!((refl.0.r + refr.0.r * 1.5 * 1.5 - 1.0).abs() < 1e-4);
+        assert!((refr.0.g / refr.0.r - 0.5).abs() < 1e-4);
+
+        // leaving the glass at a grazing angle is totally reflected
+        let wo = Vector::new(0.9, -(0.19f32).sqrt(), 0.0);
+        let bsdf = glass.bsdf(&isect(wo));
+        let mut dirs = Vec::new();
+        bsdf.for_each_specular(wo, |s| dirs.push(s.wi));
+        assert_eq!(dirs.len(), 1);
+        assert!(dirs[0].y < 0.0);
+    }
 }
@@ -2,6 +2,7 @@ use crate::{
     rays::{intersection::IntersectionData, ray::Ray},
     utils::{
         aabb::AABB,
+        transform::AnimatedTransform,
         vector::{Point, Vector},
     },
 };
@@ -11,49 +12,272 @@ use super::{
     Intersectable,
 };
 
+// vertex positions and normals of a moving mesh at one instant
+#[derive(Debug, Clone, Default)]
+pub struct MotionKey {
+    pub positions: Box<[Point]>,
+    pub normals: Box<[Vector]>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Mesh {
     pub positions: Box<[Point]>,
     pub pos_inds: Box<[u32]>,
     pub normals: Box<[Vector]>,
     pub norm_inds: Box<[u32]>,
+    pub texcoords: Box<[[f32; 2]]>,
+    pub tex_inds: Box<[u32]>,
+    // bounds over the whole motion for moving meshes, the faces' being in object space
+    // for meshes moving rigidly
     pub face_aabbs: Box<[AABB]>,
     pub aabb: AABB,
+    // keys at times 1/n, 2/n, ..., 1 of a deforming mesh whose positions and normals
+    // are the ones at time 0, empty otherwise; vertices move linearly between keys
+    pub motion: Box<[MotionKey]>,
+    // rigid motion carrying the mesh from object space to the world, interpolated at
+    // the time of each ray so rotations follow their arcs
+    pub rigid_motion: Option<AnimatedTransform>,
 }
 
+// largest rotation between two of the times the world bounds of a rigidly moving
+// mesh are taken at
+const MAX_BOUNDS_ROTATION: f32 = 2.0;
+
 impl Mesh {
     pub fn new(
         positions: Vec<Point>,
         normals: Vec<Vector>,
+        texcoords: Vec<[f32; 2]>,
         pos_inds: Vec<u32>,
-        norm_inds: Vec<u32>,
+        mut norm_inds: Vec<u32>,
+        mut tex_inds: Vec<u32>,
     ) -> Self {
-        let mut mesh_aabb = AABB::default();
-        for pos in positions.iter() {
-            mesh_aabb.update(pos);
+        // single indexed meshes share the position indices for their normals and uvs
+        if norm_inds.is_empty() && normals.len() == positions.len() {
+            norm_inds = pos_inds.clone();
         }
-        let mut face_aabbs: Vec<AABB> = Vec::with_capacity(pos_inds.len() / 3);
-
-        for face_inds in pos_inds.chunks_exact(3) {
-            let ta = &positions[face_inds[0] as usize];
-            let tb = &positions[face_inds[1] as usize];
-            let tc = &positions[face_inds[2] as usize];
-
-            let mut face_aabb = AABB::default();
-            face_aabb.update(ta);
-            face_aabb.update(tb);
-            face_aabb.update(tc);
-            face_aabbs.push(face_aabb);
+        if tex_inds.is_empty() && texcoords.len() == positions.len() {
+            tex_inds = pos_inds.clone();
         }
 
-        Self {
+        let mut mesh = Self {
             positions: positions.into_boxed_slice(),
             normals: normals.into_boxed_slice(),
             pos_inds: pos_inds.into_boxed_slice(),
             norm_inds: norm_inds.into_boxed_slice(),
-            aabb: mesh_aabb,
-            face_aabbs: face_aabbs.into_boxed_slice(),
+            texcoords: texcoords.into_boxed_slice(),
+            tex_inds: tex_inds.into_boxed_slice(),
+            ..Default::default()
+        };
+        mesh.update_bounds();
+        mesh
+    }
+
+    // makes the mesh move through the given keys, evenly spaced over times 0 to 1, the
+    // first one replacing the current vertices; every key must have the mesh's
+    // topology
+    pub fn set_motion(&mut self, mut keys: Vec<MotionKey>) {
+        assert!(keys.len() >= 2, "a motion needs at least two keys");
+        for key in keys.iter() {
+            assert_eq!(key.positions.len(), self.positions.len());
+            assert_eq!(key.normals.len(), self.normals.len());
         }
+        let first = keys.remove(0);
+        self.positions = first.positions;
+        self.normals = first.normals;
+        self.motion = keys.into_boxed_slice();
+        self.update_bounds();
+    }
+
+    pub fn set_rigid_motion(&mut self, motion: AnimatedTransform) {
+        self.rigid_motion = Some(motion);
+        self.update_bounds();
+    }
+
+    // vertices move linearly between keys, so the bounds of a face at every key enclose
+    // it at any time
+    fn update_bounds(&mut self) {
+        let mut mesh_aabb = AABB::default();
+        let mut face_aabbs = vec![AABB::default(); self.face_count()];
+        let keys = std::iter::once(&self.positions).chain(self.motion.iter().map(|k| &k.positions));
+        for positions in keys {
+            for pos in positions.iter() {
+                mesh_aabb.update(pos);
+            }
+            for (face_aabb, face_inds) in face_aabbs.iter_mut().zip(self.pos_inds.chunks_exact(3)) {
+                for &ind in face_inds {
+                    face_aabb.update(&positions[ind as usize]);
+                }
+            }
+        }
+        self.aabb = match &self.rigid_motion {
+            Some(xf) => self.swept_bounds(xf),
+            None => mesh_aabb,
+        };
+        self.face_aabbs = face_aabbs.into_boxed_slice();
+    }
+
+    // world bounds of a rigidly moving mesh, taken at the keys and at enough times in
+    // between for rotations to turn by at most MAX_BOUNDS_ROTATION, then grown by how
+    // far a vertex may stray from the straight line joining two of those times
+    //
+    // a vertex at v from the pivot in object space ends up at T(t) + R(t) S(t) v(t), its
+    // distance to the chord between two times h apart is at most h^2 / 8 times the
+    // largest second derivative, which is bounded from the rotation's angular speed w
+    // and the most the scale and v change over the motion
+    fn swept_bounds(&self, xf: &AnimatedTransform) -> AABB {
+        let keys: Vec<&[Point]> = std::iter::once(&self.positions)
+            .chain(self.motion.iter().map(|k| &k.positions))
+            .map(|p| &p[..])
+            .collect();
+        let spans = keys.len() - 1;
+        let angle = xf.rotation_angle().to_degrees();
+        let per_span = (angle / MAX_BOUNDS_ROTATION / spans.max(1) as f32).ceil() as usize;
+        let steps = spans.max(1) * per_span.clamp(1, 1024);
+
+        let mut bb = AABB::default();
+        for step in 0..=steps {
+            for p in self.positions_at(step as f32 / steps as f32) {
+                bb.update(&p);
+            }
+        }
+
+        let max_comp = |v: Vector| v.x.abs().max(v.y.abs()).max(v.z.abs());
+        let [s0, s1] = xf.scales;
+        let s = max_comp(s0).max(max_comp(s1));
+        let ds = max_comp(s1 - s0);
+        let w = xf.rotation_angle();
+        let (mut r, mut speed) = (0.0f32, 0.0f32);
+        for (k, key) in keys.iter().enumerate() {
+            for (ind, &p) in key.iter().enumerate() {
+                let v: Vector = p.into();
+                r = r.max((v - xf.pivot).norm());
+                if k > 0 {
+                    speed = speed.max(keys[k - 1][ind].vec2point(p).norm() * spans as f32);
+                }
+            }
+        }
+        let second = w * w * s * r + 2.0 * w * (ds * r + s * speed) + 2.0 * ds * speed;
+        let h = 1.0 / steps as f32;
+        let pad = second * h * h / 8.0;
+        let pad = Vector::new(pad, pad, pad);
+        let (min, max) = (bb.min + -1.0 * pad, bb.max + pad);
+        bb.update(&min);
+        bb.update(&max);
+        bb
+    }
+
+    // every vertex where it is at the given time
+    fn positions_at(&self, time: f32) -> Vec<Point> {
+        let (start, end, f) = self.key_span(time);
+        let xf = self.rigid_motion.map(|xf| xf.at(time));
+        start
+            .0
+            .iter()
+            .enumerate()
+            .map(|(ind, &a)| {
+                let p = match end {
+                    Some(end) => a + f * a.vec2point(end.positions[ind]),
+                    None => a,
+                };
+                xf.map_or(p, |xf| xf.apply_point(p))
+            })
+            .collect()
+    }
+
+    // positions and normals of the key at or before `time`, the key after it and how
+    // far between them `time` is, with no key after it for meshes that don't deform
+    fn key_span(&self, time: f32) -> ((&[Point], &[Vector]), Option<&MotionKey>, f32) {
+        if self.motion.is_empty() {
+            return ((&self.positions, &self.normals), None, 0.0);
+        }
+        let n = self.motion.len();
+        let s = time.clamp(0.0, 1.0) * n as f32;
+        let k = (s as usize).min(n - 1);
+        let start = match k {
+            0 => (&self.positions[..], &self.normals[..]),
+            _ => (
+                &self.motion[k - 1].positions[..],
+                &self.motion[k - 1].normals[..],
+            ),
+        };
+        (start, Some(&self.motion[k]), s - k as f32)
+    }
+}
+
+impl Mesh {
+    pub fn face_count(&self) -> usize {
+        self.pos_inds.len() / 3
+    }
+
+    pub fn face(&self, i: usize) -> Face {
+        let normals = if self.norm_inds.len() >= (i + 1) * 3 {
+            Some([
+                self.normals[self.norm_inds[i * 3] as usize],
+                self.normals[self.norm_inds[i * 3 + 1] as usize],
+                self.normals[self.norm_inds[i * 3 + 2] as usize],
+            ])
+        } else {
+            None
+        };
+        let uvs = if self.tex_inds.len() >= (i + 1) * 3 {
+            Some([
+                self.texcoords[self.tex_inds[i * 3] as usize],
+                self.texcoords[self.tex_inds[i * 3 + 1] as usize],
+                self.texcoords[self.tex_inds[i * 3 + 2] as usize],
+            ])
+        } else {
+            None
+        };
+        Face {
+            positions: [
+                self.positions[self.pos_inds[i * 3] as usize],
+                self.positions[self.pos_inds[i * 3 + 1] as usize],
+                self.positions[self.pos_inds[i * 3 + 2] as usize],
+            ],
+            normals,
+            uvs,
+        }
+    }
+
+    // face i where it is at the given time, times outside [0, 1] clamp to the ends of
+    // the motion
+    pub fn face_at(&self, i: usize, time: f32) -> Face {
+        let mut face = self.object_face_at(i, time);
+        let Some(xf) = &self.rigid_motion else {
+            return face;
+        };
+        let xf = xf.at(time);
+        face.positions = face.positions.map(|p| xf.apply_point(p));
+        if let Some(normals) = face.normals.as_mut() {
+            for n in normals.iter_mut() {
+                *n = xf.apply_normal(*n);
+                n.normalize();
+            }
+        }
+        face
+    }
+
+    // face i at the given time before the rigid motion carries it, the same as
+    // face_at for meshes without one
+    pub fn object_face_at(&self, i: usize, time: f32) -> Face {
+        let mut face = self.face(i);
+        let ((start_positions, start_normals), Some(end), f) = self.key_span(time) else {
+            return face;
+        };
+        for v in 0..3 {
+            let ind = self.pos_inds[i * 3 + v] as usize;
+            let a = start_positions[ind];
+            let b = end.positions[ind];
+            face.positions[v] = a + f * a.vec2point(b);
+        }
+        if let Some(normals) = face.normals.as_mut() {
+            for (v, n) in normals.iter_mut().enumerate() {
+                let ind = self.norm_inds[i * 3 + v] as usize;
+                *n = (1.0 - f) * start_normals[ind] + f * end.normals[ind];
+            }
+        }
+        face
     }
 }
 
@@ -66,20 +290,11 @@ impl Intersectable for Mesh {
             return isect;
         }
 
+        // the face bounds of a rigidly moving mesh are in object space
+        let moving = self.rigid_motion.is_some();
         for (i, bb) in self.face_aabbs.iter().enumerate() {
-            if bb.intersect(ray) {
-                //let na = self.normals[self.norm_inds[i*3] as usize];
-                //let nb = self.normals[self.norm_inds[i*3+1] as usize];
-                //let nc = self.normals[self.norm_inds[i*3+2] as usize];
-
-                let face = Face {
-                    positions: [
-                        self.positions[self.pos_inds[i * 3] as usize],
-                        self.positions[self.pos_inds[i * 3 + 1] as usize],
-                        self.positions[self.pos_inds[i * 3 + 2] as usize],
-                    ],
-                    //normals: [na,nb,nc],
-                };
+            if moving || bb.intersect(ray) {
+                let face = self.face_at(i, ray.time);
 
                 if let Some(face_isect) = triangle_intersect(ray, &face) {
                     if face_isect.depth < min_depth {
@@ -98,20 +313,11 @@ impl Intersectable for Mesh {
             return false;
         }
 
+        // the face bounds of a rigidly moving mesh are in object space
+        let moving = self.rigid_motion.is_some();
         for (i, bb) in self.face_aabbs.iter().enumerate() {
-            if bb.intersect(ray) {
-                //let na = self.normals[self.norm_inds[i*3] as usize];
-                //let nb = self.normals[self.norm_inds[i*3+1] as usize];
-                //let nc = self.normals[self.norm_inds[i*3+2] as usize];
-
-                let face = Face {
-                    positions: [
-                        self.positions[self.pos_inds[i * 3] as usize],
-                        self.positions[self.pos_inds[i * 3 + 1] as usize],
-                        self.positions[self.pos_inds[i * 3 + 2] as usize],
-                    ],
-                    //normals: [na,nb,nc],
-                };
+            if moving || bb.intersect(ray) {
+                let face = self.face_at(i, ray.time);
 
                 if let Some(face_isect) = triangle_intersect(ray, &face) {
                     if face_isect.depth < depth {
@@ -130,9 +336,15 @@ mod tests {
     use crate::{
         primitives::triangle::{triangle_intersect, Face},
         rays::ray::Ray,
-        utils::vector::{Point, Vector},
+        utils::{
+            aabb::AABB,
+            transform::AnimatedTransform,
+            vector::{Point, Vector},
+        },
     };
 
+    use super::Mesh;
+
     #[test]
     fn triangle_intersect_test() {
         let face = Face {
@@ -141,6 +353,8 @@ mod tests {
                 Point::new(2.0, 0.0, 0.0),
                 Point::new(0.0, 2.0, 0.0),
             ],
+            normals: None,
+            uvs: None,
         };
         let face_ord = Face {
             positions: [
@@ -148,10 +362,95 @@ mod tests {
                 Point::new(0.0, 2.0, 0.0),
                 Point::new(2.0, 0.0, 0.0),
             ],
+            normals: None,
+            uvs: None,
         };
         let ray = Ray::new(Point::new(1.0, 1.0, 1.0), Vector::new(0.0, 0.0, -1.0));
 
         assert!(triangle_intersect(&ray, &face).is_some());
         assert!(triangle_intersect(&ray, &face_ord).is_some());
     }
+
+    #[test]
+    fn triangle_shading_normal_test() {
+        let mut n0 = Vector::new(-1.0, 0.0, 1.0);
+        n0.normalize();
+        let face = Face {
+            positions: [
+                Point::new(0.0, 0.0, 0.0),
+                Point::new(2.0, 0.0, 0.0),
+                Point::new(0.0, 2.0, 0.0),
+            ],
+            normals: Some([n0, Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, 1.0)]),
+            uvs: Some([[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]),
+        };
+
+        // at the first vertex the shading normal is that vertex's normal
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0));
+        let isect = triangle_intersect(&ray, &face).unwrap();
+        assert!(isect.bary[0].abs() < 1e-6 && isect.bary[1].abs() < 1e-6);
+        assert!((isect.sh_normal.dot(n0) - 1.0).abs() < 1e-5);
+        assert!(isect.geo_normal.z > 0.0);
+
+        let ray = Ray::new(Point::new(1.0, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0));
+        let isect = triangle_intersect(&ray, &face).unwrap();
+        assert!((isect.bary[0] - 0.5).abs() < 1e-6 && (isect.bary[1] - 0.25).abs() < 1e-6);
+        assert!((isect.sh_normal.norm() - 1.0).abs() < 1e-5);
+        assert!(isect.sh_normal.x < 0.0 && isect.sh_normal.x > n0.x);
+        assert!((isect.uv[0] - 0.5).abs() < 1e-6 && (isect.uv[1] - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rigid_motion_follows_its_arc() {
+        // a triangle turning three quarters of a turn around the y axis while it doubles
+        // in size and moves along z
+        let mut mesh = Mesh::new(
+            vec![
+                Point::new(2.0, 0.0, 0.0),
+                Point::new(3.0, 0.0, 0.0),
+                Point::new(2.0, 1.0, 0.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![0, 1, 2],
+            Vec::new(),
+            Vec::new(),
+        );
+        let up = Vector::new(0.0, 1.0, 0.0);
+        mesh.set_rigid_motion(AnimatedTransform {
+            scales: [Vector::new(1.0, 1.0, 1.0), Vector::new(2.0, 2.0, 2.0)],
+            rotations: [(up, 0.0), (up, 270f32.to_radians())],
+            translations: [Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 5.0)],
+            pivot: Vector::new(0.0, 0.0, 0.0),
+        });
+
+        let close = |a: Point, b: Point| a.vec2point(b).norm() < 1e-4;
+        let half = mesh.face_at(0, 0.5).positions[0];
+        let (sin, cos) = 135f32.to_radians().sin_cos();
+        assert!(
+            close(half, Point::new(3.0 * cos, 0.0, 2.5 - 3.0 * sin)),
+            "{:?}",
+            half
+        );
+        let end = mesh.face_at(0, 1.0).positions[0];
+        assert!(close(end, Point::new(0.0, 0.0, 9.0)), "{:?}", end);
+
+        // the face keeps its bounds in object space, the mesh's hold it all along its
+        // path and not much more
+        let mut object = AABB::default();
+        for p in mesh.object_face_at(0, 0.5).positions {
+            object.update(&p);
+        }
+        assert_eq!(mesh.face_aabbs[0], object);
+        let mut swept = AABB::default();
+        for i in 0..=2000 {
+            for p in mesh.face_at(0, i as f32 / 2000.0).positions {
+                swept.update(&p);
+            }
+        }
+        let bb = mesh.aabb;
+        assert_eq!(bb.union(&swept), bb);
+        let slack = bb.diagonal().norm() - swept.diagonal().norm();
+        assert!(slack < 1e-3 * swept.diagonal().norm(), "{}", slack);
+    }
 }
@@ -1,7 +1,27 @@
-use crate::rays::{intersection::IntersectionData, ray::Ray};
+use crate::{
+    rays::{
+        intersection::IntersectionData,
+        ray::{self, Ray},
+    },
+    utils::{
+        aabb::AABB,
+        vector::{Point, Vector},
+    },
+};
 
+use self::{
+    cone::Cone, cylinder::Cylinder, disk::Disk, plane::Plane, quad::Quad, sphere::Sphere,
+    triangle::Triangle,
+};
+
+pub mod cone;
+pub mod cylinder;
+pub mod disk;
 pub mod material_data;
 pub mod mesh;
+pub mod plane;
+pub mod quad;
+pub mod sphere;
 pub mod triangle;
 
 pub trait Intersectable {
@@ -9,3 +29,274 @@ pub trait Intersectable {
 
     fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool;
 }
+
+// surfaces that can be placed in a scene on their own and emit as area lights,
+// based on PBRT's 3rd ed. book, sec 3.1 and 14.2
+pub trait Shape: Intersectable {
+    // None for unbounded surfaces
+    fn bounds(&self) -> Option<AABB>;
+    fn area(&self) -> f32;
+    // point distributed uniformly by area and the surface normal there
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector);
+    // normal at a point on the surface, pointing to the side that emits
+    fn normal_at(&self, p: Point) -> Vector;
+}
+
+// shape picked at runtime, e.g. from a scene file
+#[derive(Debug, Clone, Copy)]
+pub enum AnyShape {
+    Triangle(Triangle),
+    Sphere(Sphere),
+    Plane(Plane),
+    Disk(Disk),
+    Quad(Quad),
+    Cylinder(Cylinder),
+    Cone(Cone),
+}
+
+impl Intersectable for AnyShape {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        match self {
+            AnyShape::Triangle(s) => s.intersect(ray),
+            AnyShape::Sphere(s) => s.intersect(ray),
+            AnyShape::Plane(s) => s.intersect(ray),
+            AnyShape::Disk(s) => s.intersect(ray),
+            AnyShape::Quad(s) => s.intersect(ray),
+            AnyShape::Cylinder(s) => s.intersect(ray),
+            AnyShape::Cone(s) => s.intersect(ray),
+        }
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        self.intersect(ray).is_some_and(|isect| isect.depth < depth)
+    }
+}
+
+impl Shape for AnyShape {
+    fn bounds(&self) -> Option<AABB> {
+        match self {
+            AnyShape::Triangle(s) => s.bounds(),
+            AnyShape::Sphere(s) => s.bounds(),
+            AnyShape::Plane(s) => s.bounds(),
+            AnyShape::Disk(s) => s.bounds(),
+            AnyShape::Quad(s) => s.bounds(),
+            AnyShape::Cylinder(s) => s.bounds(),
+            AnyShape::Cone(s) => s.bounds(),
+        }
+    }
+
+    fn area(&self) -> f32 {
+        match self {
+            AnyShape::Triangle(s) => s.area(),
+            AnyShape::Sphere(s) => s.area(),
+            AnyShape::Plane(s) => s.area(),
+            AnyShape::Disk(s) => s.area(),
+            AnyShape::Quad(s) => s.area(),
+            AnyShape::Cylinder(s) => s.area(),
+            AnyShape::Cone(s) => s.area(),
+        }
+    }
+
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        match self {
+            AnyShape::Triangle(s) => s.sample(u),
+            AnyShape::Sphere(s) => s.sample(u),
+            AnyShape::Plane(s) => s.sample(u),
+            AnyShape::Disk(s) => s.sample(u),
+            AnyShape::Quad(s) => s.sample(u),
+            AnyShape::Cylinder(s) => s.sample(u),
+            AnyShape::Cone(s) => s.sample(u),
+        }
+    }
+
+    fn normal_at(&self, p: Point) -> Vector {
+        match self {
+            AnyShape::Triangle(s) => s.normal_at(p),
+            AnyShape::Sphere(s) => s.normal_at(p),
+            AnyShape::Plane(s) => s.normal_at(p),
+            AnyShape::Disk(s) => s.normal_at(p),
+            AnyShape::Quad(s) => s.normal_at(p),
+            AnyShape::Cylinder(s) => s.normal_at(p),
+            AnyShape::Cone(s) => s.normal_at(p),
+        }
+    }
+}
+
+// hit record of an analytic surface, whose normal is exact so the geometric and
+// shading normals agree
+pub fn surface_hit(
+    ray: &Ray,
+    t: f32,
+    point: Point,
+    normal: Vector,
+    uv: [f32; 2],
+) -> IntersectionData {
+    IntersectionData {
+        point,
+        geo_normal: normal,
+        sh_normal: normal,
+        bary: uv,
+        uv,
+        wo: -1.0 * ray.direction,
+        depth: t,
+        time: ray.time,
+    }
+}
+
+// roots of a t^2 + b t + c in increasing order, solved in f64 as the terms of ray
+// quadrics cancel badly far from the origin
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    let (a, b, c) = (a as f64, b as f64, c as f64);
+    if a == 0.0 {
+        return None;
+    }
+    let discrim = b * b - 4.0 * a * c;
+    if discrim < 0.0 {
+        return None;
+    }
+    let root = discrim.sqrt();
+    let q = if b < 0.0 {
+        -0.5 * (b - root)
+    } else {
+        -0.5 * (b + root)
+    };
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if t0 > t1 {
+        (t1 as f32, t0 as f32)
+    } else {
+        (t0 as f32, t1 as f32)
+    })
+}
+
+// the nearest root in front of the ray for which `accept` holds
+pub fn first_root<F>(roots: (f32, f32), mut accept: F) -> Option<f32>
+where
+    F: FnMut(f32) -> bool,
+{
+    [roots.0, roots.1]
+        .into_iter()
+        .find(|&t| t > ray::EPSILON && accept(t))
+}
+
+// azimuth of (x, y) in [0, 2 pi)
+pub fn azimuth(x: f32, y: f32) -> f32 {
+    let phi = y.atan2(x);
+    if phi < 0.0 {
+        phi + 2.0 * std::f32::consts::PI
+    } else {
+        phi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        lights::AreaLight,
+        rays::ray::Ray,
+        utils::{
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn shapes_hit_their_samples() {
+        let base = Point::new(1.0, -2.0, 0.5);
+        let axis = Vector::new(0.3, 2.0, -0.4);
+        let shapes = [
+            AnyShape::Sphere(Sphere::new(base, 1.5)),
+            AnyShape::Disk(Disk::new(base, axis, 1.5)),
+            AnyShape::Quad(Quad::new(base, Vector::new(2.0, 0.0, 0.5), axis)),
+            AnyShape::Cylinder(Cylinder::new(base, axis, 0.8)),
+            AnyShape::Cone(Cone::new(base, axis, 0.8)),
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        for shape in shapes {
+            let bb = shape.bounds().unwrap();
+            for _ in 0..200 {
+                let (p, n) = shape.sample([rng.gen(), rng.gen()]);
+                assert!((n.norm() - 1.0).abs() < 1e-4, "{:?}", shape);
+                assert!(
+                    (shape.normal_at(p).dot(n) - 1.0).abs() < 1e-4,
+                    "{:?}",
+                    shape
+                );
+                for i in 0..3 {
+                    assert!(p[i] >= bb.min[i] - 1e-4 && p[i] <= bb.max[i] + 1e-4);
+                }
+
+                // from just outside the surface straight back at the sample
+                let origin = p + 0.5 * n;
+                let isect = shape.intersect(&Ray::new(origin, -1.0 * n)).unwrap();
+                assert!(
+                    (isect.depth - 0.5).abs() < 1e-3,
+                    "{:?} {}",
+                    shape,
+                    isect.depth
+                );
+                assert!(isect.point.vec2point(p).norm() < 1e-3, "{:?}", shape);
+                assert!(isect.geo_normal.dot(n) > 0.999, "{:?}", shape);
+                assert!(isect.uv.iter().all(|v| (-1e-4..=1.0 + 1e-4).contains(v)));
+            }
+        }
+
+        let plane = Plane::new(base, axis);
+        assert!(plane.bounds().is_none());
+        let ray = Ray::new(base + axis, Vector::new(0.0, -1.0, 0.0));
+        assert!(
+            (plane
+                .intersect(&ray)
+                .unwrap()
+                .point
+                .vec2point(base)
+                .dot(axis))
+            .abs()
+                < 1e-4
+        );
+    }
+
+    #[test]
+    fn shape_lights_cover_their_solid_angle() {
+        // the sampled estimate of the solid angle, sum of 1 / pdf over the samples
+        // that reach the point, against its closed form
+        let solid_angle = |shape: AnyShape, p: Point| {
+            let light = AreaLight::new(RGB::new(1.0, 1.0, 1.0), shape);
+            let mut rng = StdRng::seed_from_u64(11);
+            let n = 100_000;
+            let mut sum = 0.0;
+            for _ in 0..n {
+                if let Some(ls) = light.sample_li(p, &[rng.gen(), rng.gen()]) {
+                    let l_point = p + ls.wi * ls.dist;
+                    // grazing samples on the silhouette lose a few digits
+                    let pdf = light.pdf_li(p, l_point);
+                    assert!((pdf - ls.pdf).abs() < 1e-2 * ls.pdf);
+                    sum += 1.0 / ls.pdf as f64;
+                }
+            }
+            (sum / n as f64) as f32
+        };
+
+        let (r, d) = (1.0f32, 3.0f32);
+        let sphere = AnyShape::Sphere(Sphere::new(Point::new(0.0, 0.0, 0.0), r));
+        let exact = 2.0 * PI * (1.0 - (1.0 - (r / d) * (r / d)).sqrt());
+        let est = solid_angle(sphere, Point::new(0.0, 0.0, d));
+        assert!((est - exact).abs() < 0.03 * exact, "{} {}", est, exact);
+
+        let disk = AnyShape::Disk(Disk::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            r,
+        ));
+        let exact = 2.0 * PI * (1.0 - d / (d * d + r * r).sqrt());
+        let est = solid_angle(disk, Point::new(0.0, 0.0, d));
+        assert!((est - exact).abs() < 0.03 * exact, "{} {}", est, exact);
+        // one sided, nothing reaches the back
+        assert_eq!(solid_angle(disk, Point::new(0.0, 0.0, -d)), 0.0);
+    }
+}
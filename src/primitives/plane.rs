@@ -0,0 +1,79 @@
+use crate::{
+    rays::{
+        intersection::IntersectionData,
+        ray::{self, Ray},
+    },
+    utils::{
+        aabb::AABB,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{surface_hit, Intersectable, Shape};
+
+// infinite plane through `point`, uv being distances along two axes of the plane
+// in scene units so textures repeat every unit
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    pub point: Point,
+    pub normal: Vector,
+    pub u_axis: Vector,
+    pub v_axis: Vector,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vector) -> Self {
+        let mut normal = normal;
+        normal.normalize();
+        let (u_axis, v_axis) = normal.coordinate_system();
+        Self {
+            point,
+            normal,
+            u_axis,
+            v_axis,
+        }
+    }
+}
+
+// distance along the ray to the plane through `point` with normal `n`
+pub fn plane_hit(ray: &Ray, point: Point, n: Vector) -> Option<f32> {
+    let denom = n.dot(ray.direction);
+    if denom.abs() < ray::EPSILON {
+        return None;
+    }
+    let t = ray.origin.vec2point(point).dot(n) / denom;
+    (t > ray::EPSILON).then_some(t)
+}
+
+impl Intersectable for Plane {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        let t = plane_hit(ray, self.point, self.normal)?;
+        let point = ray.origin + ray.direction * t;
+        let w = self.point.vec2point(point);
+        let uv = [w.dot(self.u_axis), w.dot(self.v_axis)];
+        Some(surface_hit(ray, t, point, self.normal, uv))
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        plane_hit(ray, self.point, self.normal).is_some_and(|t| t < depth)
+    }
+}
+
+// planes are tested outside the acceleration structures and cannot emit
+impl Shape for Plane {
+    fn bounds(&self) -> Option<AABB> {
+        None
+    }
+
+    fn area(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    fn sample(&self, _u: [f32; 2]) -> (Point, Vector) {
+        (self.point, self.normal)
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        self.normal
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    rays::{intersection::IntersectionData, ray::Ray},
+    utils::{
+        aabb::AABB,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{plane::plane_hit, surface_hit, Intersectable, Shape};
+
+// parallelogram spanned by two edges from a corner, uv being the fractions along the
+// edges and the normal following their cross product
+#[derive(Debug, Clone, Copy)]
+pub struct Quad {
+    pub corner: Point,
+    pub e1: Vector,
+    pub e2: Vector,
+    pub normal: Vector,
+}
+
+impl Quad {
+    pub fn new(corner: Point, e1: Vector, e2: Vector) -> Self {
+        let mut normal = e1.cross(e2);
+        normal.normalize();
+        Self {
+            corner,
+            e1,
+            e2,
+            normal,
+        }
+    }
+}
+
+impl Intersectable for Quad {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        let t = plane_hit(ray, self.corner, self.normal)?;
+        let point = ray.origin + ray.direction * t;
+        // w = u e1 + v e2, crossing with either edge isolates the other coordinate
+        let w = self.corner.vec2point(point);
+        let n = self.e1.cross(self.e2);
+        let nn = n.dot(n);
+        let u = w.cross(self.e2).dot(n) / nn;
+        let v = self.e1.cross(w).dot(n) / nn;
+        if !((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)) {
+            return None;
+        }
+        Some(surface_hit(ray, t, point, self.normal, [u, v]))
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        self.intersect(ray).is_some_and(|isect| isect.depth < depth)
+    }
+}
+
+impl Shape for Quad {
+    fn bounds(&self) -> Option<AABB> {
+        let mut bb = AABB::default();
+        for p in [
+            self.corner,
+            self.corner + self.e1,
+            self.corner + self.e2,
+            self.corner + self.e1 + self.e2,
+        ] {
+            bb.update(&p);
+        }
+        Some(bb)
+    }
+
+    fn area(&self) -> f32 {
+        self.e1.cross(self.e2).norm()
+    }
+
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        (self.corner + u[0] * self.e1 + u[1] * self.e2, self.normal)
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        self.normal
+    }
+}
@@ -0,0 +1,123 @@
+use std::f32::consts::PI;
+
+use crate::{
+    rays::{intersection::IntersectionData, ray::Ray},
+    samplers::uniform_cone_pdf,
+    utils::{
+        aabb::AABB,
+        vector::{Point, Vector},
+    },
+};
+
+use super::{azimuth, first_root, solve_quadratic, surface_hit, Intersectable, Shape};
+
+// based on PBRT's 3rd ed. book, sec 3.2
+// u runs around the y axis and v from the top pole down, normals point outwards
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+    pub center: Point,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Point, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    // cosine of the half angle of the cone the sphere fills as seen from `p`, None
+    // from inside, where the whole sphere is visible
+    fn cos_theta_max(&self, p: Point) -> Option<f32> {
+        let d = self.center.vec2point(p);
+        let dc2 = d.dot(d);
+        let r2 = self.radius * self.radius;
+        if dc2 <= r2 {
+            return None;
+        }
+        Some((1.0 - r2 / dc2).max(0.0).sqrt())
+    }
+
+    // based on PBRT's 3rd ed. book, sec 14.2.2
+    // point of the cap visible from `p`, sampled uniformly within the cone the sphere
+    // subtends, with its normal and solid angle pdf; None from inside the sphere
+    pub fn sample_cone(&self, p: Point, u: [f32; 2]) -> Option<(Point, Vector, f32)> {
+        let cos_theta_max = self.cos_theta_max(p)?;
+        let mut wc = p.vec2point(self.center);
+        let dc = wc.norm();
+        wc = wc / dc;
+        let (wc_x, wc_y) = wc.coordinate_system();
+
+        // direction within the cone, then the point where it meets the sphere found
+        // from the angle alpha at the center between it and the line to `p`
+        let cos_theta = (1.0 - u[0]) + u[0] * cos_theta_max;
+        let sin_theta2 = (1.0 - cos_theta * cos_theta).max(0.0);
+        let phi = 2.0 * PI * u[1];
+        let r = self.radius;
+        let ds = dc * cos_theta - (r * r - dc * dc * sin_theta2).max(0.0).sqrt();
+        let cos_alpha = ((dc * dc + r * r - ds * ds) / (2.0 * dc * r)).clamp(-1.0, 1.0);
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+        let mut n = Vector::new(sin_alpha * phi.cos(), sin_alpha * phi.sin(), cos_alpha).rotate(
+            -1.0 * wc_x,
+            -1.0 * wc_y,
+            -1.0 * wc,
+        );
+        n.normalize();
+        Some((self.center + r * n, n, uniform_cone_pdf(cos_theta_max)))
+    }
+
+    // solid angle pdf of sample_cone, None from inside the sphere
+    pub fn cone_pdf(&self, p: Point) -> Option<f32> {
+        Some(uniform_cone_pdf(self.cos_theta_max(p)?))
+    }
+}
+
+impl Intersectable for Sphere {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionData> {
+        let oc = self.center.vec2point(ray.origin);
+        let a = ray.direction.dot(ray.direction);
+        let b = 2.0 * oc.dot(ray.direction);
+        let c = oc.dot(oc) - self.radius * self.radius;
+        let t = first_root(solve_quadratic(a, b, c)?, |_| true)?;
+
+        let mut n = self.center.vec2point(ray.origin + ray.direction * t);
+        n.normalize();
+        // projected back onto the surface to undo the round-off of the ray equation
+        let point = self.center + self.radius * n;
+        let uv = [
+            azimuth(n.x, -n.z) / (2.0 * PI),
+            n.y.clamp(-1.0, 1.0).acos() / PI,
+        ];
+        Some(surface_hit(ray, t, point, n, uv))
+    }
+
+    fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        self.intersect(ray).is_some_and(|isect| isect.depth < depth)
+    }
+}
+
+impl Shape for Sphere {
+    fn bounds(&self) -> Option<AABB> {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        let mut bb = AABB::default();
+        bb.update(&(self.center + -1.0 * r));
+        bb.update(&(self.center + r));
+        Some(bb)
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        let y = 1.0 - 2.0 * u[0];
+        let r = (1.0 - y * y).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+        let n = Vector::new(r * phi.cos(), y, -r * phi.sin());
+        (self.center + self.radius * n, n)
+    }
+
+    fn normal_at(&self, p: Point) -> Vector {
+        let mut n = self.center.vec2point(p);
+        n.normalize();
+        n
+    }
+}
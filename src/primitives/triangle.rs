@@ -3,7 +3,7 @@ use crate::rays::ray::{self, Ray};
 use crate::utils::aabb::AABB;
 use crate::utils::vector::{Point, Vector};
 
-use super::Intersectable;
+use super::{Intersectable, Shape};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Triangle {
@@ -47,6 +47,8 @@ impl Intersectable for Triangle {
         }
         let face = Face {
             positions: [self.v1, self.v2, self.v3],
+            normals: None,
+            uvs: None,
         };
         return triangle_intersect(ray, &face);
     }
@@ -56,10 +58,35 @@ impl Intersectable for Triangle {
     }
 }
 
+// a free standing triangle emits towards the side of its stored normal
+impl Shape for Triangle {
+    fn bounds(&self) -> Option<AABB> {
+        Some(self.bb)
+    }
+
+    fn area(&self) -> f32 {
+        Triangle::area(self)
+    }
+
+    fn sample(&self, u: [f32; 2]) -> (Point, Vector) {
+        let sqrt_u0 = u[0].sqrt();
+        let alpha = 1.0 - sqrt_u0;
+        let beta = (1.0 - u[1]) * sqrt_u0;
+        let gamma = u[1] * sqrt_u0;
+        let p = alpha * self.v1 + beta * self.v2 + gamma * self.v3;
+        (p, self.normal)
+    }
+
+    fn normal_at(&self, _p: Point) -> Vector {
+        self.normal
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Face {
     pub positions: [Point; 3],
-    //pub normals: [Vector; 3],
+    pub normals: Option<[Vector; 3]>,
+    pub uvs: Option<[[f32; 2]; 3]>,
 }
 
 pub fn triangle_intersect(ray: &Ray, face: &Face) -> Option<IntersectionData> {
@@ -95,12 +122,37 @@ pub fn triangle_intersect(ray: &Ray, face: &Face) -> Option<IntersectionData> {
         let ipoint = ray.origin + ray.direction * t;
         let mut gn = e1.cross(e2);
         gn.normalize();
+        let sn = match face.normals {
+            Some(ns) => {
+                let mut sn = (1.0 - u - v) * ns[0] + u * ns[1] + v * ns[2];
+                sn.normalize();
+                // vertex normals carry the intended orientation, keep both on the same side
+                if sn.dot(gn) < 0.0 {
+                    gn = -1.0 * gn;
+                }
+                sn
+            }
+            None => gn,
+        };
+        let uv = match face.uvs {
+            Some(uvs) => {
+                let w = 1.0 - u - v;
+                [
+                    w * uvs[0][0] + u * uvs[1][0] + v * uvs[2][0],
+                    w * uvs[0][1] + u * uvs[1][1] + v * uvs[2][1],
+                ]
+            }
+            None => [u, v],
+        };
         return Some(IntersectionData {
             point: ipoint,
             geo_normal: gn,
+            sh_normal: sn,
+            bary: [u, v],
+            uv,
             wo: -1.0 * ray.direction,
             depth: t,
-            ..Default::default()
+            time: ray.time,
         });
     } else {
         return None;
@@ -1,9 +1,32 @@
-use crate::utils::vector::{Point, Vector};
+use crate::{
+    rays::ray::Ray,
+    utils::vector::{Point, Vector},
+};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct IntersectionData {
     pub point: Point,
     pub geo_normal: Vector,
+    // interpolated from the vertex normals, equal to geo_normal when there are none
+    pub sh_normal: Vector,
+    // barycentric coordinates of the hit point relative to the 2nd and 3rd vertices
+    pub bary: [f32; 2],
+    // texture coordinates, the barycentrics when the mesh has none
+    pub uv: [f32; 2],
     pub wo: Vector,
     pub depth: f32,
+    // time of the ray that found the hit, carried over to the rays leaving it
+    pub time: f32,
+}
+
+impl IntersectionData {
+    // origin for a ray leaving the surface in direction `dir`, pushed off along the
+    // geometric normal so it does not hit the surface it starts on
+    pub fn spawn_point(&self, dir: Vector, bias: f32) -> Point {
+        self.point + self.geo_normal.face_forward(dir) * bias
+    }
+
+    pub fn spawn_ray(&self, dir: Vector, bias: f32) -> Ray {
+        Ray::new(self.spawn_point(dir, bias), dir).with_time(self.time)
+    }
 }
@@ -5,6 +5,8 @@ pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
     pub direction_inv: Vector,
+    // instant the ray samples, moving geometry is intersected where it is at that time
+    pub time: f32,
 }
 
 //pub const EPSILON: f32 = 1e-3;
@@ -18,6 +20,12 @@ impl Ray {
             origin,
             direction,
             direction_inv,
+            time: 0.0,
         }
     }
+
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
 }
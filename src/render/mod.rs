@@ -1,79 +1,212 @@
 use crate::{
-    camera::Camera, images::image_rgb::ImageRGB, scene::Scene, shaders::Shader, utils::rgb::RGB,
+    camera::{Camera, CameraSample},
+    film::{filters::AnyFilter, Film, FilmSample},
+    images::image_rgb::ImageRGB,
+    samplers::{AnySampler, Sampler},
+    scene::Scene,
+    shaders::Shader,
+    utils::rgb::RGB,
 };
-use rand::Rng;
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use serde::Deserialize;
+
+// one camera sample of pixel (x, y), None if the camera has no ray through it
+// every sample value comes from the sampler, which is seeded per pixel and sample index,
+// so the result does not depend on which thread renders the pixel
+#[allow(clippy::too_many_arguments)]
+fn render_sample<S, C, SM>(
+    camera: &C,
+    scene: &Scene,
+    shader: &S,
+    sampler: &mut SM,
+    x: u32,
+    y: u32,
+    sample_index: u32,
+    jitter: bool,
+) -> Option<FilmSample>
+where
+    S: Shader,
+    C: Camera,
+    SM: Sampler,
+{
+    sampler.start_pixel_sample(x, y, sample_index);
+    let cam_sample = CameraSample::new(sampler, jitter);
+    let primary_ray = camera.generate_ray(x, y, &cam_sample)?;
+    let tdata_opt = scene.trace(&primary_ray);
+    Some(FilmSample {
+        p_film: cam_sample.p_film,
+        l: shader.shade(scene, &primary_ray, &tdata_opt, sampler),
+    })
+}
+
+// traces sample sample_index of every active pixel in parallel, the others get None
+// all renderers add whole sample indices to the film one after the other, which keeps
+// their results bit-identical for the same sampler
+#[allow(clippy::too_many_arguments)]
+fn render_pass<S, C>(
+    camera: &C,
+    scene: &Scene,
+    shader: &S,
+    sampler: &AnySampler,
+    width: u32,
+    active: &[bool],
+    sample_index: u32,
+    jitter: bool,
+) -> Vec<Option<FilmSample>>
+where
+    S: Shader + std::marker::Sync,
+    C: Camera + std::marker::Sync,
+{
+    let mut samples = vec![None; active.len()];
+    samples.par_iter_mut().enumerate().for_each(|(i, smpl)| {
+        if !active[i] {
+            return;
+        }
+        let y = i as u32 / width;
+        let x = i as u32 % width;
+        let mut sampler = *sampler;
+        *smpl = render_sample(
+            camera,
+            scene,
+            shader,
+            &mut sampler,
+            x,
+            y,
+            sample_index,
+            jitter,
+        );
+    });
+    samples
+}
+
+// how a headless render walks the samples, all three give the same image; only the
+// incremental renderer samples adaptively and keeps a heatmap
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RendererType {
+    #[default]
+    Incremental,
+    Parallel,
+    Standard,
+}
+
+impl RendererType {
+    pub const NAMES: [&'static str; 3] = ["incremental", "parallel", "standard"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "incremental" => Some(RendererType::Incremental),
+            "parallel" => Some(RendererType::Parallel),
+            "standard" => Some(RendererType::Standard),
+            _ => None,
+        }
+    }
+}
 
 pub fn parallel_render<S, C>(
     camera: &C,
     scene: &Scene,
     shader: &S,
-    image: &mut ImageRGB,
+    sampler: &AnySampler,
+    film: &mut Film,
     spp: usize,
     jitter: bool,
 ) where
     S: Shader + std::marker::Sync,
     C: Camera + std::marker::Sync,
 {
-    image.data.par_iter_mut().enumerate().for_each(|(i, rgb)| {
-        let y = i as u32 / image.width;
-        let x = i as u32 % image.width;
-        let mut color = RGB::new(0.0, 0.0, 0.0);
-        for _ss in 0..spp {
-            let jitter_v = if jitter {
-                let mut rng = rand::thread_rng();
-                Some([rng.gen::<f32>(), rng.gen::<f32>()])
-            } else {
-                None
-            };
-
-            let primary_ray = match camera.generate_ray(x, y, jitter_v) {
-                Some(ray) => ray,
-                None => continue,
-            };
-            let tdata_opt = scene.trace(&primary_ray);
-            let this_color = shader.shade(scene, &tdata_opt);
-            color += this_color;
-        }
-        color /= spp as f32;
-        *rgb = color;
-    });
+    let active = vec![true; film.weights.len()];
+    for sample_index in 0..spp as u32 {
+        let samples = render_pass(
+            camera,
+            scene,
+            shader,
+            sampler,
+            film.width(),
+            &active,
+            sample_index,
+            jitter,
+        );
+        film.add_samples(&samples);
+    }
 }
 
 pub fn standard_render<S, C>(
     camera: &C,
     scene: &Scene,
     shader: &S,
-    image: &mut ImageRGB,
+    sampler: &AnySampler,
+    film: &mut Film,
     spp: usize,
     jitter: bool,
 ) where
     S: Shader + std::marker::Sync,
     C: Camera + std::marker::Sync,
 {
-    for y in 0..image.height {
-        for x in 0..image.width {
-            let mut color = RGB::new(0.0, 0.0, 0.0);
-            for _ss in 0..spp {
-                let jitter_v = if jitter {
-                    let mut rng = rand::thread_rng();
-                    Some([rng.gen::<f32>(), rng.gen::<f32>()])
-                } else {
-                    None
-                };
-
-                let primary_ray = match camera.generate_ray(x, y, jitter_v) {
-                    Some(ray) => ray,
-                    None => continue,
-                };
-                let tdata_opt = scene.trace(&primary_ray);
-                let this_color = shader.shade(scene, &tdata_opt);
-                color += this_color;
+    let mut sampler = *sampler;
+    let mut samples = vec![None; film.weights.len()];
+    for sample_index in 0..spp as u32 {
+        for y in 0..film.height() {
+            for x in 0..film.width() {
+                samples[(y * film.width() + x) as usize] = render_sample(
+                    camera,
+                    scene,
+                    shader,
+                    &mut sampler,
+                    x,
+                    y,
+                    sample_index,
+                    jitter,
+                );
             }
+        }
+        film.add_samples(&samples);
+    }
+}
+
+// stops sampling pixels once their estimated error is low enough
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveSampling {
+    // largest relative error of a pixel's mean luminance that counts as converged
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    // samples every pixel takes before its error estimate is trusted
+    #[serde(default = "default_min_spp")]
+    pub min_spp: u32,
+}
+
+fn default_threshold() -> f32 {
+    0.02
+}
+
+fn default_min_spp() -> u32 {
+    16
+}
+
+// running mean and variance of the luminance of a pixel's own samples (Welford)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelStats {
+    pub count: u32,
+    pub mean: f32,
+    pub m2: f32,
+}
 
-            color /= spp as f32;
-            image.set(x, y, &color);
+impl PixelStats {
+    pub fn add(&mut self, v: f32) {
+        self.count += 1;
+        let delta = v - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (v - self.mean);
+    }
+
+    // standard error of the mean over the mean, the offset keeps dark pixels from
+    // asking for a huge number of samples
+    pub fn relative_error(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
         }
+        let variance = self.m2 / (self.count - 1) as f32;
+        (variance / self.count as f32).sqrt() / (self.mean + 0.1)
     }
 }
 
@@ -82,24 +215,70 @@ pub struct IncrementalRenderer {
     pub spp_current: u32,
     pub spp_bound: Option<u32>,
     pub jitter: bool,
+    pub sampler: AnySampler,
+    // when set, spp_bound is the most samples a pixel can take
+    pub adaptive: Option<AdaptiveSampling>,
+    // accumulates every pass, the image is resolved from it after each one
+    pub film: Film,
+    pub stats: Vec<PixelStats>,
+    // pixels still taking samples, a converged pixel never comes back
+    pub active: Vec<bool>,
 }
 
 impl IncrementalRenderer {
-    pub fn new(stride: u32, bound: Option<u32>, jitter: bool) -> Self {
+    pub fn new(
+        stride: u32,
+        bound: Option<u32>,
+        jitter: bool,
+        sampler: AnySampler,
+        filter: AnyFilter,
+    ) -> Self {
         Self {
             spp_stride: stride,
             spp_current: 0,
             spp_bound: bound,
             jitter,
+            sampler,
+            adaptive: None,
+            film: Film::new(0, 0, filter),
+            stats: Vec::new(),
+            active: Vec::new(),
         }
     }
 
     pub fn has_finished(&self) -> bool {
-        return if let Some(bound) = self.spp_bound {
-            self.spp_current > bound
-        } else {
-            false
-        };
+        if let Some(bound) = self.spp_bound {
+            if self.spp_current >= bound {
+                return true;
+            }
+        }
+        // before the first pass the active pixels are not known yet
+        self.spp_current > 0 && !self.active.iter().any(|&a| a)
+    }
+
+    // samples the next pass adds, the last pass is cut short to land on the bound
+    fn next_pass_spp(&self) -> u32 {
+        match self.spp_bound {
+            Some(bound) => self.spp_stride.min(bound.saturating_sub(self.spp_current)),
+            None => self.spp_stride,
+        }
+    }
+
+    // drops everything accumulated so far, e.g. after the camera moved, the next pass
+    // starts over from the first sample
+    pub fn reset(&mut self) {
+        self.reset_to(self.film.width(), self.film.height());
+    }
+
+    fn reset_to(&mut self, width: u32, height: u32) {
+        self.film = Film::new(width, height, self.film.filter);
+        self.stats = vec![PixelStats::default(); (width * height) as usize];
+        self.active = vec![true; (width * height) as usize];
+        self.spp_current = 0;
+    }
+
+    pub fn active_pixels(&self) -> usize {
+        self.active.iter().filter(|&&a| a).count()
     }
 
     pub fn render<S, C>(&mut self, camera: &C, scene: &Scene, shader: &S, image: &mut ImageRGB)
@@ -107,32 +286,172 @@ impl IncrementalRenderer {
         S: Shader + std::marker::Sync,
         C: Camera + std::marker::Sync,
     {
+        if self.film.width() != image.width || self.film.height() != image.height {
+            self.reset_to(image.width, image.height);
+        }
         if self.has_finished() {
             return;
         }
-        image.data.par_iter_mut().enumerate().for_each(|(i, rgb)| {
-            let y = i as u32 / image.width;
-            let x = i as u32 % image.width;
-            let mut color = RGB::new(0.0, 0.0, 0.0);
-            for _ in 0..self.spp_stride {
-                let jitter_v = if self.jitter {
-                    let mut rng = rand::thread_rng();
-                    Some([rng.gen::<f32>(), rng.gen::<f32>()])
-                } else {
-                    None
-                };
-
-                let primary_ray = match camera.generate_ray(x, y, jitter_v) {
-                    Some(ray) => ray,
-                    None => continue,
-                };
-                let tdata_opt = scene.trace(&primary_ray);
-                let this_color = shader.shade(scene, &tdata_opt);
-                color += this_color;
+        let pass_spp = self.next_pass_spp();
+        let total_spp = self.spp_current + pass_spp;
+        // passes continue the sample sequence of the pixels where the last one stopped,
+        // active pixels have all taken the same number of samples
+        for sample_index in self.spp_current..total_spp {
+            let samples = render_pass(
+                camera,
+                scene,
+                shader,
+                &self.sampler,
+                image.width,
+                &self.active,
+                sample_index,
+                self.jitter,
+            );
+            self.film.add_samples(&samples);
+            for (stats, smpl) in self.stats.iter_mut().zip(samples.iter()) {
+                if let Some(smpl) = smpl {
+                    stats.add(smpl.l.y());
+                }
+            }
+        }
+        self.spp_current = total_spp;
+
+        if let Some(adaptive) = self.adaptive {
+            if self.spp_current >= adaptive.min_spp {
+                for (active, stats) in self.active.iter_mut().zip(self.stats.iter()) {
+                    *active = *active && stats.relative_error() > adaptive.threshold;
+                }
             }
-            *rgb = (*rgb * self.spp_current as f32 + color)
-                / (self.spp_current + self.spp_stride) as f32;
+        }
+        self.film.write_image(image);
+    }
+
+    // samples taken per pixel, black for none up to white for the most any pixel took
+    pub fn heatmap(&self) -> ImageRGB {
+        let mut image = ImageRGB::new(self.film.width(), self.film.height());
+        let max = self.stats.iter().map(|s| s.count).max().unwrap_or(0).max(1);
+        for (rgb, stats) in image.data.iter_mut().zip(self.stats.iter()) {
+            let t = stats.count as f32 / max as f32;
+            *rgb = RGB::new(
+                (3.0 * t).clamp(0.0, 1.0),
+                (3.0 * t - 1.0).clamp(0.0, 1.0),
+                (3.0 * t - 2.0).clamp(0.0, 1.0),
+            );
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::scene::scene_file::{FilterDesc, LoadedScene, SceneFile};
+
+    #[test]
+    fn renderers_are_deterministic() {
+        let mut desc = SceneFile::read(Path::new("./models/cornell_box_VI.toml")).unwrap();
+        desc.camera.width = 16;
+        desc.camera.height = 12;
+        desc.render.spp = 8;
+        desc.render.seed = 5;
+        // wide enough for samples to land in several pixels
+        desc.render.filter = FilterDesc::Gaussian {
+            radius: 1.5,
+            sigma: 0.5,
+        };
+        let LoadedScene {
+            mut scene,
+            camera,
+            shader,
+            render,
+        } = desc.build(Path::new("./models")).unwrap();
+        scene.build_accel();
+        let sampler = render.build_sampler();
+        let spp = render.spp as usize;
+
+        let filter = render.filter.build();
+        let resolve = |film: &Film| {
+            let mut image = ImageRGB::new(16, 12);
+            film.write_image(&mut image);
+            image
+        };
+
+        let mut film = Film::new(16, 12, filter);
+        standard_render(&camera, &scene, &shader, &sampler, &mut film, spp, true);
+        let standard = resolve(&film);
+        let mut film = Film::new(16, 12, filter);
+        parallel_render(&camera, &scene, &shader, &sampler, &mut film, spp, true);
+        let parallel = resolve(&film);
+        let mut incremental = ImageRGB::new(16, 12);
+        let mut renderer = IncrementalRenderer::new(3, Some(render.spp), true, sampler, filter);
+        while !renderer.has_finished() {
+            renderer.render(&camera, &scene, &shader, &mut incremental);
+        }
+
+        let bits = |img: &ImageRGB| -> Vec<[u32; 3]> {
+            img.data
+                .iter()
+                .map(|c| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()])
+                .collect()
+        };
+        assert_eq!(bits(&standard), bits(&parallel));
+        assert_eq!(bits(&standard), bits(&incremental));
+        assert!(standard.data.iter().any(|c| !c.is_zero()));
+
+        // a reset, as after a camera move, starts the accumulation over
+        renderer.render(&camera, &scene, &shader, &mut incremental);
+        renderer.reset();
+        assert_eq!(renderer.spp_current, 0);
+        assert!(!renderer.has_finished());
+        while !renderer.has_finished() {
+            renderer.render(&camera, &scene, &shader, &mut incremental);
+        }
+        assert_eq!(bits(&standard), bits(&incremental));
+
+        // another seed gives another noise pattern
+        desc.render.seed = 6;
+        let other = desc
+            .build(Path::new("./models"))
+            .unwrap()
+            .render
+            .build_sampler();
+        let mut film = Film::new(16, 12, filter);
+        parallel_render(&camera, &scene, &shader, &other, &mut film, spp, true);
+        assert_ne!(bits(&standard), bits(&resolve(&film)));
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_converged_pixels() {
+        let mut desc = SceneFile::read(Path::new("./models/cornell_box_VI.toml")).unwrap();
+        desc.camera.width = 16;
+        desc.camera.height = 12;
+        desc.render.spp = 64;
+        desc.render.spp_stride = 4;
+        desc.render.adaptive = Some(AdaptiveSampling {
+            threshold: 0.05,
+            min_spp: 8,
         });
-        self.spp_current += self.spp_stride;
+        let LoadedScene {
+            mut scene,
+            camera,
+            shader,
+            render,
+        } = desc.build(Path::new("./models")).unwrap();
+        scene.build_accel();
+
+        let mut renderer = render.build_renderer();
+        let mut image = ImageRGB::new(16, 12);
+        while !renderer.has_finished() {
+            renderer.render(&camera, &scene, &shader, &mut image);
+        }
+        let counts: Vec<u32> = renderer.stats.iter().map(|s| s.count).collect();
+        assert!(counts.iter().all(|&c| (8..=64).contains(&c)));
+        // the flat background converges right away while the noisy interior does not
+        assert_eq!(counts[0], 8);
+        assert!(counts.iter().any(|&c| c > 8));
+        let heatmap = renderer.heatmap();
+        assert!(heatmap.data[0].r < heatmap.data.iter().map(|c| c.r).fold(0.0, f32::max));
     }
 }
@@ -0,0 +1,72 @@
+use super::{hash, hash_to_unit, mix_bits, permutation_element, Sampler};
+
+// Halton sequence with Owen scrambled digits, PBRT's 4th ed. sec 8.6
+// sample i of a pixel is point i of the sequence, each pixel scrambles with its own seed;
+// past the prime table the remaining dimensions are plain hashed random values
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HaltonSampler {
+    pub seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    fn sample_dimension(&mut self) -> f32 {
+        let dim = self.dimension;
+        self.dimension += 1;
+        let dim_seed = hash(&[self.pixel_seed, dim as u64]);
+        match PRIMES.get(dim as usize) {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.sample_index, dim_seed),
+            None => hash_to_unit(&[dim_seed, self.sample_index as u64]),
+        }
+    }
+}
+
+// digits of a in the given base mirrored around the radix point, every digit permuted
+// depending on the digits before it
+pub fn owen_scrambled_radical_inverse(base: u32, mut a: u32, seed: u64) -> f32 {
+    let base64 = base as u64;
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    // digits past f32 precision make no difference
+    while inv_base_m > 1e-8 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_seed = mix_bits(seed ^ reversed_digits) as u32;
+        let digit = permutation_element(digit, base, digit_seed);
+        reversed_digits = reversed_digits * base64 + digit as u64;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    ((reversed_digits as f64 * inv_base_m) as f32).min(1.0 - f32::EPSILON)
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.pixel_seed = hash(&[self.seed, x as u64, y as u64]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> [f32; 2] {
+        [self.sample_dimension(), self.sample_dimension()]
+    }
+}
@@ -0,0 +1,36 @@
+use super::{hash, u32_to_unit, Sampler};
+
+// uniform random values, every pixel sample gets its own splitmix64 stream
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IndependentSampler {
+    pub seed: u64,
+    state: u64,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: 0 }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 32) as u32
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state = hash(&[self.seed, x as u64, y as u64, sample_index as u64]);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        u32_to_unit(self.next_u32())
+    }
+
+    fn get_2d(&mut self) -> [f32; 2] {
+        [self.get_1d(), self.get_1d()]
+    }
+}
@@ -0,0 +1,248 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use serde::Deserialize;
+
+use crate::utils::vector::Vector;
+
+use self::{
+    halton::HaltonSampler, independent::IndependentSampler, sobol::SobolSampler,
+    stratified::StratifiedSampler,
+};
+
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+// based on PBRT's 4th ed. book, ch. 8
+// a sampler hands out the sample values of one pixel sample, dimension after dimension,
+// so the same consumer code gets stratified or low discrepancy points across the
+// samples of a pixel; callers should request the dimensions in the same order for
+// every sample of a pixel
+pub trait Sampler {
+    // restarts at the first dimension of the given sample of pixel (x, y)
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32);
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> [f32; 2];
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerType {
+    pub const NAMES: [&'static str; 4] = ["independent", "stratified", "halton", "sobol"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(SamplerType::Independent),
+            "stratified" => Some(SamplerType::Stratified),
+            "halton" => Some(SamplerType::Halton),
+            "sobol" => Some(SamplerType::Sobol),
+            _ => None,
+        }
+    }
+}
+
+// sampler picked at runtime, e.g. from a scene file, every render thread works on a copy
+#[derive(Debug, Clone, Copy)]
+pub enum AnySampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+}
+
+impl AnySampler {
+    // spp is the sample count the stratified sampler divides its strata for
+    pub fn new(kind: SamplerType, spp: u32, seed: u64) -> Self {
+        match kind {
+            SamplerType::Independent => AnySampler::Independent(IndependentSampler::new(seed)),
+            SamplerType::Stratified => AnySampler::Stratified(StratifiedSampler::new(spp, seed)),
+            SamplerType::Halton => AnySampler::Halton(HaltonSampler::new(seed)),
+            SamplerType::Sobol => AnySampler::Sobol(SobolSampler::new(seed)),
+        }
+    }
+}
+
+impl Sampler for AnySampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        match self {
+            AnySampler::Independent(s) => s.start_pixel_sample(x, y, sample_index),
+            AnySampler::Stratified(s) => s.start_pixel_sample(x, y, sample_index),
+            AnySampler::Halton(s) => s.start_pixel_sample(x, y, sample_index),
+            AnySampler::Sobol(s) => s.start_pixel_sample(x, y, sample_index),
+        }
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        match self {
+            AnySampler::Independent(s) => s.get_1d(),
+            AnySampler::Stratified(s) => s.get_1d(),
+            AnySampler::Halton(s) => s.get_1d(),
+            AnySampler::Sobol(s) => s.get_1d(),
+        }
+    }
+
+    fn get_2d(&mut self) -> [f32; 2] {
+        match self {
+            AnySampler::Independent(s) => s.get_2d(),
+            AnySampler::Stratified(s) => s.get_2d(),
+            AnySampler::Halton(s) => s.get_2d(),
+            AnySampler::Sobol(s) => s.get_2d(),
+        }
+    }
+}
+
+// uniform point on the unit disk, Shirley and Chiu's concentric mapping keeps the
+// strata of u adjacent on the disk
+pub fn sample_uniform_disk_concentric(u: [f32; 2]) -> [f32; 2] {
+    let ox = 2.0 * u[0] - 1.0;
+    let oy = 2.0 * u[1] - 1.0;
+    if ox == 0.0 && oy == 0.0 {
+        return [0.0, 0.0];
+    }
+    let (r, theta) = if ox.abs() > oy.abs() {
+        (ox, FRAC_PI_4 * (oy / ox))
+    } else {
+        (oy, FRAC_PI_2 - FRAC_PI_4 * (ox / oy))
+    };
+    [r * theta.cos(), r * theta.sin()]
+}
+
+// uniform direction in the cone around +z whose half angle has cosine cos_max, based
+// on PBRT's 3rd ed. book, sec 13.6.4
+pub fn sample_uniform_cone(u: [f32; 2], cos_max: f32) -> Vector {
+    let cos_theta = (1.0 - u[0]) + u[0] * cos_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u[1];
+    Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+pub fn uniform_cone_pdf(cos_max: f32) -> f32 {
+    1.0 / (2.0 * PI * (1.0 - cos_max))
+}
+
+// 64 bit finalizer, PBRT's MixBits
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, &v| {
+        mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15))
+    })
+}
+
+// uniform float in [0, 1) from the upper 24 bits
+pub fn u32_to_unit(v: u32) -> f32 {
+    (v >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+pub fn hash_to_unit(values: &[u64]) -> f32 {
+    u32_to_unit((hash(values) >> 32) as u32)
+}
+
+// i-th element of a pseudo-random permutation of 0..n, Kensler's "Correlated
+// Multi-Jittered Sampling" (2013)
+pub fn permutation_element(mut i: u32, n: u32, p: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        // cycle walking until the element falls inside the range
+        if i < n {
+            break;
+        }
+    }
+    (i + p) % n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2D points of the second dimension pair for every sample of one pixel
+    fn points(kind: SamplerType, spp: u32, x: u32, y: u32) -> Vec<[f32; 2]> {
+        let mut sampler = AnySampler::new(kind, spp, 42);
+        (0..spp)
+            .map(|i| {
+                sampler.start_pixel_sample(x, y, i);
+                sampler.get_2d();
+                sampler.get_1d();
+                sampler.get_2d()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn samplers_stratify_and_converge() {
+        let kinds = [
+            SamplerType::Independent,
+            SamplerType::Stratified,
+            SamplerType::Halton,
+            SamplerType::Sobol,
+        ];
+        for kind in kinds {
+            for p in points(kind, 64, 3, 5) {
+                assert!(p.iter().all(|v| (0.0..1.0).contains(v)), "{:?}", kind);
+            }
+        }
+
+        // 16 samples of a (0, 2) sequence or a 4x4 jittered grid fill every cell
+        for kind in [SamplerType::Stratified, SamplerType::Sobol] {
+            let mut cells = [false; 16];
+            for p in points(kind, 16, 7, 1) {
+                cells[(p[0] * 4.0) as usize + 4 * (p[1] * 4.0) as usize] = true;
+            }
+            assert!(cells.iter().all(|&c| c), "{:?}", kind);
+        }
+
+        // mean squared error of a smooth integral over many pixels, exact value 1/4
+        let mse = |kind: SamplerType| {
+            let mut err = 0.0;
+            for px in 0..256 {
+                let pts = points(kind, 16, px, 0);
+                let est = pts.iter().map(|p| p[0] * p[1]).sum::<f32>() / pts.len() as f32;
+                err += (est - 0.25) * (est - 0.25);
+            }
+            err / 256.0
+        };
+        let independent = mse(SamplerType::Independent);
+        for kind in &kinds[1..] {
+            assert!(mse(*kind) < 0.5 * independent, "{:?}", kind);
+        }
+    }
+}
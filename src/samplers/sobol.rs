@@ -0,0 +1,90 @@
+use super::{hash, u32_to_unit, Sampler};
+
+// shuffled Owen scrambled Sobol points, Burley's "Practical Hash-based Owen Scrambling"
+// (JCGT 2020)
+// every 1D or 2D request takes the first one or two Sobol dimensions, which together
+// form a (0, 2) sequence, and decorrelates them from the other requests by shuffling
+// the sample index with a per dimension Owen scramble; each sample prefix of a power
+// of two size keeps its stratification in every 1D and 2D projection
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SobolSampler {
+    pub seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    // shuffled index and the scramble seed of the next dimension
+    fn next_dimension(&mut self) -> (u32, u64) {
+        let dim_seed = hash(&[self.pixel_seed, self.dimension as u64]);
+        self.dimension += 1;
+        let index = nested_uniform_scramble(self.sample_index, dim_seed as u32);
+        (index, dim_seed)
+    }
+}
+
+// first Sobol dimension, the van der Corput sequence
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+// second Sobol dimension, its generator matrix is the Pascal triangle mod 2
+fn sobol_1(mut index: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut x = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+
+// hash that only propagates bits upwards, Vegdahl's variant of the Laine-Karras hash
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20_adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x0552_6c56);
+    x ^= x.wrapping_mul(0x53a2_2864);
+    x
+}
+
+// base 2 Owen scrambling, every bit flipped depending on the bits above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.pixel_seed = hash(&[self.seed, x as u64, y as u64]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (index, dim_seed) = self.next_dimension();
+        let seed = (dim_seed >> 32) as u32;
+        u32_to_unit(nested_uniform_scramble(sobol_0(index), seed))
+    }
+
+    fn get_2d(&mut self) -> [f32; 2] {
+        let (index, dim_seed) = self.next_dimension();
+        let seed_x = (dim_seed >> 32) as u32;
+        let seed_y = hash(&[dim_seed]) as u32;
+        [
+            u32_to_unit(nested_uniform_scramble(sobol_0(index), seed_x)),
+            u32_to_unit(nested_uniform_scramble(sobol_1(index), seed_y)),
+        ]
+    }
+}
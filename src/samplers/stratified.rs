@@ -0,0 +1,58 @@
+use super::{hash, hash_to_unit, permutation_element, Sampler};
+
+// jittered strata, PBRT's 4th ed. sec 8.5
+// 1D dimensions split [0, 1) into spp strata and 2D ones use the smallest square-ish grid
+// with at least spp cells, each dimension visits the strata in its own random order so
+// the dimensions are not correlated with each other
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StratifiedSampler {
+    pub spp: u32,
+    pub seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(spp: u32, seed: u64) -> Self {
+        Self {
+            spp: spp.max(1),
+            seed,
+            ..Default::default()
+        }
+    }
+
+    // stratum of the current sample among n, plus the jitter inside it
+    fn stratum(&mut self, n: u32) -> (u32, u64) {
+        let dim_seed = hash(&[self.pixel_seed, self.dimension as u64]);
+        self.dimension += 1;
+        let stratum = permutation_element(self.sample_index % n, n, dim_seed as u32);
+        (stratum, dim_seed)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.pixel_seed = hash(&[self.seed, x as u64, y as u64]);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (stratum, dim_seed) = self.stratum(self.spp);
+        let jitter = hash_to_unit(&[dim_seed, self.sample_index as u64]);
+        ((stratum as f32 + jitter) / self.spp as f32).min(1.0 - f32::EPSILON)
+    }
+
+    fn get_2d(&mut self) -> [f32; 2] {
+        let nx = (self.spp as f32).sqrt().ceil() as u32;
+        let ny = self.spp.div_ceil(nx);
+        let (stratum, dim_seed) = self.stratum(nx * ny);
+        let jx = hash_to_unit(&[dim_seed, self.sample_index as u64, 0]);
+        let jy = hash_to_unit(&[dim_seed, self.sample_index as u64, 1]);
+        [
+            (((stratum % nx) as f32 + jx) / nx as f32).min(1.0 - f32::EPSILON),
+            (((stratum / nx) as f32 + jy) / ny as f32).min(1.0 - f32::EPSILON),
+        ]
+    }
+}
@@ -1,26 +1,126 @@
-use std::path::Path;
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    lights::Light,
-    primitives::{material_data::MaterialData, mesh::Mesh, Intersectable},
+    accel::bvh::{Bvh, BvhStats},
+    lights::{
+        light_sampler::{AnyLightSampler, LightSamplerType},
+        mesh_light::MeshLight,
+        Light,
+    },
+    primitives::{
+        material_data::MaterialData,
+        mesh::{Mesh, MotionKey},
+        triangle::triangle_intersect,
+        AnyShape, Intersectable, Shape,
+    },
     rays::{intersection::IntersectionData, ray::Ray},
+    textures::{FilterMode, Texture, WrapMode},
     utils::{
+        aabb::AABB,
         rgb::RGB,
+        transform::{AnimatedTransform, Transform},
         vector::{Point, Vector},
     },
 };
 
+pub mod scene_file;
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct TraceData {
     pub isect: IntersectionData,
     pub mat_data: MaterialData,
+    // index into Scene::lights when an emitter was hit
+    pub light: Option<u32>,
+}
+
+// a primitive the scene-wide BVH is built over, instances being the leaves that lead
+// into the BVH of their prototype
+#[derive(Debug, Clone, Copy)]
+pub enum ScenePrim {
+    Face { mesh: u32, face: u32 },
+    // mesh moving rigidly, traced through its own BVH in object space
+    MovingMesh { mesh: u32 },
+    Light { light: u32 },
+    // triangle of a mesh light given in the scene file
+    LightFace { light: u32, face: u32 },
+    Instance { instance: u32 },
+    Shape { shape: u32 },
+}
+
+// mesh shared by any number of instances, kept in its own object space with a BVH
+// over its faces
+#[derive(Debug, Clone, Default)]
+pub struct Prototype {
+    pub mesh: Mesh,
+    pub mat_ind: u16,
+    pub accel: Bvh,
+}
+
+// a prototype placed in the world, drawn with its own material when it has one
+// instances are not sampled as lights, emissive ones only shine when hit
+#[derive(Debug, Clone, Copy)]
+pub struct Instance {
+    pub prototype: u32,
+    pub world_from_object: Transform,
+    pub mat_ind: Option<u16>,
+    // world space bounds
+    pub bb: AABB,
+}
+
+// brings a hit found in object space, on a prototype or a rigidly moving mesh, back to
+// world space, `ray` being the world space ray that was traced
+fn hit_to_world(xf: &Transform, isect: IntersectionData, ray: &Ray) -> IntersectionData {
+    let mut geo_normal = xf.apply_normal(isect.geo_normal);
+    geo_normal.normalize();
+    let mut sh_normal = xf.apply_normal(isect.sh_normal);
+    sh_normal.normalize();
+    // the geometric normal follows the winding, like on a mesh baked in place
+    if xf.swaps_handedness() {
+        geo_normal = -1.0 * geo_normal;
+    }
+    if sh_normal.dot(geo_normal) < 0.0 {
+        geo_normal = -1.0 * geo_normal;
+    }
+    IntersectionData {
+        point: xf.apply_point(isect.point),
+        geo_normal,
+        sh_normal,
+        wo: -1.0 * ray.direction,
+        ..isect
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Scene {
     pub prims: Vec<(Mesh, u16)>,
     pub materials_data: Vec<MaterialData>,
+    pub material_names: Vec<String>,
     pub lights: Vec<Light>,
+    // light of each emissive mesh in `prims`
+    pub mesh_lights: Vec<Option<u32>>,
+    // object space BVH over the faces of each mesh in `prims` that moves rigidly
+    pub mesh_accels: Vec<Option<Bvh>>,
+    pub accel: Bvh,
+    pub accel_prims: Vec<ScenePrim>,
+    pub textures: Vec<Texture>,
+    pub texture_paths: Vec<PathBuf>,
+    pub prototypes: Vec<Prototype>,
+    pub instances: Vec<Instance>,
+    // analytic surfaces with their materials
+    pub shapes: Vec<(AnyShape, u16)>,
+    // unbounded shapes, tested one by one next to the BVH
+    pub unbounded_prims: Vec<ScenePrim>,
+    // the environment light in `lights`, which replaces the shaders' background
+    pub environment: Option<u32>,
+    // lights in `lights` that rays escaping the scene can see, the environment and
+    // directional lights with an angular size
+    pub infinite_lights: Vec<u32>,
+    // how shaders pick the light to sample at a hit, built with the accelerator
+    pub light_sampler_type: LightSamplerType,
+    pub light_sampler: AnyLightSampler,
 }
 
 impl Scene {
@@ -28,92 +128,421 @@ impl Scene {
         Self {
             prims: Vec::new(),
             materials_data: Vec::new(),
+            material_names: Vec::new(),
             lights: Vec::new(),
+            mesh_lights: Vec::new(),
+            mesh_accels: Vec::new(),
+            accel: Bvh::default(),
+            accel_prims: Vec::new(),
+            textures: Vec::new(),
+            texture_paths: Vec::new(),
+            prototypes: Vec::new(),
+            instances: Vec::new(),
+            shapes: Vec::new(),
+            unbounded_prims: Vec::new(),
+            environment: None,
+            infinite_lights: Vec::new(),
+            light_sampler_type: LightSamplerType::default(),
+            light_sampler: AnyLightSampler::default(),
         }
     }
 
-    pub fn trace(&self, ray: &Ray) -> Option<TraceData> {
-        if self.prims.len() == 0 {
+    // material with its texture maps evaluated at the hit point
+    pub fn material_at(&self, mat_ind: u16, isect: &IntersectionData) -> MaterialData {
+        let mut mat = self.materials_data[mat_ind as usize];
+        if let Some(t) = mat.ka_map {
+            mat.ka = mat.ka * self.textures[t as usize].sample(isect.uv);
+        }
+        if let Some(t) = mat.kd_map {
+            mat.kd = mat.kd * self.textures[t as usize].sample(isect.uv);
+        }
+        if let Some(t) = mat.ks_map {
+            mat.ks = mat.ks * self.textures[t as usize].sample(isect.uv);
+        }
+        mat
+    }
+
+    // loads an image as a texture, reusing it if the same file was loaded before
+    pub fn load_texture(&mut self, path: &Path, wrap: WrapMode, filter: FilterMode) -> Option<u32> {
+        let cached = self
+            .texture_paths
+            .iter()
+            .zip(self.textures.iter())
+            .position(|(p, t)| p == path && t.wrap == wrap && t.filter == filter);
+        if let Some(ind) = cached {
+            return Some(ind as u32);
+        }
+        match Texture::load(path, wrap, filter) {
+            Ok(tex) => {
+                self.textures.push(tex);
+                self.texture_paths.push(path.to_path_buf());
+                Some((self.textures.len() - 1) as u32)
+            }
+            Err(e) => {
+                eprintln!("failed to load texture {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    // MTL map statements may carry options before the file name, only -clamp is honoured
+    fn load_mtl_texture(&mut self, map: &str, dir: &Path) -> Option<u32> {
+        let tokens: Vec<&str> = map.split_whitespace().collect();
+        let file = tokens.last()?;
+        let clamp = tokens.windows(2).any(|w| w[0] == "-clamp" && w[1] == "on");
+        let wrap = if clamp {
+            WrapMode::Clamp
+        } else {
+            WrapMode::Repeat
+        };
+        self.load_texture(&dir.join(file), wrap, FilterMode::Bilinear)
+    }
+
+    // every bounded primitive rays can hit, area lights that are faces of an emissive
+    // mesh are hit through the mesh
+    fn scene_prims(&self) -> impl Iterator<Item = (ScenePrim, AABB)> + '_ {
+        let is_moving = |mesh_ind: usize| matches!(self.mesh_accels.get(mesh_ind), Some(Some(_)));
+        let faces = self
+            .prims
+            .iter()
+            .enumerate()
+            .filter(move |(mesh_ind, _)| !is_moving(*mesh_ind))
+            .flat_map(|(mesh_ind, (mesh, _))| {
+                mesh.face_aabbs
+                    .iter()
+                    .enumerate()
+                    .map(move |(face_ind, bb)| {
+                        let prim = ScenePrim::Face {
+                            mesh: mesh_ind as u32,
+                            face: face_ind as u32,
+                        };
+                        (prim, *bb)
+                    })
+            });
+        let lights = self
+            .lights
+            .iter()
+            .enumerate()
+            .flat_map(|(light_ind, light)| {
+                let light_ind = light_ind as u32;
+                let prims: Vec<(ScenePrim, AABB)> = match light {
+                    Light::Area(al) => al
+                        .shape
+                        .bounds()
+                        .map(|bb| (ScenePrim::Light { light: light_ind }, bb))
+                        .into_iter()
+                        .collect(),
+                    Light::Mesh(ml) if !ml.from_mesh => ml
+                        .triangles
+                        .iter()
+                        .enumerate()
+                        .map(|(face, tri)| {
+                            let prim = ScenePrim::LightFace {
+                                light: light_ind,
+                                face: face as u32,
+                            };
+                            (prim, tri.bb)
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                prims
+            });
+        let instances = self.instances.iter().enumerate().map(|(ind, inst)| {
+            let prim = ScenePrim::Instance {
+                instance: ind as u32,
+            };
+            (prim, inst.bb)
+        });
+        let shapes = self
+            .shapes
+            .iter()
+            .enumerate()
+            .filter_map(|(ind, (shape, _))| {
+                let prim = ScenePrim::Shape { shape: ind as u32 };
+                Some((prim, shape.bounds()?))
+            });
+        let moving_meshes = self
+            .prims
+            .iter()
+            .enumerate()
+            .filter(move |(mesh_ind, _)| is_moving(*mesh_ind))
+            .map(|(mesh_ind, (mesh, _))| {
+                let prim = ScenePrim::MovingMesh {
+                    mesh: mesh_ind as u32,
+                };
+                (prim, mesh.aabb)
+            });
+        faces
+            .chain(moving_meshes)
+            .chain(lights)
+            .chain(instances)
+            .chain(shapes)
+    }
+
+    fn scene_unbounded_prims(&self) -> impl Iterator<Item = ScenePrim> + '_ {
+        self.shapes
+            .iter()
+            .enumerate()
+            .filter(|(_, (shape, _))| shape.bounds().is_none())
+            .map(|(ind, _)| ScenePrim::Shape { shape: ind as u32 })
+    }
+
+    // (re)builds the BVH over every mesh face, area light triangle and instance,
+    // must be called again after the scene geometry or lights change
+    pub fn build_accel(&mut self) -> BvhStats {
+        let (accel_prims, bbs): (Vec<ScenePrim>, Vec<AABB>) = self.scene_prims().unzip();
+        self.accel = Bvh::build(&bbs);
+        self.accel_prims = accel_prims;
+        self.unbounded_prims = self.scene_unbounded_prims().collect();
+        let scene_bb = self.accel.nodes.first().map_or(AABB::default(), |n| n.bb);
+        self.light_sampler = AnyLightSampler::new(self.light_sampler_type, &self.lights, &scene_bb);
+        self.accel.stats
+    }
+
+    // closest hit on an instance's prototype nearer than t_max
+    fn instance_intersect(&self, instance: u32, ray: &Ray, t_max: f32) -> Option<IntersectionData> {
+        let inst = &self.instances[instance as usize];
+        let proto = &self.prototypes[inst.prototype as usize];
+        let obj_ray = inst.world_from_object.inverse().apply_ray(ray);
+        let mut closest = None;
+        proto.accel.intersect(&obj_ray, t_max, |face, t_max| {
+            let isect = triangle_intersect(&obj_ray, &proto.mesh.face_at(face as usize, ray.time))?;
+            if isect.depth >= t_max {
+                return None;
+            }
+            closest = Some(isect);
+            Some(isect.depth)
+        });
+        closest.map(|isect| hit_to_world(&inst.world_from_object, isect, ray))
+    }
+
+    // closest hit on a rigidly moving mesh nearer than t_max, found in the mesh's space
+    // at the time of the ray
+    fn moving_mesh_intersect(
+        &self,
+        mesh_ind: u32,
+        ray: &Ray,
+        t_max: f32,
+    ) -> Option<IntersectionData> {
+        let (mesh, _) = &self.prims[mesh_ind as usize];
+        let (Some(xf), Some(Some(accel))) =
+            (mesh.rigid_motion, self.mesh_accels.get(mesh_ind as usize))
+        else {
             return None;
+        };
+        let world_from_object = xf.at(ray.time);
+        let obj_ray = world_from_object.inverse().apply_ray(ray);
+        let mut closest = None;
+        accel.intersect(&obj_ray, t_max, |face, t_max| {
+            let isect =
+                triangle_intersect(&obj_ray, &mesh.object_face_at(face as usize, ray.time))?;
+            if isect.depth >= t_max {
+                return None;
+            }
+            closest = Some(isect);
+            Some(isect.depth)
+        });
+        closest.map(|isect| hit_to_world(&world_from_object, isect, ray))
+    }
+
+    fn moving_mesh_test_line_intersect(&self, mesh_ind: u32, ray: &Ray, depth: f32) -> bool {
+        let (mesh, _) = &self.prims[mesh_ind as usize];
+        let (Some(xf), Some(Some(accel))) =
+            (mesh.rigid_motion, self.mesh_accels.get(mesh_ind as usize))
+        else {
+            return false;
+        };
+        let obj_ray = xf.at(ray.time).inverse().apply_ray(ray);
+        accel.test_line_intersect(&obj_ray, depth, |face| {
+            triangle_intersect(&obj_ray, &mesh.object_face_at(face as usize, ray.time))
+                .is_some_and(|isect| isect.depth < depth)
+        })
+    }
+
+    fn instance_test_line_intersect(&self, instance: u32, ray: &Ray, depth: f32) -> bool {
+        let inst = &self.instances[instance as usize];
+        let proto = &self.prototypes[inst.prototype as usize];
+        let obj_ray = inst.world_from_object.inverse().apply_ray(ray);
+        proto.accel.test_line_intersect(&obj_ray, depth, |face| {
+            triangle_intersect(&obj_ray, &proto.mesh.face_at(face as usize, ray.time))
+                .is_some_and(|isect| isect.depth < depth)
+        })
+    }
+
+    fn prim_intersect(&self, prim: ScenePrim, ray: &Ray, t_max: f32) -> Option<IntersectionData> {
+        match prim {
+            ScenePrim::Face { mesh, face } => triangle_intersect(
+                ray,
+                &self.prims[mesh as usize].0.face_at(face as usize, ray.time),
+            ),
+            ScenePrim::Light { light } => match &self.lights[light as usize] {
+                Light::Area(al) => al.intersect(ray),
+                _ => None,
+            },
+            ScenePrim::LightFace { light, face } => match &self.lights[light as usize] {
+                Light::Mesh(ml) => ml.triangles[face as usize].intersect(ray),
+                _ => None,
+            },
+            ScenePrim::MovingMesh { mesh } => self.moving_mesh_intersect(mesh, ray, t_max),
+            ScenePrim::Instance { instance } => self.instance_intersect(instance, ray, t_max),
+            ScenePrim::Shape { shape } => self.shapes[shape as usize].0.intersect(ray),
         }
-        let mut trace_opt: Option<TraceData> = None;
-        for (prim, ind) in self.prims.iter() {
-            if let Some(curr_isect) = prim.intersect(ray) {
-                if let Some(trace) = trace_opt {
-                    if trace.isect.depth > curr_isect.depth {
-                        trace_opt = Some(TraceData {
-                            isect: curr_isect,
-                            mat_data: self.materials_data[*ind as usize],
-                        });
-                    }
-                } else {
-                    trace_opt = Some(TraceData {
-                        isect: curr_isect,
-                        mat_data: self.materials_data[*ind as usize],
-                    });
+    }
+
+    fn prim_trace_data(&self, prim: ScenePrim, isect: IntersectionData) -> TraceData {
+        match prim {
+            ScenePrim::Face { mesh, .. } | ScenePrim::MovingMesh { mesh } => {
+                let mat_data = self.surface_material(self.prims[mesh as usize].1, &isect);
+                let light = self.mesh_lights.get(mesh as usize).copied().flatten();
+                TraceData {
+                    isect,
+                    mat_data,
+                    light,
                 }
             }
-        }
-        for light in self.lights.iter() {
-            if let Light::Area(al) = light {
-                if let Some(curr_isect) = al.intersect(ray) {
-                    if let Some(trace) = trace_opt {
-                        if trace.isect.depth > curr_isect.depth {
-                            trace_opt = Some(TraceData {
-                                isect: curr_isect,
-                                mat_data: MaterialData {
-                                    le: Some(al.power),
-                                    ..Default::default()
-                                },
-                            });
-                        }
-                    } else {
-                        trace_opt = Some(TraceData {
-                            isect: curr_isect,
-                            mat_data: MaterialData {
-                                le: Some(al.power),
-                                ..Default::default()
-                            },
-                        });
-                    }
+            ScenePrim::Light { light } => {
+                let le = match &self.lights[light as usize] {
+                    Light::Area(al) => Some(al.le(isect.point, isect.wo)),
+                    _ => None,
+                };
+                emitter_trace_data(isect, le, light)
+            }
+            ScenePrim::LightFace { light, face } => {
+                let le = match &self.lights[light as usize] {
+                    Light::Mesh(ml) => Some(ml.le(ml.triangles[face as usize].normal, isect.wo)),
+                    _ => None,
+                };
+                emitter_trace_data(isect, le, light)
+            }
+            ScenePrim::Instance { instance } => {
+                let inst = &self.instances[instance as usize];
+                let mat_ind = inst
+                    .mat_ind
+                    .unwrap_or(self.prototypes[inst.prototype as usize].mat_ind);
+                TraceData {
+                    isect,
+                    mat_data: self.surface_material(mat_ind, &isect),
+                    light: None,
                 }
             }
+            ScenePrim::Shape { shape } => TraceData {
+                isect,
+                mat_data: self.surface_material(self.shapes[shape as usize].1, &isect),
+                light: None,
+            },
         }
+    }
 
-        trace_opt
+    fn surface_material(&self, mat_ind: u16, isect: &IntersectionData) -> MaterialData {
+        let mut mat_data = self.material_at(mat_ind, isect);
+        // emitters only shine towards the side their normal points to
+        if mat_data.le.is_some() && isect.geo_normal.dot(isect.wo) <= 0.0 {
+            mat_data.le = Some(RGB::default());
+        }
+        mat_data
     }
 
-    pub fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
-        if self.prims.len() == 0 {
-            return false;
+    fn prim_test_line_intersect(&self, prim: ScenePrim, ray: &Ray, depth: f32) -> bool {
+        match prim {
+            ScenePrim::Light { light } => match &self.lights[light as usize] {
+                Light::Area(al) => al.test_line_intersect(ray, depth),
+                _ => false,
+            },
+            ScenePrim::MovingMesh { mesh } => {
+                self.moving_mesh_test_line_intersect(mesh, ray, depth)
+            }
+            ScenePrim::Instance { instance } => {
+                self.instance_test_line_intersect(instance, ray, depth)
+            }
+            _ => self
+                .prim_intersect(prim, ray, depth)
+                .is_some_and(|isect| isect.depth < depth),
+        }
+    }
+
+    pub fn trace(&self, ray: &Ray) -> Option<TraceData> {
+        if self.accel.nodes.is_empty() {
+            return self.trace_brute_force(ray);
         }
-        for (prim, _ind) in self.prims.iter() {
-            if prim.test_line_intersect(ray, depth) {
-                return true;
+        let mut closest: Option<(ScenePrim, IntersectionData)> = None;
+        self.accel.intersect(ray, f32::MAX, |prim_ind, t_max| {
+            let prim = self.accel_prims[prim_ind as usize];
+            let isect = self.prim_intersect(prim, ray, t_max)?;
+            if isect.depth >= t_max {
+                return None;
             }
+            closest = Some((prim, isect));
+            Some(isect.depth)
+        });
+        for &prim in self.unbounded_prims.iter() {
+            let t_max = closest.map_or(f32::MAX, |(_, c)| c.depth);
+            if let Some(isect) = self.prim_intersect(prim, ray, t_max) {
+                if isect.depth < t_max {
+                    closest = Some((prim, isect));
+                }
+            }
+        }
+        closest.map(|(prim, isect)| self.prim_trace_data(prim, isect))
+    }
+
+    pub fn test_line_intersect(&self, ray: &Ray, depth: f32) -> bool {
+        if self.accel.nodes.is_empty() {
+            return self.test_line_intersect_brute_force(ray, depth);
         }
-        for light in self.lights.iter() {
-            if let Light::Area(al) = light {
-                if al.test_line_intersect(ray, depth) {
-                    return true;
+        self.unbounded_prims
+            .iter()
+            .any(|&prim| self.prim_test_line_intersect(prim, ray, depth))
+            || self.accel.test_line_intersect(ray, depth, |prim_ind| {
+                self.prim_test_line_intersect(self.accel_prims[prim_ind as usize], ray, depth)
+            })
+    }
+
+    // linear scan over every primitive, used when no BVH was built
+    pub fn trace_brute_force(&self, ray: &Ray) -> Option<TraceData> {
+        let mut closest: Option<(ScenePrim, IntersectionData)> = None;
+        let prims = self.scene_prims().map(|(prim, _)| prim);
+        for prim in prims.chain(self.scene_unbounded_prims()) {
+            if let Some(isect) = self.prim_intersect(prim, ray, f32::MAX) {
+                if closest.is_none_or(|(_, c)| c.depth > isect.depth) {
+                    closest = Some((prim, isect));
                 }
             }
         }
-        false
+        closest.map(|(prim, isect)| self.prim_trace_data(prim, isect))
+    }
+
+    pub fn test_line_intersect_brute_force(&self, ray: &Ray, depth: f32) -> bool {
+        self.scene_prims()
+            .map(|(prim, _)| prim)
+            .chain(self.scene_unbounded_prims())
+            .any(|prim| self.prim_test_line_intersect(prim, ray, depth))
     }
 
-    pub fn load_obj_file(&mut self, path: &Path) {
-        let (mut obj_models, obj_materials) =
-            tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).expect("failed to load OBJ file");
-        let obj_materials = obj_materials.expect("failed to load MTL file");
+    // returns the range of material indices the file added
+    pub fn load_obj_file(&mut self, path: &Path) -> Result<Range<usize>, tobj::LoadError> {
+        // a default material comes first in a scene without materials
+        let mats_start_ind = self.materials_data.len().max(1);
+        let meshes = self.load_obj_meshes(path)?;
+        self.prims.extend(meshes);
+        self.add_mesh_lights();
+        Ok(mats_start_ind..self.materials_data.len())
+    }
+
+    // adds the file's materials and returns its meshes without placing them in the
+    // scene, e.g. to become instance prototypes
+    pub fn load_obj_meshes(&mut self, path: &Path) -> Result<Vec<(Mesh, u16)>, tobj::LoadError> {
+        let (mut obj_models, obj_materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+        let obj_materials = obj_materials?;
+        let dir = path.parent().unwrap_or(Path::new(""));
 
-        if self.materials_data.len() == 0 {
+        if self.materials_data.is_empty() {
             self.materials_data.reserve(obj_materials.len() + 1);
-            self.materials_data.push(MaterialData::default());
+            self.add_material("default", MaterialData::default());
         } else {
             self.materials_data.reserve(obj_materials.len());
         }
-        self.prims.reserve(obj_models.len());
+        let mut meshes = Vec::with_capacity(obj_models.len());
 
         let mats_start_ind = self.materials_data.len();
         for obj_mat in obj_materials {
@@ -130,14 +559,53 @@ impl Scene {
             if let Some(ns) = obj_mat.shininess {
                 mat.ns = ns;
             }
-            if let Some(tf_str) = obj_mat.unknown_param.get("Tf") {
-                let mut spl_iter = tf_str.split(' ');
-                let r = spl_iter.next().unwrap().parse::<f32>().unwrap();
-                let g = spl_iter.next().unwrap().parse::<f32>().unwrap();
-                let b = spl_iter.next().unwrap().parse::<f32>().unwrap();
-                mat.kt = RGB::new(r, g, b);
+            // a map without its constant means the map alone
+            if let Some(map) = &obj_mat.ambient_texture {
+                mat.ka_map = self.load_mtl_texture(map, dir);
+                if obj_mat.ambient.is_none() {
+                    mat.ka = RGB::new(1.0, 1.0, 1.0);
+                }
+            }
+            if let Some(map) = &obj_mat.diffuse_texture {
+                mat.kd_map = self.load_mtl_texture(map, dir);
+                if obj_mat.diffuse.is_none() {
+                    mat.kd = RGB::new(1.0, 1.0, 1.0);
+                }
             }
-            self.materials_data.push(mat);
+            if let Some(map) = &obj_mat.specular_texture {
+                mat.ks_map = self.load_mtl_texture(map, dir);
+                if obj_mat.specular.is_none() {
+                    mat.ks = RGB::new(1.0, 1.0, 1.0);
+                }
+            }
+            if let Some(ni) = obj_mat.optical_density {
+                mat.ior = ni;
+            }
+            // PBR extension, roughness and metallic
+            for (key, value) in [("Pr", &mut mat.roughness), ("Pm", &mut mat.metallic)] {
+                if let Some(param) = obj_mat.unknown_param.get(key) {
+                    match param.trim().parse::<f32>() {
+                        Ok(v) => *value = Some(v.clamp(0.0, 1.0)),
+                        Err(_) => {
+                            eprintln!("{}: ignoring malformed {} {:?}", obj_mat.name, key, param)
+                        }
+                    }
+                }
+            }
+            if let Some(ke) = obj_mat.unknown_param.get("Ke") {
+                match parse_mtl_rgb(ke) {
+                    Some(ke) if !ke.is_zero() => mat.le = Some(ke),
+                    Some(_) => {}
+                    None => eprintln!("{}: ignoring malformed Ke {:?}", obj_mat.name, ke),
+                }
+            }
+            if let Some(tf) = obj_mat.unknown_param.get("Tf") {
+                match parse_mtl_rgb(tf) {
+                    Some(kt) => mat.kt = kt,
+                    None => eprintln!("{}: ignoring malformed Tf {:?}", obj_mat.name, tf),
+                }
+            }
+            self.add_material(&obj_mat.name, mat);
         }
 
         for obj_model in obj_models.iter_mut() {
@@ -146,6 +614,8 @@ impl Scene {
             let obj_pos_inds = std::mem::take(&mut obj_mesh.indices);
             let obj_normals = std::mem::take(&mut obj_mesh.normals);
             let obj_normal_inds = std::mem::take(&mut obj_mesh.normal_indices);
+            let obj_texcoords = std::mem::take(&mut obj_mesh.texcoords);
+            let obj_texcoord_inds = std::mem::take(&mut obj_mesh.texcoord_indices);
 
             let positions: Vec<Point> = obj_positions
                 .chunks_exact(3)
@@ -157,18 +627,463 @@ impl Scene {
                 .map(|a| Vector::new(a[0], a[1], a[2]))
                 .collect();
 
-            let mesh = Mesh::new(positions, normals, obj_pos_inds, obj_normal_inds);
+            let texcoords: Vec<[f32; 2]> = obj_texcoords
+                .chunks_exact(2)
+                .map(|a| [a[0], a[1]])
+                .collect();
+
+            let mesh = Mesh::new(
+                positions,
+                normals,
+                texcoords,
+                obj_pos_inds,
+                obj_normal_inds,
+                obj_texcoord_inds,
+            );
             let mat_ind: u16 = if let Some(m_id) = obj_mesh.material_id {
                 (m_id + mats_start_ind).try_into().unwrap()
             } else {
                 0
             };
 
-            self.prims.push((mesh, mat_ind));
+            meshes.push((mesh, mat_ind));
         }
+        Ok(meshes)
+    }
+
+    pub fn add_material(&mut self, name: &str, mat: MaterialData) -> u16 {
+        self.materials_data.push(mat);
+        self.material_names.push(name.to_string());
+        (self.materials_data.len() - 1).try_into().unwrap()
+    }
+
+    // the most recently added material with this name
+    pub fn find_material(&self, name: &str) -> Option<u16> {
+        self.material_names
+            .iter()
+            .rposition(|n| n == name)
+            .map(|i| i as u16)
+    }
+
+    // the prototype's BVH is built right away, its faces being in object space
+    pub fn add_prototype(&mut self, mesh: Mesh, mat_ind: u16) -> u32 {
+        let accel = Bvh::build(&mesh.face_aabbs);
+        self.prototypes.push(Prototype {
+            mesh,
+            mat_ind,
+            accel,
+        });
+        (self.prototypes.len() - 1) as u32
+    }
+
+    pub fn add_instance(
+        &mut self,
+        prototype: u32,
+        world_from_object: Transform,
+        mat_ind: Option<u16>,
+    ) -> u32 {
+        let bb = world_from_object.apply_aabb(&self.prototypes[prototype as usize].mesh.aabb);
+        self.instances.push(Instance {
+            prototype,
+            world_from_object,
+            mat_ind,
+            bb,
+        });
+        (self.instances.len() - 1) as u32
     }
 
+    pub fn add_shape(&mut self, shape: AnyShape, mat_ind: u16) -> u32 {
+        self.shapes.push((shape, mat_ind));
+        (self.shapes.len() - 1) as u32
+    }
+
+    // a later environment light takes an earlier one's place, so only one is ever
+    // sampled or seen by escaping rays
     pub fn add_light(&mut self, light: Light) {
+        let ind = self.lights.len() as u32;
+        if let Light::Environment(_) = light {
+            if let Some(prev) = self.environment {
+                self.lights[prev as usize] = light;
+                return;
+            }
+            self.environment = Some(ind);
+        }
+        if light.is_infinite() {
+            self.infinite_lights.push(ind);
+        }
         self.lights.push(light);
     }
+
+    // radiance arriving along a ray that leaves the scene in direction `dir`, the
+    // environment's or else `background`, plus that of any light at infinity in the way
+    pub fn escaped_radiance(&self, dir: Vector, background: RGB) -> RGB {
+        let mut le = if self.environment.is_some() {
+            RGB::default()
+        } else {
+            background
+        };
+        for &light in &self.infinite_lights {
+            le += self.lights[light as usize].le_dir(dir);
+        }
+        le
+    }
+
+    // registers one mesh light, made of all its faces, for every mesh whose material
+    // emits and that has no light yet, the light keeps the mesh's orientation
+    pub fn add_mesh_lights(&mut self) {
+        self.mesh_lights.resize(self.prims.len(), None);
+        for mesh_ind in 0..self.prims.len() {
+            let (mesh, mat_ind) = &self.prims[mesh_ind];
+            let Some(le) = self.materials_data[*mat_ind as usize].le else {
+                continue;
+            };
+            if self.mesh_lights[mesh_ind].is_some() || le.is_zero() {
+                continue;
+            }
+            self.mesh_lights[mesh_ind] = Some(self.lights.len() as u32);
+            self.lights
+                .push(Light::Mesh(MeshLight::from_mesh(mesh, le)));
+        }
+    }
+
+    // makes a mesh deform through the given keys, unless there are none, see
+    // Mesh::set_motion, and move along the rigid motion, rays then finding it through a
+    // BVH over its faces in object space
+    // the light of an emissive mesh samples it where it is at the time of the ray, see
+    // MeshLight::from_mesh for the motions it allows
+    pub fn set_mesh_motion(
+        &mut self,
+        mesh_ind: usize,
+        keys: Vec<MotionKey>,
+        rigid_motion: Option<AnimatedTransform>,
+    ) {
+        let (mesh, mat_ind) = &mut self.prims[mesh_ind];
+        if !keys.is_empty() {
+            mesh.set_motion(keys);
+        }
+        if let Some(rigid_motion) = rigid_motion {
+            mesh.set_rigid_motion(rigid_motion);
+            let accel = Bvh::build(&mesh.face_aabbs);
+            self.mesh_accels
+                .resize(self.mesh_accels.len().max(mesh_ind + 1), None);
+            self.mesh_accels[mesh_ind] = Some(accel);
+        }
+        let (Some(Some(light)), Some(le)) = (
+            self.mesh_lights.get(mesh_ind),
+            self.materials_data[*mat_ind as usize].le,
+        ) else {
+            return;
+        };
+        self.lights[*light as usize] = Light::Mesh(MeshLight::from_mesh(mesh, le));
+    }
+}
+
+// hit on a light that is not part of the scene's geometry, which has no material
+fn emitter_trace_data(isect: IntersectionData, le: Option<RGB>, light: u32) -> TraceData {
+    TraceData {
+        isect,
+        mat_data: MaterialData {
+            le,
+            ..Default::default()
+        },
+        light: Some(light),
+    }
+}
+
+// MTL colors are either "r g b" or a single gray value
+fn parse_mtl_rgb(s: &str) -> Option<RGB> {
+    let vals = s
+        .split_whitespace()
+        .map(|v| v.parse::<f32>().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    match vals[..] {
+        [v] => Some(RGB::new(v, v, v)),
+        [r, g, b] => Some(RGB::new(r, g, b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        images::image_rgb::ImageRGB,
+        lights::{environment::EnvironmentLight, AreaLight, Light},
+        primitives::{
+            mesh::Mesh,
+            triangle::{triangle_intersect, Triangle},
+            AnyShape,
+        },
+        rays::ray::Ray,
+        utils::{
+            rgb::RGB,
+            transform::{AnimatedTransform, Transform},
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::Scene;
+
+    fn cornell_box() -> Scene {
+        let mut scene = Scene::new();
+        scene
+            .load_obj_file(Path::new("./models/cornell_box_VI.obj"))
+            .unwrap();
+        // a free standing light next to the emissive ceiling panel of the OBJ
+        scene.add_light(Light::Area(AreaLight::new(
+            RGB::new(1.0, 1.0, 1.0),
+            AnyShape::Triangle(Triangle::new(
+                Point::new(443.0, 500.0, 227.0),
+                Point::new(443.0, 500.0, 332.0),
+                Point::new(313.0, 500.0, 332.0),
+                Vector::new(0.0, -1.0, 0.0),
+            )),
+        )));
+        scene
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let mut scene = cornell_box();
+        let stats = scene.build_accel();
+        assert_eq!(
+            stats.prim_count,
+            scene
+                .prims
+                .iter()
+                .map(|(m, _)| m.face_count())
+                .sum::<usize>()
+                + 1
+        );
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..2000 {
+            let origin = Point::new(
+                rng.gen_range(0.0..556.0),
+                rng.gen_range(0.0..548.0),
+                rng.gen_range(-800.0..559.0),
+            );
+            let dir = Vector::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            let ray = Ray::new(origin, dir);
+
+            let brute = scene.trace_brute_force(&ray);
+            let accel = scene.trace(&ray);
+            assert_eq!(brute.is_some(), accel.is_some());
+            if let (Some(b), Some(a)) = (brute, accel) {
+                assert_eq!(b.isect.depth, a.isect.depth);
+                assert_eq!(b.isect.point, a.isect.point);
+                assert_eq!(b.mat_data.le.is_some(), a.mat_data.le.is_some());
+                assert_eq!(b.mat_data.kd.y(), a.mat_data.kd.y());
+            }
+
+            let depth = rng.gen_range(0.0..800.0);
+            assert_eq!(
+                scene.test_line_intersect_brute_force(&ray, depth),
+                scene.test_line_intersect(&ray, depth)
+            );
+        }
+    }
+
+    #[test]
+    fn emissive_mesh_lights() {
+        let mut scene = Scene::new();
+        scene
+            .load_obj_file(Path::new("./models/cornell_box_VI_manyLights.obj"))
+            .unwrap();
+        // three emissive quads, one light each made of its two triangles
+        assert_eq!(scene.lights.len(), 3);
+        assert!(scene
+            .lights
+            .iter()
+            .all(|l| matches!(l, Light::Mesh(ml) if ml.from_mesh && ml.triangles.len() == 2)));
+        scene.build_accel();
+
+        // looking up at the middle of the ceiling panel
+        let ray = Ray::new(Point::new(280.0, 100.0, 280.0), Vector::new(0.0, 1.0, 0.0));
+        let tdata = scene.trace(&ray).unwrap();
+        let light = tdata.light.unwrap() as usize;
+        let Light::Mesh(ml) = &scene.lights[light] else {
+            panic!("not a mesh light");
+        };
+        assert!((tdata.mat_data.le.unwrap().r - 93.0).abs() < 1e-3);
+        assert!((ml.le(tdata.isect.geo_normal, tdata.isect.wo).r - 93.0).abs() < 1e-3);
+        // the power of the whole panel, radiance times pi times its area
+        let expected = 93.0 * std::f32::consts::PI * ml.area;
+        assert!((ml.power.r - expected).abs() < 1e-3 * expected);
+        // the hit's pdf agrees with sampling the light from the ray's origin
+        let ls = ml.sample_li(ray.origin, 0.0, &[0.3, 0.6]).unwrap();
+        let l_point = ray.origin + ls.wi * ls.dist;
+        let l_normal = Vector::new(0.0, -1.0, 0.0);
+        assert!((ml.pdf_li(ray.origin, l_point, l_normal, 0.0) - ls.pdf).abs() < 1e-3 * ls.pdf);
+
+        // the panel's back is dark, between it and the ceiling
+        let ray = Ray::new(Point::new(280.0, 548.4, 280.0), Vector::new(0.0, -1.0, 0.0));
+        let tdata = scene.trace(&ray).unwrap();
+        assert!(tdata.light.is_some());
+        assert!(tdata.mat_data.le.unwrap().is_zero());
+    }
+
+    #[test]
+    fn second_environment_replaces_first() {
+        let sky = |c: f32| {
+            let mut image = ImageRGB::new(4, 2);
+            for y in 0..2 {
+                for x in 0..4 {
+                    image.set(x, y, &RGB::new(c, c, c));
+                }
+            }
+            Light::Environment(EnvironmentLight::new(image, 1.0, Transform::default()))
+        };
+        let mut scene = Scene::new();
+        scene.add_light(sky(1.0));
+        scene.add_light(sky(3.0));
+        assert_eq!(scene.environment, Some(0));
+        assert_eq!(scene.lights.len(), 1);
+        assert_eq!(scene.infinite_lights, vec![0]);
+        let le = scene.escaped_radiance(Vector::new(0.0, 1.0, 0.0), RGB::new(5.0, 5.0, 5.0));
+        assert!((le.r - 3.0).abs() < 1e-4, "{:?}", le);
+    }
+
+    #[test]
+    fn instances_match_baked_meshes() {
+        // a non uniform scale that mirrors the sphere, so windings flip
+        let xf = Transform::translate(Vector::new(-100.0, 250.0, 40.0))
+            * Transform::rotate(0.6, Vector::new(1.0, 1.0, 0.0))
+            * Transform::scale(1.5, -0.7, 1.2);
+
+        let mut instanced = Scene::new();
+        let meshes = instanced
+            .load_obj_meshes(Path::new("./models/glass_sphere.obj"))
+            .unwrap();
+        let (mesh, mat_ind) = meshes[0].clone();
+        let prototype = instanced.add_prototype(mesh.clone(), mat_ind);
+        instanced.add_instance(prototype, xf, None);
+        instanced.build_accel();
+
+        let mut baked = Scene::new();
+        baked.materials_data = instanced.materials_data.clone();
+        let normals = mesh
+            .normals
+            .iter()
+            .map(|&n| {
+                let mut n = xf.apply_normal(n);
+                n.normalize();
+                n
+            })
+            .collect();
+        let positions = mesh.positions.iter().map(|&p| xf.apply_point(p)).collect();
+        baked.prims.push((
+            Mesh::new(
+                positions,
+                normals,
+                mesh.texcoords.to_vec(),
+                mesh.pos_inds.to_vec(),
+                mesh.norm_inds.to_vec(),
+                mesh.tex_inds.to_vec(),
+            ),
+            mat_ind,
+        ));
+        baked.build_accel();
+
+        let target = instanced.instances[0].bb.centroid();
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = target
+                + Vector::new(
+                    rng.gen_range(-300.0..300.0),
+                    rng.gen_range(-300.0..300.0),
+                    rng.gen_range(-300.0..300.0),
+                );
+            let aim = target
+                + Vector::new(
+                    rng.gen_range(-80.0..80.0),
+                    rng.gen_range(-80.0..80.0),
+                    rng.gen_range(-80.0..80.0),
+                );
+            let ray = Ray::new(origin, origin.vec2point(aim));
+
+            let a = instanced.trace(&ray);
+            let b = baked.trace(&ray);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                hits += 1;
+                assert!((a.isect.depth - b.isect.depth).abs() < 1e-2);
+                assert!(a.isect.point.vec2point(b.isect.point).norm() < 1e-2);
+                assert!(a.isect.geo_normal.dot(b.isect.geo_normal) > 0.999);
+                assert!(a.isect.sh_normal.dot(b.isect.sh_normal) > 0.999);
+                assert!((a.isect.wo.dot(b.isect.wo) - 1.0).abs() < 1e-6);
+            }
+            let depth = rng.gen_range(0.0..600.0);
+            assert_eq!(
+                instanced.test_line_intersect(&ray, depth),
+                baked.test_line_intersect(&ray, depth)
+            );
+        }
+        assert!(hits > 100, "{}", hits);
+    }
+
+    #[test]
+    fn moving_meshes_match_their_faces() {
+        // the sphere turning more than half a turn while it mirrors and moves away
+        let mut scene = Scene::new();
+        scene
+            .load_obj_file(Path::new("./models/glass_sphere.obj"))
+            .unwrap();
+        let axis = Vector::new(1.0, 1.0, 0.0);
+        let motion = AnimatedTransform {
+            scales: [Vector::new(1.0, 1.0, 1.0), Vector::new(1.5, -0.7, 1.2)],
+            rotations: [(axis, 0.0), (axis, 4.0)],
+            translations: [Vector::new(0.0, 0.0, 0.0), Vector::new(-100.0, 250.0, 40.0)],
+            pivot: Vector::new(400.0, 80.0, 130.0),
+        };
+        scene.set_mesh_motion(0, Vec::new(), Some(motion));
+        scene.build_accel();
+        let mesh = &scene.prims[0].0;
+
+        let target = mesh.aabb.centroid();
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = target
+                + Vector::new(
+                    rng.gen_range(-400.0..400.0),
+                    rng.gen_range(-400.0..400.0),
+                    rng.gen_range(-400.0..400.0),
+                );
+            let aim = target
+                + Vector::new(
+                    rng.gen_range(-150.0..150.0),
+                    rng.gen_range(-150.0..150.0),
+                    rng.gen_range(-150.0..150.0),
+                );
+            let ray = Ray::new(origin, origin.vec2point(aim)).with_time(rng.gen());
+
+            // against every face where the motion has carried it
+            let expected = (0..mesh.face_count())
+                .filter_map(|i| triangle_intersect(&ray, &mesh.face_at(i, ray.time)))
+                .min_by(|a, b| a.depth.total_cmp(&b.depth));
+            let found = scene.trace(&ray);
+            assert_eq!(found.is_some(), expected.is_some());
+            if let (Some(a), Some(b)) = (found, expected) {
+                hits += 1;
+                assert!((a.isect.depth - b.depth).abs() < 1e-2);
+                assert!(a.isect.geo_normal.dot(b.geo_normal) > 0.999);
+                assert!(a.isect.sh_normal.dot(b.sh_normal) > 0.999);
+            }
+            let depth = rng.gen_range(0.0..800.0);
+            assert_eq!(
+                scene.test_line_intersect(&ray, depth),
+                expected.is_some_and(|e| e.depth < depth)
+            );
+        }
+        assert!(hits > 100, "{}", hits);
+    }
 }
@@ -0,0 +1,1815 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bsdfs::{conductor::ConductorPreset, microfacet::MicrofacetType},
+    camera::{
+        equirectangular::Equirectangular,
+        fisheye::Fisheye,
+        orthographic::Orthographic,
+        perspective::{FovAxis, Perspective},
+        AnyCamera, CameraFrame, Shutter, ThinLens,
+    },
+    film::filters::{
+        AnyFilter, BoxFilter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter,
+    },
+    images::load_image,
+    lights::{
+        directional::DirectionalLight,
+        environment::EnvironmentLight,
+        light_sampler::LightSamplerType,
+        mesh_light::{face_triangle, MeshLight},
+        spot::SpotLight,
+        AmbientLight, AreaLight, Light, PointLight,
+    },
+    primitives::{
+        cone::Cone,
+        cylinder::Cylinder,
+        disk::Disk,
+        material_data::MaterialData,
+        mesh::{Mesh, MotionKey},
+        plane::Plane,
+        quad::Quad,
+        sphere::Sphere,
+        triangle::Triangle,
+        AnyShape,
+    },
+    render::{AdaptiveSampling, IncrementalRenderer},
+    samplers::{AnySampler, SamplerType},
+    shaders::{
+        ambient_shader::AmbientShader, distributed_shader::DistributedShader,
+        path_tracer_shader::PathTracerShader, whitted_shader::WhittedShader, AnyShader,
+    },
+    textures::{FilterMode, WrapMode},
+    utils::{
+        transform::{AnimatedTransform, Transform},
+        vector::{Point, Vector},
+        Extent2D,
+    },
+};
+
+use super::Scene;
+
+// TOML scene description, relative paths are resolved against the scene file's directory
+//
+// [camera]             projection selected by `type`, thin lens and output resolution
+// [render]             sample budget, sampler, light sampler, seed, pixel filter and
+//                      adaptive sampling
+// [shader]             shader and its parameters, selected by `type`
+// [[geometry]]         OBJ files, optionally forcing a single material or moving over
+//                      the frame through [geometry.motion]; with `instances` the file's
+//                      meshes are shared by copies placed by their own transforms
+// [[shapes]]           analytic spheres, planes, disks, quads, cylinders and cones,
+//                      selected by `type`
+// [materials.<name>]   overrides for materials loaded from MTL files, or new materials
+// [[lights]]           ambient, point, spot, directional, area, mesh or environment
+//                      lights, selected by `type`; area lights are a triangle or any
+//                      bounded shape, spherical ones being sampled by the solid angle
+//                      they cover, mesh lights the triangles of an OBJ file sampled by
+//                      area, environment lights an HDR latitude-longitude map
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub render: RenderDesc,
+    pub shader: ShaderDesc,
+    #[serde(default)]
+    pub geometry: Vec<GeometryDesc>,
+    #[serde(default)]
+    pub shapes: Vec<SceneShapeDesc>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDesc>,
+    #[serde(default)]
+    pub lights: Vec<LightDesc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraType {
+    #[default]
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDesc {
+    #[serde(default, rename = "type")]
+    pub kind: CameraType,
+    pub eye: [f32; 3],
+    pub at: [f32; 3],
+    #[serde(default = "default_up")]
+    pub up: [f32; 3],
+    // field of view in degrees, along `fov_axis` for perspective cameras and across the
+    // image circle for fisheyes
+    #[serde(default = "default_fov")]
+    pub fov: f32,
+    #[serde(default)]
+    pub fov_axis: FovAxis,
+    // height of the orthographic view plane in scene units
+    pub view_height: Option<f32>,
+    pub width: u32,
+    pub height: u32,
+    // thin lens radius in scene units, 0 renders everything in focus
+    #[serde(default)]
+    pub aperture: f32,
+    // distance of the sharp plane from the eye, defaults to the distance to `at`
+    pub focus_distance: Option<f32>,
+    // polygonal aperture with this many sides, 0 for a round one
+    #[serde(default)]
+    pub aperture_blades: u32,
+    // rotation of the polygonal aperture in degrees
+    #[serde(default)]
+    pub aperture_rotation: f32,
+    // open and close times of the shutter, geometry moves from time 0 to 1
+    #[serde(default)]
+    pub shutter: [f32; 2],
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderDesc {
+    // total samples per pixel
+    #[serde(default = "default_spp")]
+    pub spp: u32,
+    // samples added per progressive pass
+    #[serde(default = "default_spp_stride")]
+    pub spp_stride: u32,
+    #[serde(default = "default_true")]
+    pub jitter: bool,
+    #[serde(default)]
+    pub sampler: SamplerType,
+    // how the path tracer picks the light to sample at each hit
+    #[serde(default)]
+    pub light_sampler: LightSamplerType,
+    // renders with the same seed, sampler and spp are bit-identical
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub filter: FilterDesc,
+    // spp becomes the per pixel maximum
+    pub adaptive: Option<AdaptiveSampling>,
+}
+
+impl Default for RenderDesc {
+    fn default() -> Self {
+        Self {
+            spp: default_spp(),
+            spp_stride: default_spp_stride(),
+            jitter: true,
+            sampler: SamplerType::default(),
+            light_sampler: LightSamplerType::default(),
+            seed: 0,
+            filter: FilterDesc::default(),
+            adaptive: None,
+        }
+    }
+}
+
+impl RenderDesc {
+    pub fn build_sampler(&self) -> AnySampler {
+        AnySampler::new(self.sampler, self.spp, self.seed)
+    }
+
+    pub fn build_renderer(&self) -> IncrementalRenderer {
+        let mut renderer = IncrementalRenderer::new(
+            self.spp_stride,
+            Some(self.spp),
+            self.jitter,
+            self.build_sampler(),
+            self.filter.build(),
+        );
+        renderer.adaptive = self.adaptive;
+        renderer
+    }
+}
+
+// pixel reconstruction filter, radii are in pixels
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FilterDesc {
+    Box {
+        #[serde(default = "default_box_radius")]
+        radius: f32,
+    },
+    Tent {
+        #[serde(default = "default_one")]
+        radius: f32,
+    },
+    Gaussian {
+        #[serde(default = "default_gaussian_radius")]
+        radius: f32,
+        #[serde(default = "default_gaussian_sigma")]
+        sigma: f32,
+    },
+    Mitchell {
+        #[serde(default = "default_two")]
+        radius: f32,
+        #[serde(default = "default_mitchell_bc")]
+        b: f32,
+        #[serde(default = "default_mitchell_bc")]
+        c: f32,
+    },
+    Lanczos {
+        #[serde(default = "default_two")]
+        radius: f32,
+    },
+}
+
+impl Default for FilterDesc {
+    fn default() -> Self {
+        FilterDesc::Box {
+            radius: default_box_radius(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ShaderDesc {
+    Ambient {
+        #[serde(default)]
+        background: [f32; 3],
+    },
+    Whitted {
+        #[serde(default)]
+        background: [f32; 3],
+        #[serde(default = "default_bias")]
+        bias: f32,
+        #[serde(default = "default_depth")]
+        reflection_depth: u16,
+    },
+    Distributed {
+        #[serde(default)]
+        background: [f32; 3],
+        #[serde(default = "default_bias")]
+        bias: f32,
+        #[serde(default = "default_depth")]
+        reflection_depth: u16,
+    },
+    PathTracer {
+        #[serde(default)]
+        background: [f32; 3],
+        #[serde(default = "default_bias")]
+        bias: f32,
+        #[serde(default = "default_depth")]
+        reflection_depth: u16,
+        #[serde(default = "default_continue_prob")]
+        continue_prob: f32,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeometryDesc {
+    pub file: PathBuf,
+    // replaces the MTL materials of every mesh in the file
+    pub material: Option<String>,
+    pub motion: Option<MotionDesc>,
+    // when given, the file's meshes are only drawn through these instances
+    #[serde(default)]
+    pub instances: Vec<InstanceDesc>,
+}
+
+// copies of a geometry's meshes, one per cell of a `count` grid whose cells are
+// `spacing` apart along the world axes, the first cell being placed by the transform
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceDesc {
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+    // degrees, counterclockwise looking down the axis
+    #[serde(default)]
+    pub rotate: f32,
+    #[serde(default = "default_up")]
+    pub axis: [f32; 3],
+    #[serde(default)]
+    pub translate: [f32; 3],
+    // replaces the geometry's materials for these copies
+    pub material: Option<String>,
+    #[serde(default = "default_count")]
+    pub count: [u32; 3],
+    #[serde(default)]
+    pub spacing: [f32; 3],
+}
+
+// scale, then rotation around `axis`, then translation
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformDesc {
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 3],
+    // degrees, counterclockwise looking down the axis
+    #[serde(default)]
+    pub rotate: f32,
+    #[serde(default = "default_up")]
+    pub axis: [f32; 3],
+    #[serde(default)]
+    pub translate: [f32; 3],
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        Self {
+            scale: default_scale(),
+            rotate: 0.0,
+            axis: default_up(),
+            translate: [0.0; 3],
+        }
+    }
+}
+
+// geometry moving over the frame, rigidly between two poses and/or deforming into the
+// meshes of another file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MotionDesc {
+    // poses at times 0 and 1 applied to the file's geometry, scaling and rotating
+    // around `pivot`; rotations around the same axis may exceed half a turn, others
+    // take the shorter way
+    #[serde(default)]
+    pub start: TransformDesc,
+    #[serde(default)]
+    pub end: TransformDesc,
+    #[serde(default)]
+    pub pivot: [f32; 3],
+    // the same meshes at time 1, with their vertices in the same order
+    pub end_file: Option<PathBuf>,
+}
+
+// cylinders and cones are open, `axis` going from the center of their base to the
+// center of the top or to the apex
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ShapeDesc {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+    },
+    Plane {
+        point: [f32; 3],
+        normal: [f32; 3],
+    },
+    Disk {
+        center: [f32; 3],
+        normal: [f32; 3],
+        radius: f32,
+    },
+    // parallelogram from a corner along two edges, facing their cross product
+    Quad {
+        corner: [f32; 3],
+        edges: [[f32; 3]; 2],
+    },
+    Cylinder {
+        base: [f32; 3],
+        axis: [f32; 3],
+        radius: f32,
+    },
+    Cone {
+        base: [f32; 3],
+        axis: [f32; 3],
+        radius: f32,
+    },
+}
+
+// a shape drawn with a material, the "default" one when none is given
+#[derive(Debug, Deserialize)]
+pub struct SceneShapeDesc {
+    #[serde(flatten)]
+    pub shape: ShapeDesc,
+    pub material: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDesc {
+    pub ka: Option<[f32; 3]>,
+    pub kd: Option<[f32; 3]>,
+    pub ks: Option<[f32; 3]>,
+    pub kt: Option<[f32; 3]>,
+    pub le: Option<[f32; 3]>,
+    pub ns: Option<f32>,
+    // index of refraction of transmissive materials, MTL Ni
+    pub ior: Option<f32>,
+    // microfacet parameters, see MaterialData
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+    pub distribution: Option<MicrofacetType>,
+    pub conductor: Option<ConductorPreset>,
+    // color maps, .ppm files are taken as sRGB encoded and .pfm or .hdr ones as linear
+    pub map_ka: Option<PathBuf>,
+    pub map_kd: Option<PathBuf>,
+    pub map_ks: Option<PathBuf>,
+    #[serde(default)]
+    pub wrap: WrapMode,
+    #[serde(default)]
+    pub filter: FilterMode,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LightDesc {
+    Ambient {
+        color: [f32; 3],
+    },
+    Point {
+        color: [f32; 3],
+        position: [f32; 3],
+    },
+    // either a triangle given by its vertices or a shape
+    Area {
+        power: [f32; 3],
+        vertices: Option<[[f32; 3]; 3]>,
+        // defaults to the winding order normal
+        normal: Option<[f32; 3]>,
+        shape: Option<ShapeDesc>,
+    },
+    // one luminaire made of every triangle in an OBJ file, emitting `power` in total
+    // towards the side of their vertex normals, or of their winding without them
+    Mesh {
+        file: PathBuf,
+        power: [f32; 3],
+    },
+    // latitude-longitude map (.hdr or .pfm) lighting the scene from infinitely far
+    // away, its top row looking along +y and its center along -z before the rotation
+    Environment {
+        file: PathBuf,
+        #[serde(default = "default_one")]
+        scale: f32,
+        // degrees, counterclockwise looking down the axis
+        #[serde(default)]
+        rotate: f32,
+        #[serde(default = "default_up")]
+        axis: [f32; 3],
+    },
+    // point light aimed at `at`, lighting a cone of `cone_angle` degrees half angle and
+    // fading out over its outer `cone_delta` degrees
+    Spot {
+        color: [f32; 3],
+        position: [f32; 3],
+        at: [f32; 3],
+        #[serde(default = "default_cone_angle")]
+        cone_angle: f32,
+        #[serde(default = "default_cone_delta")]
+        cone_delta: f32,
+    },
+    // light from infinitely far away travelling along `direction`, `color` being the
+    // irradiance on a surface facing it; an angular diameter in degrees, about 0.53
+    // for the sun, softens its shadows
+    Directional {
+        color: [f32; 3],
+        direction: [f32; 3],
+        #[serde(default)]
+        angular_diameter: f32,
+    },
+}
+
+fn default_up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_count() -> [u32; 3] {
+    [1, 1, 1]
+}
+
+fn default_fov() -> f32 {
+    60.0
+}
+
+fn default_spp() -> u32 {
+    64
+}
+
+fn default_spp_stride() -> u32 {
+    1
+}
+
+fn default_box_radius() -> f32 {
+    0.5
+}
+
+fn default_gaussian_radius() -> f32 {
+    1.5
+}
+
+fn default_gaussian_sigma() -> f32 {
+    0.5
+}
+
+fn default_mitchell_bc() -> f32 {
+    1.0 / 3.0
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_cone_angle() -> f32 {
+    30.0
+}
+
+fn default_cone_delta() -> f32 {
+    5.0
+}
+
+fn default_two() -> f32 {
+    2.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_bias() -> f32 {
+    0.001
+}
+
+fn default_depth() -> u16 {
+    2
+}
+
+fn default_continue_prob() -> f32 {
+    0.5
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Obj(PathBuf, tobj::LoadError),
+    Texture(String, PathBuf),
+    UnknownMaterial(String, PathBuf),
+    Invalid(String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io(p, e) => write!(f, "cannot read {}: {}", p.display(), e),
+            SceneFileError::Parse(p, e) => write!(f, "invalid scene file {}:\n{}", p.display(), e),
+            SceneFileError::Obj(p, e) => write!(f, "cannot load OBJ file {}: {}", p.display(), e),
+            SceneFileError::Texture(m, p) => {
+                write!(f, "material {:?}: cannot load texture {}", m, p.display())
+            }
+            SceneFileError::UnknownMaterial(m, p) => write!(
+                f,
+                "geometry {}: material {:?} is not defined in [materials] or any MTL file",
+                p.display(),
+                m
+            ),
+            SceneFileError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+pub struct LoadedScene {
+    pub scene: Scene,
+    pub camera: AnyCamera,
+    pub shader: AnyShader,
+    pub render: RenderDesc,
+}
+
+impl SceneFile {
+    pub fn parse(src: &str, path: &Path) -> Result<Self, SceneFileError> {
+        toml::from_str(src).map_err(|e| SceneFileError::Parse(path.to_path_buf(), e))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SceneFileError> {
+        let src =
+            std::fs::read_to_string(path).map_err(|e| SceneFileError::Io(path.to_path_buf(), e))?;
+        Self::parse(&src, path)
+    }
+
+    pub fn load(path: &Path) -> Result<LoadedScene, SceneFileError> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::read(path)?.build(dir)
+    }
+
+    // builds the scene, camera and shader, paths are relative to `dir`
+    pub fn build(&self, dir: &Path) -> Result<LoadedScene, SceneFileError> {
+        self.validate()?;
+        let mut scene = Scene::new();
+        scene.light_sampler_type = self.render.light_sampler;
+
+        let mut moving = Vec::new();
+        for geo in self.geometry.iter() {
+            let file = dir.join(&geo.file);
+            if !geo.instances.is_empty() {
+                self.build_instances(geo, &file, &mut scene)?;
+                continue;
+            }
+            let first_prim = scene.prims.len();
+            scene
+                .load_obj_file(&file)
+                .map_err(|e| SceneFileError::Obj(file.clone(), e))?;
+
+            if let Some(name) = &geo.material {
+                let mat_ind = self.scene_material(name, &file, &mut scene)?;
+                for (_, ind) in scene.prims[first_prim..].iter_mut() {
+                    *ind = mat_ind;
+                }
+            }
+
+            if let Some(motion) = &geo.motion {
+                let end_meshes = match &motion.end_file {
+                    Some(end_file) => Some(load_end_meshes(
+                        &dir.join(end_file),
+                        &scene.prims[first_prim..],
+                    )?),
+                    None => None,
+                };
+                for mesh_ind in first_prim..scene.prims.len() {
+                    let keys = match &end_meshes {
+                        Some(ends) => {
+                            let mesh = &scene.prims[mesh_ind].0;
+                            let start = MotionKey {
+                                positions: mesh.positions.clone(),
+                                normals: mesh.normals.clone(),
+                            };
+                            vec![start, ends[mesh_ind - first_prim].clone()]
+                        }
+                        None => Vec::new(),
+                    };
+                    scene.set_mesh_motion(mesh_ind, keys, motion.rigid_motion());
+                }
+                moving.push((geo, first_prim..scene.prims.len()));
+            }
+        }
+
+        for (i, desc) in self.shapes.iter().enumerate() {
+            let name = desc.material.as_deref().unwrap_or("default");
+            let mat_ind = match scene.find_material(name) {
+                Some(ind) => ind,
+                None if name == "default" || self.materials.contains_key(name) => {
+                    scene.add_material(name, MaterialData::default())
+                }
+                None => {
+                    return Err(SceneFileError::Invalid(format!(
+                    "[[shapes]] #{}: material {:?} is not defined in [materials] or any MTL file",
+                    i + 1,
+                    name
+                )))
+                }
+            };
+            scene.add_shape(desc.shape.build(), mat_ind);
+        }
+
+        for (name, desc) in self.materials.iter() {
+            if scene.find_material(name).is_none() {
+                scene.add_material(name, MaterialData::default());
+            }
+            // several OBJ files may define a material with the same name
+            for i in 0..scene.materials_data.len() {
+                if scene.material_names[i] == *name {
+                    let mut mat = scene.materials_data[i];
+                    desc.apply(name, &mut mat, &mut scene, dir)?;
+                    scene.materials_data[i] = mat;
+                }
+            }
+        }
+
+        // materials made emissive by the scene file
+        scene.add_mesh_lights();
+        // mesh lights pick faces by their area at time 0, which must stay their share
+        // of the mesh's area
+        for (geo, meshes) in moving {
+            let Some(motion) = &geo.motion else {
+                continue;
+            };
+            let even = motion.end_file.is_none()
+                && motion.rigid_motion().is_none_or(|m| m.scales_evenly());
+            if !even && meshes.into_iter().any(|i| scene.mesh_lights[i].is_some()) {
+                return Err(SceneFileError::Invalid(format!(
+                    "[geometry.motion] of {}: emissive meshes cannot deform or change \
+                     their proportions",
+                    geo.file.display()
+                )));
+            }
+        }
+        for light in self.lights.iter() {
+            scene.add_light(light.build(dir)?);
+        }
+
+        let camera = self.camera.build();
+        let shader = self.shader.build();
+        Ok(LoadedScene {
+            scene,
+            camera,
+            shader,
+            render: self.render,
+        })
+    }
+
+    // materials only defined in the scene file get their values once every geometry
+    // is loaded
+    fn scene_material(
+        &self,
+        name: &str,
+        file: &Path,
+        scene: &mut Scene,
+    ) -> Result<u16, SceneFileError> {
+        match scene.find_material(name) {
+            Some(ind) => Ok(ind),
+            None if self.materials.contains_key(name) => {
+                Ok(scene.add_material(name, MaterialData::default()))
+            }
+            None => Err(SceneFileError::UnknownMaterial(
+                name.to_string(),
+                file.to_path_buf(),
+            )),
+        }
+    }
+
+    // every mesh of the file becomes a prototype shared by all the copies
+    fn build_instances(
+        &self,
+        geo: &GeometryDesc,
+        file: &Path,
+        scene: &mut Scene,
+    ) -> Result<(), SceneFileError> {
+        let meshes = scene
+            .load_obj_meshes(file)
+            .map_err(|e| SceneFileError::Obj(file.to_path_buf(), e))?;
+        let geo_mat = match &geo.material {
+            Some(name) => Some(self.scene_material(name, file, scene)?),
+            None => None,
+        };
+        let prototypes: Vec<u32> = meshes
+            .into_iter()
+            .map(|(mesh, mat_ind)| scene.add_prototype(mesh, geo_mat.unwrap_or(mat_ind)))
+            .collect();
+
+        for inst in geo.instances.iter() {
+            let mat_ind = match &inst.material {
+                Some(name) => Some(self.scene_material(name, file, scene)?),
+                None => None,
+            };
+            let pose = inst.pose().to_transform();
+            let [nx, ny, nz] = inst.count;
+            for k in 0..nz {
+                for j in 0..ny {
+                    for i in 0..nx {
+                        let cell = [i, j, k].map(|c| c as f32);
+                        let offset = [0, 1, 2].map(|a| cell[a] * inst.spacing[a]);
+                        let xf = Transform::translate(to_vector(offset)) * pose;
+                        for &prototype in prototypes.iter() {
+                            scene.add_instance(prototype, xf, mat_ind);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), SceneFileError> {
+        let cam = &self.camera;
+        if cam.width == 0 || cam.height == 0 {
+            return Err(SceneFileError::Invalid(format!(
+                "[camera]: resolution must be positive, got {}x{}",
+                cam.width, cam.height
+            )));
+        }
+        match cam.kind {
+            CameraType::Perspective if !(cam.fov > 0.0 && cam.fov < 180.0) => {
+                return Err(SceneFileError::Invalid(format!(
+                    "[camera]: fov must be in (0, 180) degrees, got {}",
+                    cam.fov
+                )));
+            }
+            CameraType::Fisheye if !(cam.fov > 0.0 && cam.fov <= 360.0) => {
+                return Err(SceneFileError::Invalid(format!(
+                    "[camera]: fisheye fov must be in (0, 360] degrees, got {}",
+                    cam.fov
+                )));
+            }
+            CameraType::Orthographic
+                if !cam.view_height.is_some_and(|h| h > 0.0 && h.is_finite()) =>
+            {
+                return Err(SceneFileError::Invalid(
+                    "[camera]: orthographic cameras need a positive view_height".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if cam.eye == cam.at {
+            return Err(SceneFileError::Invalid(
+                "[camera]: eye and at must be different points".to_string(),
+            ));
+        }
+        if !(cam.aperture >= 0.0 && cam.aperture.is_finite()) {
+            return Err(SceneFileError::Invalid(format!(
+                "[camera]: aperture must not be negative, got {}",
+                cam.aperture
+            )));
+        }
+        if cam
+            .focus_distance
+            .is_some_and(|d| !(d > 0.0 && d.is_finite()))
+        {
+            return Err(SceneFileError::Invalid(
+                "[camera]: focus_distance must be positive".to_string(),
+            ));
+        }
+        if cam.aperture_blades == 1 || cam.aperture_blades == 2 {
+            return Err(SceneFileError::Invalid(format!(
+                "[camera]: aperture_blades must be 0 (round) or at least 3, got {}",
+                cam.aperture_blades
+            )));
+        }
+        let [open, close] = cam.shutter;
+        if !(open.is_finite() && close.is_finite() && open <= close) {
+            return Err(SceneFileError::Invalid(format!(
+                "[camera]: shutter must be an interval [open, close], got {:?}",
+                cam.shutter
+            )));
+        }
+        for geo in self.geometry.iter() {
+            if !geo.instances.is_empty() && geo.motion.is_some() {
+                return Err(SceneFileError::Invalid(format!(
+                    "[[geometry]] {}: instanced geometry cannot move",
+                    geo.file.display()
+                )));
+            }
+            for inst in geo.instances.iter() {
+                if inst.scale.iter().any(|&s| !(s.is_finite() && s != 0.0)) || inst.axis == [0.0; 3]
+                {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[[geometry.instances]] of {}: scale must be finite and not zero, \
+                         and the rotation axis not zero",
+                        geo.file.display()
+                    )));
+                }
+                if inst.count.contains(&0) {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[[geometry.instances]] of {}: count must be positive",
+                        geo.file.display()
+                    )));
+                }
+            }
+            let Some(motion) = &geo.motion else {
+                continue;
+            };
+            let poses = [motion.start, motion.end];
+            if poses
+                .iter()
+                .any(|p| p.scale.iter().any(|&s| !(s.is_finite() && s != 0.0)))
+            {
+                return Err(SceneFileError::Invalid(format!(
+                    "[geometry.motion] of {}: scale must be finite and not zero",
+                    geo.file.display()
+                )));
+            }
+            if poses.iter().any(|p| p.axis == [0.0; 3]) {
+                return Err(SceneFileError::Invalid(format!(
+                    "[geometry.motion] of {}: rotation axis must not be zero",
+                    geo.file.display()
+                )));
+            }
+        }
+        if self.render.spp == 0 || self.render.spp_stride == 0 {
+            return Err(SceneFileError::Invalid(
+                "[render]: spp and spp_stride must be positive".to_string(),
+            ));
+        }
+        if let Some(adaptive) = self.render.adaptive {
+            if !(adaptive.threshold > 0.0 && adaptive.threshold.is_finite()) {
+                return Err(SceneFileError::Invalid(format!(
+                    "[render.adaptive]: threshold must be positive, got {}",
+                    adaptive.threshold
+                )));
+            }
+            if adaptive.min_spp < 2 {
+                return Err(SceneFileError::Invalid(
+                    "[render.adaptive]: min_spp must be at least 2".to_string(),
+                ));
+            }
+        }
+        let radius = self.render.filter.radius();
+        if !(radius > 0.0 && radius <= 8.0) {
+            return Err(SceneFileError::Invalid(format!(
+                "[render.filter]: radius must be in (0, 8] pixels, got {}",
+                radius
+            )));
+        }
+        if let FilterDesc::Gaussian { sigma, .. } = self.render.filter {
+            if !(sigma > 0.0 && sigma.is_finite()) {
+                return Err(SceneFileError::Invalid(format!(
+                    "[render.filter]: sigma must be positive, got {}",
+                    sigma
+                )));
+            }
+        }
+        if let ShaderDesc::PathTracer { continue_prob, .. } = self.shader {
+            if !(continue_prob > 0.0 && continue_prob <= 1.0) {
+                return Err(SceneFileError::Invalid(format!(
+                    "[shader]: continue_prob must be in (0, 1], got {}",
+                    continue_prob
+                )));
+            }
+        }
+        for (name, mat) in &self.materials {
+            if mat.ior.is_some_and(|ior| !(ior > 0.0 && ior.is_finite())) {
+                return Err(SceneFileError::Invalid(format!(
+                    "[materials.{}]: ior must be positive",
+                    name
+                )));
+            }
+            for (key, v) in [("roughness", mat.roughness), ("metallic", mat.metallic)] {
+                if v.is_some_and(|v| !(0.0..=1.0).contains(&v)) {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[materials.{}]: {} must be in [0, 1]",
+                        name, key
+                    )));
+                }
+            }
+        }
+        for (i, shape) in self.shapes.iter().enumerate() {
+            if let Err(e) = shape.shape.check() {
+                return Err(SceneFileError::Invalid(format!(
+                    "[[shapes]] #{}: {}",
+                    i + 1,
+                    e
+                )));
+            }
+        }
+        let environments = self
+            .lights
+            .iter()
+            .filter(|l| matches!(l, LightDesc::Environment { .. }))
+            .count();
+        if environments > 1 {
+            return Err(SceneFileError::Invalid(
+                "[[lights]]: a scene has at most one environment light".to_string(),
+            ));
+        }
+        for (i, light) in self.lights.iter().enumerate() {
+            let err = match *light {
+                LightDesc::Spot {
+                    position,
+                    at,
+                    cone_angle,
+                    cone_delta,
+                    ..
+                } => {
+                    if position == at {
+                        Some("spot light position and at must differ")
+                    } else if !(cone_angle > 0.0 && cone_angle <= 180.0) {
+                        Some("spot light cone_angle must be in (0, 180]")
+                    } else if !(0.0..=cone_angle).contains(&cone_delta) {
+                        Some("spot light cone_delta must be in [0, cone_angle]")
+                    } else {
+                        None
+                    }
+                }
+                LightDesc::Directional {
+                    direction,
+                    angular_diameter,
+                    ..
+                } => {
+                    if direction == [0.0; 3] {
+                        Some("directional light direction must not be zero")
+                    } else if !(0.0..180.0).contains(&angular_diameter) {
+                        Some("directional light angular_diameter must be in [0, 180)")
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            if let Some(e) = err {
+                return Err(SceneFileError::Invalid(format!(
+                    "[[lights]] #{}: {}",
+                    i + 1,
+                    e
+                )));
+            }
+            if let LightDesc::Environment { scale, axis, .. } = light {
+                if !(scale.is_finite() && *scale >= 0.0) || *axis == [0.0; 3] {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[[lights]] #{}: environment scale must be non-negative and the \
+                         rotation axis not zero",
+                        i + 1
+                    )));
+                }
+            }
+            if let LightDesc::Area {
+                shape: Some(shape),
+                vertices,
+                normal,
+                ..
+            } = light
+            {
+                let err = match shape {
+                    _ if vertices.is_some() || normal.is_some() => {
+                        Some("give either a shape or vertices".to_string())
+                    }
+                    ShapeDesc::Plane { .. } => Some("planes cannot emit".to_string()),
+                    _ => shape.check().err(),
+                };
+                if let Some(e) = err {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[[lights]] #{}: {}",
+                        i + 1,
+                        e
+                    )));
+                }
+            } else if let LightDesc::Area { vertices, .. } = light {
+                let Some(vertices) = vertices else {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[[lights]] #{}: area lights need vertices or a shape",
+                        i + 1
+                    )));
+                };
+                let [a, b, c] = vertices.map(|v| Point::new(v[0], v[1], v[2]));
+                let e1: Vector = (b - a).into();
+                let e2: Vector = (c - a).into();
+                if e1.cross(e2).norm() == 0.0 {
+                    return Err(SceneFileError::Invalid(format!(
+                        "[[lights]] #{}: area light triangle is degenerate",
+                        i + 1
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CameraDesc {
+    pub fn build(&self) -> AnyCamera {
+        let (frame, extent) = (self.frame(), self.extent());
+        let mut camera = match self.kind {
+            CameraType::Perspective => AnyCamera::Perspective(self.build_perspective()),
+            CameraType::Orthographic => AnyCamera::Orthographic(Orthographic::new(
+                frame,
+                extent,
+                self.view_height.unwrap_or(1.0),
+            )),
+            CameraType::Fisheye => {
+                AnyCamera::Fisheye(Fisheye::new(frame, extent, self.fov.to_radians()))
+            }
+            CameraType::Equirectangular => {
+                AnyCamera::Equirectangular(Equirectangular::new(frame, extent))
+            }
+        };
+        camera.set_shutter(Shutter {
+            open: self.shutter[0],
+            close: self.shutter[1],
+        });
+        camera
+    }
+
+    fn build_perspective(&self) -> Perspective {
+        Perspective::new(
+            self.frame(),
+            self.extent(),
+            self.fov.to_radians(),
+            self.fov_axis,
+            self.lens(),
+        )
+    }
+
+    // `[camera]` table reproducing this camera when pasted into a scene file
+    pub fn to_toml(&self) -> String {
+        #[derive(Serialize)]
+        struct Snippet<'a> {
+            camera: &'a CameraDesc,
+        }
+        toml::to_string(&Snippet { camera: self }).expect("camera serializes to toml")
+    }
+
+    pub fn frame(&self) -> CameraFrame {
+        CameraFrame::look_at(to_point(self.eye), to_point(self.at), to_vector(self.up))
+    }
+
+    pub fn extent(&self) -> Extent2D {
+        Extent2D {
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn lens(&self) -> ThinLens {
+        let eye = to_point(self.eye);
+        let at = to_point(self.at);
+        let to_at: Vector = (at - eye).into();
+        ThinLens {
+            radius: self.aperture,
+            focal_distance: self.focus_distance.unwrap_or(to_at.norm()),
+            blades: self.aperture_blades,
+            rotation: self.aperture_rotation.to_radians(),
+        }
+    }
+}
+
+impl TransformDesc {
+    fn to_transform(self) -> Transform {
+        let [sx, sy, sz] = self.scale;
+        Transform::translate(to_vector(self.translate))
+            * Transform::rotate(self.rotate.to_radians(), to_vector(self.axis))
+            * Transform::scale(sx, sy, sz)
+    }
+
+    fn is_identity(&self) -> bool {
+        self.scale == [1.0; 3] && self.rotate == 0.0 && self.translate == [0.0; 3]
+    }
+}
+
+impl InstanceDesc {
+    fn pose(&self) -> TransformDesc {
+        TransformDesc {
+            scale: self.scale,
+            rotate: self.rotate,
+            axis: self.axis,
+            translate: self.translate,
+        }
+    }
+}
+
+impl MotionDesc {
+    // transform carrying the file's geometry from the start pose to the end one, None
+    // when it only deforms
+    fn rigid_motion(&self) -> Option<AnimatedTransform> {
+        if self.start.is_identity() && self.end.is_identity() {
+            return None;
+        }
+        let [start, end] = [self.start, self.end];
+        Some(AnimatedTransform {
+            scales: [to_vector(start.scale), to_vector(end.scale)],
+            rotations: [
+                (to_vector(start.axis), start.rotate.to_radians()),
+                (to_vector(end.axis), end.rotate.to_radians()),
+            ],
+            translations: [to_vector(start.translate), to_vector(end.translate)],
+            pivot: to_vector(self.pivot),
+        })
+    }
+}
+
+// vertices of the meshes of `path` at the end of a deforming motion, which must match
+// the meshes loaded from the geometry's own file one by one
+fn load_end_meshes(path: &Path, meshes: &[(Mesh, u16)]) -> Result<Vec<MotionKey>, SceneFileError> {
+    let (models, _) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
+        .map_err(|e| SceneFileError::Obj(path.to_path_buf(), e))?;
+    let mismatch = || {
+        SceneFileError::Invalid(format!(
+            "[geometry.motion]: {} does not have the same meshes and vertices as the geometry",
+            path.display()
+        ))
+    };
+    if models.len() != meshes.len() {
+        return Err(mismatch());
+    }
+    models
+        .iter()
+        .zip(meshes.iter())
+        .map(|(model, (mesh, _))| {
+            let positions: Box<[Point]> = model
+                .mesh
+                .positions
+                .chunks_exact(3)
+                .map(|a| Point::new(a[0], a[1], a[2]))
+                .collect();
+            let normals: Box<[Vector]> = model
+                .mesh
+                .normals
+                .chunks_exact(3)
+                .map(|a| Vector::new(a[0], a[1], a[2]))
+                .collect();
+            if positions.len() != mesh.positions.len() || normals.len() != mesh.normals.len() {
+                return Err(mismatch());
+            }
+            Ok(MotionKey { positions, normals })
+        })
+        .collect()
+}
+
+impl FilterDesc {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            FilterDesc::Box { radius }
+            | FilterDesc::Tent { radius }
+            | FilterDesc::Gaussian { radius, .. }
+            | FilterDesc::Mitchell { radius, .. }
+            | FilterDesc::Lanczos { radius } => radius,
+        }
+    }
+
+    pub fn build(&self) -> AnyFilter {
+        match *self {
+            FilterDesc::Box { radius } => AnyFilter::Box(BoxFilter { radius }),
+            FilterDesc::Tent { radius } => AnyFilter::Tent(TentFilter { radius }),
+            FilterDesc::Gaussian { radius, sigma } => {
+                AnyFilter::Gaussian(GaussianFilter { radius, sigma })
+            }
+            FilterDesc::Mitchell { radius, b, c } => {
+                AnyFilter::Mitchell(MitchellFilter { radius, b, c })
+            }
+            FilterDesc::Lanczos { radius } => AnyFilter::Lanczos(LanczosFilter { radius }),
+        }
+    }
+}
+
+impl ShaderDesc {
+    pub const NAMES: [&'static str; 4] = ["ambient", "whitted", "distributed", "path_tracer"];
+
+    // another shader type, keeping the parameters the two have in common
+    pub fn with_type(&self, name: &str) -> Option<ShaderDesc> {
+        let (background, bias, reflection_depth) = match *self {
+            ShaderDesc::Ambient { background } => (background, default_bias(), default_depth()),
+            ShaderDesc::Whitted {
+                background,
+                bias,
+                reflection_depth,
+            }
+            | ShaderDesc::Distributed {
+                background,
+                bias,
+                reflection_depth,
+            }
+            | ShaderDesc::PathTracer {
+                background,
+                bias,
+                reflection_depth,
+                ..
+            } => (background, bias, reflection_depth),
+        };
+        let continue_prob = match *self {
+            ShaderDesc::PathTracer { continue_prob, .. } => continue_prob,
+            _ => default_continue_prob(),
+        };
+        match name {
+            "ambient" => Some(ShaderDesc::Ambient { background }),
+            "whitted" => Some(ShaderDesc::Whitted {
+                background,
+                bias,
+                reflection_depth,
+            }),
+            "distributed" => Some(ShaderDesc::Distributed {
+                background,
+                bias,
+                reflection_depth,
+            }),
+            "path_tracer" => Some(ShaderDesc::PathTracer {
+                background,
+                bias,
+                reflection_depth,
+                continue_prob,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn build(&self) -> AnyShader {
+        match *self {
+            ShaderDesc::Ambient { background } => AnyShader::Ambient(AmbientShader {
+                background: background.into(),
+            }),
+            ShaderDesc::Whitted {
+                background,
+                bias,
+                reflection_depth,
+            } => AnyShader::Whitted(WhittedShader {
+                background: background.into(),
+                shadow_bias: bias,
+                reflection_depth,
+            }),
+            ShaderDesc::Distributed {
+                background,
+                bias,
+                reflection_depth,
+            } => AnyShader::Distributed(DistributedShader {
+                background: background.into(),
+                shadow_bias: bias,
+                reflection_depth,
+            }),
+            ShaderDesc::PathTracer {
+                background,
+                bias,
+                reflection_depth,
+                continue_prob,
+            } => AnyShader::PathTracer(PathTracerShader {
+                background: background.into(),
+                collision_bias: bias,
+                reflection_depth,
+                continue_prob,
+            }),
+        }
+    }
+}
+
+impl MaterialDesc {
+    fn apply(
+        &self,
+        name: &str,
+        mat: &mut MaterialData,
+        scene: &mut Scene,
+        dir: &Path,
+    ) -> Result<(), SceneFileError> {
+        if let Some(ka) = self.ka {
+            mat.ka = ka.into();
+        }
+        if let Some(kd) = self.kd {
+            mat.kd = kd.into();
+        }
+        if let Some(ks) = self.ks {
+            mat.ks = ks.into();
+        }
+        if let Some(kt) = self.kt {
+            mat.kt = kt.into();
+        }
+        if let Some(le) = self.le {
+            mat.le = Some(le.into());
+        }
+        if let Some(ns) = self.ns {
+            mat.ns = ns;
+        }
+        if let Some(ior) = self.ior {
+            mat.ior = ior;
+        }
+        if let Some(roughness) = self.roughness {
+            mat.roughness = Some(roughness);
+        }
+        if let Some(metallic) = self.metallic {
+            mat.metallic = Some(metallic);
+        }
+        if let Some(distribution) = self.distribution {
+            mat.distribution = distribution;
+        }
+        if let Some(conductor) = self.conductor {
+            mat.conductor = Some(conductor);
+        }
+
+        let mut load = |file: &Option<PathBuf>| -> Result<Option<u32>, SceneFileError> {
+            match file {
+                Some(file) => {
+                    let file = dir.join(file);
+                    match scene.load_texture(&file, self.wrap, self.filter) {
+                        Some(ind) => Ok(Some(ind)),
+                        None => Err(SceneFileError::Texture(name.to_string(), file)),
+                    }
+                }
+                None => Ok(None),
+            }
+        };
+        if let Some(t) = load(&self.map_ka)? {
+            mat.ka_map = Some(t);
+        }
+        if let Some(t) = load(&self.map_kd)? {
+            mat.kd_map = Some(t);
+        }
+        if let Some(t) = load(&self.map_ks)? {
+            mat.ks_map = Some(t);
+        }
+        Ok(())
+    }
+}
+
+impl LightDesc {
+    pub fn build(&self, dir: &Path) -> Result<Light, SceneFileError> {
+        let light = match *self {
+            LightDesc::Ambient { color } => Light::Ambient(AmbientLight {
+                color: color.into(),
+            }),
+            LightDesc::Point { color, position } => Light::Point(PointLight {
+                color: color.into(),
+                position: to_point(position),
+            }),
+            LightDesc::Area {
+                shape: Some(shape),
+                power,
+                ..
+            } => Light::Area(AreaLight::new(power.into(), shape.build())),
+            LightDesc::Area {
+                power,
+                vertices,
+                normal,
+                ..
+            } => {
+                let [a, b, c] = vertices.unwrap_or_default().map(to_point);
+                let normal = match normal {
+                    Some(n) => to_vector(n),
+                    None => {
+                        let e1: Vector = (b - a).into();
+                        e1.cross((c - a).into())
+                    }
+                };
+                let mut normal = normal;
+                normal.normalize();
+                let tri = Triangle::new(a, b, c, normal);
+                Light::Area(AreaLight::new(power.into(), AnyShape::Triangle(tri)))
+            }
+            LightDesc::Mesh { ref file, power } => {
+                let path = dir.join(file);
+                let triangles = load_light_triangles(&path)?;
+                if triangles.iter().all(|t| t.area() == 0.0) {
+                    return Err(SceneFileError::Invalid(format!(
+                        "mesh light {} has no triangles with area",
+                        path.display()
+                    )));
+                }
+                Light::Mesh(MeshLight::new(power.into(), triangles))
+            }
+            LightDesc::Environment {
+                ref file,
+                scale,
+                rotate,
+                axis,
+            } => {
+                let path = dir.join(file);
+                let image = load_image(&path).map_err(|e| SceneFileError::Io(path, e))?;
+                let rotation = Transform::rotate(rotate.to_radians(), to_vector(axis));
+                Light::Environment(EnvironmentLight::new(image, scale, rotation))
+            }
+            LightDesc::Spot {
+                color,
+                position,
+                at,
+                cone_angle,
+                cone_delta,
+            } => {
+                let position = to_point(position);
+                Light::Spot(SpotLight::new(
+                    color.into(),
+                    position,
+                    position.vec2point(to_point(at)),
+                    cone_angle.to_radians(),
+                    cone_delta.to_radians(),
+                ))
+            }
+            LightDesc::Directional {
+                color,
+                direction,
+                angular_diameter,
+            } => Light::Directional(DirectionalLight::new(
+                color.into(),
+                to_vector(direction),
+                angular_diameter.to_radians(),
+            )),
+        };
+        Ok(light)
+    }
+}
+
+// triangles of every mesh in an OBJ file, its materials are ignored
+fn load_light_triangles(path: &Path) -> Result<Vec<Triangle>, SceneFileError> {
+    let (models, _) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
+        .map_err(|e| SceneFileError::Obj(path.to_path_buf(), e))?;
+    let mut triangles = Vec::new();
+    for model in models {
+        let obj_mesh = model.mesh;
+        let mesh = Mesh::new(
+            obj_mesh
+                .positions
+                .chunks_exact(3)
+                .map(|a| Point::new(a[0], a[1], a[2]))
+                .collect(),
+            obj_mesh
+                .normals
+                .chunks_exact(3)
+                .map(|a| Vector::new(a[0], a[1], a[2]))
+                .collect(),
+            Vec::new(),
+            obj_mesh.indices,
+            obj_mesh.normal_indices,
+            Vec::new(),
+        );
+        triangles.extend((0..mesh.face_count()).map(|i| face_triangle(&mesh.face(i))));
+    }
+    Ok(triangles)
+}
+
+impl ShapeDesc {
+    pub fn build(&self) -> AnyShape {
+        match *self {
+            ShapeDesc::Sphere { center, radius } => {
+                AnyShape::Sphere(Sphere::new(to_point(center), radius))
+            }
+            ShapeDesc::Plane { point, normal } => {
+                AnyShape::Plane(Plane::new(to_point(point), to_vector(normal)))
+            }
+            ShapeDesc::Disk {
+                center,
+                normal,
+                radius,
+            } => AnyShape::Disk(Disk::new(to_point(center), to_vector(normal), radius)),
+            ShapeDesc::Quad { corner, edges } => AnyShape::Quad(Quad::new(
+                to_point(corner),
+                to_vector(edges[0]),
+                to_vector(edges[1]),
+            )),
+            ShapeDesc::Cylinder { base, axis, radius } => {
+                AnyShape::Cylinder(Cylinder::new(to_point(base), to_vector(axis), radius))
+            }
+            ShapeDesc::Cone { base, axis, radius } => {
+                AnyShape::Cone(Cone::new(to_point(base), to_vector(axis), radius))
+            }
+        }
+    }
+
+    // what makes the shape degenerate, if anything
+    fn check(&self) -> Result<(), String> {
+        let positive = |v: f32| v > 0.0 && v.is_finite();
+        match *self {
+            ShapeDesc::Sphere { radius, .. } if !positive(radius) => {
+                Err("sphere radius must be positive".to_string())
+            }
+            ShapeDesc::Plane { normal, .. } if normal == [0.0; 3] => {
+                Err("plane normal must not be zero".to_string())
+            }
+            ShapeDesc::Disk { normal, radius, .. } if normal == [0.0; 3] || !positive(radius) => {
+                Err("disk needs a nonzero normal and a positive radius".to_string())
+            }
+            ShapeDesc::Quad { edges, .. }
+                if to_vector(edges[0]).cross(to_vector(edges[1])).norm() == 0.0 =>
+            {
+                Err("quad edges must not be parallel".to_string())
+            }
+            ShapeDesc::Cylinder { axis, radius, .. } | ShapeDesc::Cone { axis, radius, .. }
+                if axis == [0.0; 3] || !positive(radius) =>
+            {
+                Err("cylinders and cones need a nonzero axis and a positive radius".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn to_point(a: [f32; 3]) -> Point {
+    Point::new(a[0], a[1], a[2])
+}
+
+fn to_vector(a: [f32; 3]) -> Vector {
+    Vector::new(a[0], a[1], a[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        camera::{Camera, CameraSample},
+        lights::{
+            light_sampler::{AnyLightSampler, LightSamplerType},
+            Light,
+        },
+        primitives::AnyShape,
+        rays::ray::Ray,
+        shaders::AnyShader,
+        utils::{
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::{CameraDesc, SceneFile, SceneFileError};
+
+    #[test]
+    fn scene_file_cornell_box() {
+        let loaded = SceneFile::load(Path::new("./models/cornell_box_VI.toml")).unwrap();
+        assert!(!loaded.scene.prims.is_empty());
+        // the ceiling panel's two triangles are a single light
+        assert_eq!(loaded.scene.lights.len(), 1);
+        assert!(matches!(loaded.scene.lights[0], Light::Mesh(_)));
+        assert!(matches!(loaded.shader, AnyShader::PathTracer(_)));
+        assert_eq!(loaded.render.spp, 64);
+
+        // Ni and Tf from the MTL make the sphere glass
+        let loaded = SceneFile::load(Path::new("./models/cornell_box_glass.toml")).unwrap();
+        let glass = loaded.scene.find_material("glass").unwrap();
+        let glass = loaded.scene.materials_data[glass as usize];
+        assert_eq!(glass.ior, 1.5);
+        assert!(!glass.kt.is_zero());
+
+        // the viewer's camera snippet parses back into the same camera
+        let desc = SceneFile::read(Path::new("./models/cornell_box_dof.toml")).unwrap();
+        let snippet = desc.camera.to_toml();
+        assert!(snippet.starts_with("[camera]"));
+        let table: toml::Table = toml::from_str(&snippet).unwrap();
+        let camera: CameraDesc = table["camera"].clone().try_into().unwrap();
+        assert_eq!(camera.eye, desc.camera.eye);
+        assert_eq!(camera.focus_distance, desc.camera.focus_distance);
+        assert_eq!(camera.aperture_blades, 6);
+
+        // the thrown sphere crosses a pixel during the middle of the shutter interval
+        let mut desc = SceneFile::read(Path::new("./models/cornell_box_motion.toml")).unwrap();
+        desc.camera.width = 400;
+        desc.camera.height = 400;
+        let mut loaded = desc.build(Path::new("./models")).unwrap();
+        loaded.scene.build_accel();
+        let rubber = loaded.scene.find_material("rubber").unwrap();
+        let rubber = loaded.scene.materials_data[rubber as usize].kd;
+        let hits_rubber = |time: f32| {
+            let sample = CameraSample {
+                p_film: [0.5, 0.5],
+                p_lens: [0.5, 0.5],
+                time,
+            };
+            let ray = loaded.camera.generate_ray(163, 245, &sample).unwrap();
+            loaded
+                .scene
+                .trace(&ray)
+                .is_some_and(|t| t.mat_data.kd.r == rubber.r && t.mat_data.kd.g == rubber.g)
+        };
+        assert!(!hits_rubber(0.0));
+        assert!(hits_rubber(0.5));
+
+        // one prototype shared by a grid of copies and a single larger one
+        let loaded = SceneFile::load(Path::new("./models/cornell_box_instances.toml")).unwrap();
+        assert_eq!(loaded.scene.prototypes.len(), 1);
+        assert_eq!(loaded.scene.instances.len(), 122);
+        let rubber = loaded.scene.find_material("rubber");
+        assert_eq!(loaded.scene.instances[0].mat_ind, rubber);
+        assert_eq!(loaded.scene.instances[121].mat_ind, None);
+
+        // analytic shapes next to the meshes and a spherical lamp
+        let loaded = SceneFile::load(Path::new("./models/cornell_box_shapes.toml")).unwrap();
+        assert_eq!(loaded.scene.shapes.len(), 4);
+        assert!(matches!(loaded.scene.shapes[0].0, AnyShape::Sphere(_)));
+        let Some(Light::Area(lamp)) = loaded.scene.lights.last() else {
+            panic!("the last light is not an area light");
+        };
+        assert!(matches!(lamp.shape, AnyShape::Sphere(_)));
+
+        // rays that leave the outdoor scene see the sky, deep blue at the zenith
+        let mut loaded = SceneFile::load(Path::new("./models/environment_shapes.toml")).unwrap();
+        loaded.scene.build_accel();
+        assert_eq!(loaded.scene.environment, Some(0));
+        let up = Ray::new(Point::new(0.0, 3.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        assert!(loaded.scene.trace(&up).is_none());
+        let sky = loaded.scene.escaped_radiance(up.direction, RGB::default());
+        assert!(sky.b > sky.r && sky.r > 0.0, "{:?}", sky);
+
+        // the sun's disc shows in the sky, the spot light is a delta light
+        let loaded = SceneFile::load(Path::new("./models/shapes_sun.toml")).unwrap();
+        let Some(Light::Directional(sun)) = loaded.scene.lights.last() else {
+            panic!("the last light is not a directional light");
+        };
+        let to_sun = -1.0 * sun.direction;
+        assert_eq!(loaded.scene.infinite_lights, vec![0]);
+        let sky = RGB::new(0.35, 0.45, 0.6);
+        assert!(loaded.scene.escaped_radiance(to_sun, sky).r > 1000.0);
+        assert_eq!(loaded.scene.escaped_radiance(up.direction, sky).r, sky.r);
+        let loaded = SceneFile::load(Path::new("./models/cornell_box_spot.toml")).unwrap();
+        let Some(Light::Spot(spot)) = loaded.scene.lights.last() else {
+            panic!("the last light is not a spot light");
+        };
+        assert!((spot.cos_total_width - 20f32.to_radians().cos()).abs() < 1e-6);
+        assert!(loaded.scene.infinite_lights.is_empty());
+
+        // the ring lamp is one light, hit through its own triangles
+        let mut loaded = SceneFile::load(Path::new("./models/cornell_box_ring_lamp.toml")).unwrap();
+        let Some(Light::Mesh(ring)) = loaded.scene.lights.last() else {
+            panic!("the last light is not a mesh light");
+        };
+        assert_eq!(ring.triangles.len(), 24);
+        assert!(!ring.from_mesh);
+        let ring_power = ring.power;
+        loaded.scene.build_accel();
+        let up = Ray::new(
+            Point::new(278.0 - 60.0, 400.0, 285.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        let tdata = loaded.scene.trace(&up).unwrap();
+        assert_eq!(tdata.light, Some(0));
+        let le = tdata.mat_data.le.unwrap();
+        let Light::Mesh(ring) = &loaded.scene.lights[0] else {
+            unreachable!()
+        };
+        let expected = ring_power.r / (std::f32::consts::PI * ring.area);
+        assert!((le.r - expected).abs() < 1e-3 * expected);
+        // nothing shines up through the ring's hole
+        let hole = Ray::new(Point::new(278.0, 400.0, 280.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(loaded.scene.trace(&hole).unwrap().light, None);
+
+        // lamps along the back wall picked by the light BVH
+        let mut loaded = SceneFile::load(Path::new("./models/cornell_box_lamps.toml")).unwrap();
+        assert_eq!(loaded.render.light_sampler, LightSamplerType::Bvh);
+        loaded.scene.build_accel();
+        let AnyLightSampler::Bvh(bvh) = &loaded.scene.light_sampler else {
+            panic!("the scene does not use a light BVH");
+        };
+        // every light except the ambient one is in the tree
+        let in_bvh = bvh.bit_trails.iter().filter(|t| t.is_some()).count();
+        assert_eq!(in_bvh, loaded.scene.lights.len() - 1);
+    }
+
+    #[test]
+    fn scene_file_errors() {
+        let path = Path::new("test.toml");
+        let err = SceneFile::parse("[camera]\neye = [0, 0, 0]\n", path).unwrap_err();
+        assert!(matches!(err, SceneFileError::Parse(..)));
+
+        let src = r#"
+            [camera]
+            eye = [0, 0, 0]
+            at = [0, 0, 1]
+            fov = 60
+            width = 10
+            height = 10
+            [shader]
+            type = "path_tracer"
+            continue_prob = 2.0
+        "#;
+        let err = SceneFile::parse(src, path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("continue_prob"));
+
+        let err = SceneFile::parse(&src.replace("path_tracer", "photon_mapper"), path)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("photon_mapper"), "{}", err);
+
+        let err = SceneFile::parse(&src.replace("fov = 60", "fov = 60\nfocus = 3"), path)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("focus"), "{}", err);
+
+        let err = SceneFile::parse(&src.replace("fov = 60", "fov = 60\nshutter = [1, 0]"), path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("shutter"));
+
+        let plane_light = "[[lights]]\ntype = \"area\"\npower = [1, 1, 1]\n\
+                           shape = { type = \"plane\", point = [0, 0, 0], normal = [0, 1, 0] }\n";
+        let valid = src.replace("continue_prob = 2.0", "continue_prob = 0.5");
+        let err = SceneFile::parse(&format!("{}{}", valid, plane_light), path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("planes cannot emit"), "{}", err);
+
+        let lamp = "[[lights]]\ntype = \"mesh\"\nfile = \"missing.obj\"\npower = [1, 1, 1]\n";
+        let err = SceneFile::parse(&format!("{}{}", valid, lamp), path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(matches!(err, SceneFileError::Obj(..)), "{}", err);
+
+        let sky = "[[lights]]\ntype = \"environment\"\nfile = \"missing.hdr\"\n";
+        let err = SceneFile::parse(&format!("{}{}", valid, sky), path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(matches!(err, SceneFileError::Io(..)), "{}", err);
+        let err = SceneFile::parse(&format!("{}{}{}", valid, sky, sky), path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("at most one environment"),
+            "{}",
+            err
+        );
+
+        let spot = "[[lights]]\ntype = \"spot\"\ncolor = [1, 1, 1]\nposition = [0, 1, 0]\n\
+                    at = [0, 0, 0]\ncone_angle = 10\ncone_delta = 20\n";
+        let err = SceneFile::parse(&format!("{}{}", valid, spot), path)
+            .unwrap()
+            .build(Path::new(""))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cone_delta"), "{}", err);
+
+        // mesh lights only follow motions that keep their faces' share of the area
+        let lamp = "[[geometry]]\nfile = \"glass_sphere.obj\"\nmaterial = \"lamp\"\n\
+                    [geometry.motion]\nend = { scale = [1, 2, 1] }\n\
+                    [materials.lamp]\nle = [1, 1, 1]\n";
+        let err = SceneFile::parse(&format!("{}{}", valid, lamp), path)
+            .unwrap()
+            .build(Path::new("./models"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("emissive meshes"), "{}", err);
+        let even = lamp.replace("[1, 2, 1]", "[2, 2, 2]");
+        assert!(SceneFile::parse(&format!("{}{}", valid, even), path)
+            .unwrap()
+            .build(Path::new("./models"))
+            .is_ok());
+    }
+}
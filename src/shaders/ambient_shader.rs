@@ -1,6 +1,8 @@
 use super::Shader;
 use crate::{
     lights::Light,
+    rays::ray::Ray,
+    samplers::Sampler,
     scene::{Scene, TraceData},
     utils::rgb::RGB,
 };
@@ -11,12 +13,18 @@ pub struct AmbientShader {
 }
 
 impl Shader for AmbientShader {
-    fn shade(&self, scene: &Scene, tdata_opt: &Option<TraceData>) -> RGB {
+    fn shade<S: Sampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        _sampler: &mut S,
+    ) -> RGB {
         let mut color = RGB::new(0.0, 0.0, 0.0);
 
-        // if no intersection, return background
+        // if no intersection, return the environment or background
         if tdata_opt.is_none() {
-            return self.background;
+            return scene.escaped_radiance(ray.direction, self.background);
         }
         let tdata = tdata_opt.unwrap();
 
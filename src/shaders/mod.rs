@@ -1,13 +1,130 @@
 use crate::{
+    bsdfs::SurfaceBsdf,
+    lights::LightSample,
+    rays::{intersection::IntersectionData, ray::Ray},
+    samplers::Sampler,
     scene::{Scene, TraceData},
     utils::rgb::RGB,
 };
 
+use self::{
+    ambient_shader::AmbientShader, distributed_shader::DistributedShader,
+    path_tracer_shader::PathTracerShader, whitted_shader::WhittedShader,
+};
+
 pub mod ambient_shader;
 pub mod distributed_shader;
 pub mod path_tracer_shader;
 pub mod whitted_shader;
 
 pub trait Shader {
-    fn shade(&self, scene: &Scene, tdata_opt: &Option<TraceData>) -> RGB;
+    // random decisions draw their values from the sampler, which the caller has started
+    // on the current pixel sample; `tdata_opt` is what `ray` hit, rays that escape
+    // see the scene's environment light or else the shader's background
+    fn shade<S: Sampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        sampler: &mut S,
+    ) -> RGB;
+}
+
+// shadow ray test between a surface point and a light sample, both ends are pulled in by
+// the bias so neither the surface nor the emitter itself count as occluders; lights
+// behind the surface only reach it through a BSDF that transmits
+pub fn light_visible(
+    scene: &Scene,
+    isect: &IntersectionData,
+    bsdf: &SurfaceBsdf,
+    ls: &LightSample,
+    bias: f32,
+) -> bool {
+    if !bsdf.has_transmission() && ls.wi.dot(isect.geo_normal.face_forward(isect.wo)) <= 0.0 {
+        return false;
+    }
+    let ray = isect.spawn_ray(ls.wi, bias);
+    if ls.dist.is_infinite() {
+        return !scene.test_line_intersect(&ray, ls.dist);
+    }
+    // aimed from the offset origin at the sampled point itself, a ray parallel to wi
+    // would clip the silhouette of a curved emitter it reaches at a grazing angle
+    let to_light = ray.origin.vec2point(isect.point + ls.wi * ls.dist);
+    let dist = to_light.norm();
+    let ray = Ray::new(ray.origin, to_light).with_time(isect.time);
+    !scene.test_line_intersect(&ray, dist - bias)
+}
+
+// shader picked at runtime, e.g. from a scene file
+#[derive(Debug, Clone, Copy)]
+pub enum AnyShader {
+    Ambient(AmbientShader),
+    Whitted(WhittedShader),
+    Distributed(DistributedShader),
+    PathTracer(PathTracerShader),
+}
+
+impl Shader for AnyShader {
+    fn shade<S: Sampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        sampler: &mut S,
+    ) -> RGB {
+        match self {
+            AnyShader::Ambient(s) => s.shade(scene, ray, tdata_opt, sampler),
+            AnyShader::Whitted(s) => s.shade(scene, ray, tdata_opt, sampler),
+            AnyShader::Distributed(s) => s.shade(scene, ray, tdata_opt, sampler),
+            AnyShader::PathTracer(s) => s.shade(scene, ray, tdata_opt, sampler),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        lights::{AreaLight, Light},
+        primitives::{material_data::MaterialData, sphere::Sphere, AnyShape},
+        rays::intersection::IntersectionData,
+        scene::Scene,
+        utils::{
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::light_visible;
+
+    #[test]
+    fn sphere_light_samples_are_visible() {
+        let mut scene = Scene::new();
+        let sphere = AnyShape::Sphere(Sphere::new(Point::new(0.0, 0.5, 0.0), 0.2));
+        scene.add_light(Light::Area(AreaLight::new(RGB::new(1.0, 1.0, 1.0), sphere)));
+        scene.build_accel();
+
+        // a floor point with nothing in between, samples near the silhouette included
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let isect = IntersectionData {
+            point: Point::new(0.3, 0.0, 0.1),
+            geo_normal: up,
+            sh_normal: up,
+            wo: up,
+            ..Default::default()
+        };
+        let bsdf = MaterialData {
+            kd: RGB::new(0.5, 0.5, 0.5),
+            ..Default::default()
+        }
+        .bsdf(&isect);
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..10_000 {
+            let ls = scene.lights[0]
+                .sample_li(isect.point, 0.0, &[rng.gen(), rng.gen()])
+                .unwrap();
+            assert!(light_visible(&scene, &isect, &bsdf, &ls, 1e-3));
+        }
+    }
 }
@@ -1,16 +1,22 @@
-use core::f32;
-
-use rand::{thread_rng, Rng};
-
 use crate::{
-    lights::Light,
-    rays::ray::{self, Ray},
+    bsdfs::SurfaceBsdf,
+    lights::{light_sampler::LightSampler, Light},
+    rays::ray::Ray,
+    samplers::Sampler,
     scene::{Scene, TraceData},
-    utils::{rgb::RGB, vector::Vector},
+    utils::{
+        rgb::RGB,
+        vector::{Point, Vector},
+    },
 };
 
-use super::Shader;
+use super::{light_visible, Shader};
+
+// based on PBRT's 3rd ed. book, sec 14.5
+// next event estimation and BSDF sampling are combined with multiple importance sampling,
+// so emitters found by either strategy are weighted with the power heuristic
 
+#[derive(Debug, Clone, Copy, Default)]
 pub struct PathTracerShader {
     pub background: RGB,
     pub collision_bias: f32,
@@ -18,247 +24,280 @@ pub struct PathTracerShader {
     pub reflection_depth: u16,
 }
 
-impl PathTracerShader {
-    fn diffuse_reflection(&self, scene: &Scene, tdata: &TraceData, depth: u16) -> RGB {
-        let color = RGB::new(0.0, 0.0, 0.0);
-
-        let mut rng = thread_rng();
-        let rnd: [f32; 2] = [rng.gen(), rng.gen()];
-
-        let cos_theta = rnd[1].sqrt();
-        let d_around_z = Vector::new(
-            (2.0 * f32::consts::PI * rnd[0]).cos() * (1.0 - rnd[1]).sqrt(),
-            (2.0 * f32::consts::PI * rnd[0]).sin() * (1.0 - rnd[1]).sqrt(),
-            cos_theta,
-        );
-        let pdf = cos_theta / f32::consts::PI;
-        let (rx, ry) = tdata.isect.geo_normal.coordinate_system();
-
-        let diffuse = Ray::new(
-            tdata.isect.point + tdata.isect.geo_normal * self.collision_bias,
-            d_around_z.rotate(rx, ry, tdata.isect.geo_normal),
-        );
-
-        let ntdata_opt = scene.trace(&diffuse);
-        if let Some(ntdata) = ntdata_opt {
-            if ntdata.mat_data.le.is_some() {
-                return color;
-            }
-        }
-        let rcolor: RGB;
-        if depth > 0 {
-            rcolor = self.shade_impl(scene, &ntdata_opt, depth - 1);
-        } else {
-            rcolor = self.shade_impl(scene, &ntdata_opt, depth);
-        }
-        return (tdata.mat_data.kd * cos_theta * rcolor) / pdf;
-    }
-
-    fn specular_reflection(&self, scene: &Scene, tdata: &TraceData, depth: u16) -> RGB {
-        let gn = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-        let cos = gn.dot(tdata.isect.wo);
-        let ray_dir = 2.0 * cos * gn - tdata.isect.wo;
-
-        let origin = tdata.isect.point + gn * self.collision_bias;
-        let sp_ray = Ray::new(origin, ray_dir);
-
-        let sp_tdata_opt = scene.trace(&sp_ray);
-        let rcolor: RGB;
-        if depth > 0 {
-            rcolor = self.shade_impl(scene, &sp_tdata_opt, depth - 1);
-        } else {
-            rcolor = self.shade_impl(scene, &sp_tdata_opt, depth);
-        }
-        return rcolor * tdata.mat_data.ks;
+pub fn power_heuristic(nf: f32, f_pdf: f32, ng: f32, g_pdf: f32) -> f32 {
+    let f = nf * f_pdf;
+    let g = ng * g_pdf;
+    if f == 0.0 && g == 0.0 {
+        return 0.0;
     }
+    (f * f) / (f * f + g * g)
+}
 
-    fn direct_lighting_smpl(&self, scene: &Scene, tdata: &TraceData) -> RGB {
+impl PathTracerShader {
+    // ambient light reaches every hit, so it is added whole instead of sampled
+    fn ambient_lighting(scene: &Scene, tdata: &TraceData) -> RGB {
         let mut color = RGB::default();
-        let rnd_ind = thread_rng().gen::<usize>() % scene.lights.len();
-
-        match scene.lights[rnd_ind] {
-            Light::Ambient(ambient_light) => {
+        for light in &scene.lights {
+            if let Light::Ambient(ambient_light) = light {
                 color += tdata.mat_data.ka * ambient_light.color;
             }
-            Light::Point(point_light) => {
-                if tdata.mat_data.kd.is_zero() {
-                    return color;
-                }
-                let mut ray_dir: Vector = (point_light.position - tdata.isect.point).into();
-                let light_dist = ray_dir.norm();
-                ray_dir.normalize();
-
-                let mut g_normal = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-                g_normal.normalize();
-
-                if ray_dir.dot(g_normal) < 0.0 {
-                    return color;
-                }
-
-                let ray_o = tdata.isect.point + g_normal * self.collision_bias;
-                let ray: Ray = Ray::new(ray_o, ray_dir);
-                if !scene.test_line_intersect(&ray, light_dist) {
-                    color +=
-                        tdata.mat_data.kd * point_light.color * 0f32.max(g_normal.dot(ray_dir));
-                }
-                //let light_tdata_opt = scene.trace(&ray);
-                //if light_tdata_opt.is_none() || light_tdata_opt.unwrap().isect.depth >= light_dist {
-                //    color +=
-                //        tdata.mat_data.kd * point_light.color * 0f32.max(g_normal.dot(ray_dir));
-                //}
-            }
-            Light::Area(area_light) => {
-                if tdata.mat_data.kd.is_zero() {
-                    return color;
-                }
-
-                let mut rng = thread_rng();
-                let rnd = [rng.gen(), rng.gen()];
-                let (l_int, l_point) = area_light.stochastic_radiance(&rnd);
-
-                let mut l_dir: Vector = (l_point - tdata.isect.point).into();
-                let light_dist = l_dir.norm();
-                l_dir.normalize();
-
-                let cosl = l_dir.dot(tdata.isect.geo_normal.face_forward(l_dir));
-                let cosl_la = l_dir.dot(area_light.tri.normal);
-
-                if cosl > 0. && cosl_la <= 0.0 {
-                    let mut g_normal = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-                    g_normal.normalize();
-
-                    let ray_o = tdata.isect.point + g_normal * self.collision_bias;
-                    let ray: Ray = Ray::new(ray_o, l_dir);
-
-                    if !scene.test_line_intersect(&ray, light_dist - self.collision_bias * 2.0) {
-                        color += tdata.mat_data.kd * l_int * 0f32.max(g_normal.dot(l_dir));
-                    }
-                    //let light_tdata_opt = scene.trace(&ray);
-                    //if light_tdata_opt.is_none()
-                    //    || light_tdata_opt.unwrap().isect.depth >= light_dist - self.collision_bias
-                    //    || light_tdata_opt.unwrap().mat_data.le.is_some()
-                    //{
-                    //    color += tdata.mat_data.kd * l_int * 0f32.max(g_normal.dot(l_dir));
-                    //}
-                }
-            }
         }
-        color * scene.lights.len() as f32
+        color
     }
 
-    fn direct_lighting(&self, scene: &Scene, tdata: &TraceData) -> RGB {
+    // samples one light picked by the scene's light sampler, weighted against
+    // sampling the BSDF
+    fn direct_lighting_smpl<S: Sampler>(
+        &self,
+        scene: &Scene,
+        tdata: &TraceData,
+        bsdf: &SurfaceBsdf,
+        sampler: &mut S,
+    ) -> RGB {
         let mut color = RGB::default();
+        // drawn before any early out so the following dimensions stay in place
+        let u_light = sampler.get_1d();
+        let rnd = sampler.get_2d();
+        let Some((light_ind, select_pdf)) =
+            scene
+                .light_sampler
+                .sample(tdata.isect.point, tdata.isect.sh_normal, u_light)
+        else {
+            return color;
+        };
+        let light = &scene.lights[light_ind as usize];
+
+        // delta lobes never line up with a sampled light direction
+        if bsdf.is_specular() {
+            return color;
+        }
 
-        for light in scene.lights.iter() {
-            match light {
-                Light::Ambient(ambient_light) => {
-                    color += tdata.mat_data.ka * ambient_light.color;
-                }
-                Light::Point(point_light) => {
-                    if tdata.mat_data.kd.is_zero() {
-                        continue;
-                    }
-                    let mut ray_dir: Vector = (point_light.position - tdata.isect.point).into();
-                    let light_dist = ray_dir.norm();
-                    ray_dir.normalize();
-
-                    let mut g_normal = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-                    g_normal.normalize();
-
-                    if ray_dir.dot(g_normal) < 0.0 {
-                        continue;
-                    }
+        let Some(ls) = light.sample_li(tdata.isect.point, tdata.isect.time, &rnd) else {
+            return color;
+        };
+        let wo = tdata.isect.wo;
+        let f = bsdf.eval(wo, ls.wi) * bsdf.abs_cos(ls.wi);
+        if f.is_zero() || !light_visible(scene, &tdata.isect, bsdf, &ls, self.collision_bias) {
+            return color;
+        }
 
-                    let ray_o = tdata.isect.point + g_normal * self.collision_bias;
-                    let ray: Ray = Ray::new(ray_o, ray_dir);
-                    let light_tdata_opt = scene.trace(&ray); // TODO: visibility instead of trace
+        let light_pdf = ls.pdf * select_pdf;
+        color += f * ls.li / light_pdf;
+        if !ls.is_delta {
+            let bsdf_pdf = bsdf.pdf(wo, ls.wi);
+            color = color * power_heuristic(1.0, light_pdf, 1.0, bsdf_pdf);
+        }
+        color
+    }
 
-                    if light_tdata_opt.is_none()
-                        || light_tdata_opt.unwrap().isect.depth >= light_dist
-                    {
-                        color +=
-                            tdata.mat_data.kd * point_light.color * 0f32.max(g_normal.dot(ray_dir));
-                    }
+    fn shade_impl<S: Sampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        sampler: &mut S,
+    ) -> RGB {
+        let mut color = RGB::new(0.0, 0.0, 0.0);
+        let mut beta = RGB::new(1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        let mut tdata_opt = *tdata_opt;
+
+        // emitters seen directly or through a mirror cannot be light sampled
+        let mut specular_bounce = true;
+        let mut bsdf_pdf = 0.0;
+        let mut prev_point = Point::default();
+        let mut prev_normal = Vector::default();
+        let mut depth: u16 = 0;
+
+        loop {
+            // if no intersection, return the environment or background, lights at
+            // infinity being weighted like any other emitter
+            let Some(tdata) = tdata_opt else {
+                if scene.environment.is_none() {
+                    color += beta * self.background;
                 }
-                Light::Area(area_light) => {
-                    if tdata.mat_data.kd.is_zero() {
-                        continue;
+                for &light_ind in &scene.infinite_lights {
+                    let light = &scene.lights[light_ind as usize];
+                    let le = light.le_dir(ray.direction);
+                    if specular_bounce {
+                        color += beta * le;
+                    } else {
+                        let light_pdf = light.pdf_dir(ray.direction)
+                            * scene.light_sampler.pmf(prev_point, prev_normal, light_ind);
+                        color += beta * le * power_heuristic(1.0, bsdf_pdf, 1.0, light_pdf);
                     }
-
-                    let mut rng = thread_rng();
-                    let rnd = [rng.gen(), rng.gen()];
-                    let (l_int, l_point) = area_light.stochastic_radiance(&rnd);
-
-                    let mut l_dir: Vector = (l_point - tdata.isect.point).into();
-                    let light_dist = l_dir.norm();
-                    l_dir.normalize();
-
-                    let cosl = l_dir.dot(tdata.isect.geo_normal.face_forward(l_dir));
-                    let cosl_la = l_dir.dot(area_light.tri.normal);
-
-                    if cosl > 0. && cosl_la <= 0.0 {
-                        let mut g_normal = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-                        g_normal.normalize();
-
-                        let ray_o = tdata.isect.point + g_normal * self.collision_bias;
-                        let ray: Ray = Ray::new(ray_o, l_dir);
-                        let light_tdata_opt = scene.trace(&ray); // TODO: visibility instead of trace
-
-                        if light_tdata_opt.is_none()
-                            || light_tdata_opt.unwrap().isect.depth
-                                >= light_dist - self.collision_bias
-                            || light_tdata_opt.unwrap().mat_data.le.is_some()
-                        {
-                            color += tdata.mat_data.kd * l_int * 0f32.max(g_normal.dot(l_dir));
-                        }
+                }
+                break;
+            };
+
+            if let Some(le) = tdata.mat_data.le {
+                match tdata.light {
+                    Some(light_ind) if !specular_bounce => {
+                        let light_pdf =
+                            scene.lights[light_ind as usize].pdf_li(
+                                prev_point,
+                                tdata.isect.point,
+                                tdata.isect.geo_normal,
+                                tdata.isect.time,
+                            ) * scene.light_sampler.pmf(prev_point, prev_normal, light_ind);
+                        color += beta * le * power_heuristic(1.0, bsdf_pdf, 1.0, light_pdf);
                     }
+                    _ => color += beta * le,
                 }
+                break;
             }
-        }
-        color
-    }
 
-    fn shade_impl(&self, scene: &Scene, tdata_opt: &Option<TraceData>, depth: u16) -> RGB {
-        let mut color = RGB::new(0.0, 0.0, 0.0);
+            let bsdf = tdata.mat_data.bsdf(&tdata.isect);
+            color += beta * Self::ambient_lighting(scene, &tdata);
+            color += beta * self.direct_lighting_smpl(scene, &tdata, &bsdf, sampler);
 
-        // if no intersection, return background
-        if tdata_opt.is_none() {
-            return self.background;
-        }
-        let tdata = tdata_opt.unwrap();
-        if let Some(le) = tdata.mat_data.le {
-            return le;
-        }
+            let u_lobe = sampler.get_1d();
+            let u_dir = sampler.get_2d();
+            let u_rr = sampler.get_1d();
 
-        let rnd_depth: f32 = thread_rng().gen();
-        if depth > 0 || rnd_depth < self.continue_prob {
-            let lcolor: RGB;
-            let mdata = &tdata.mat_data;
-            let s_p = mdata.ks.y() / (mdata.ks.y() + mdata.kd.y());
-            let rnd: f32 = thread_rng().gen();
-            if rnd <= s_p || s_p >= (1.0 - ray::EPSILON) {
-                lcolor = self.specular_reflection(scene, &tdata, depth) / s_p;
-            } else {
-                lcolor = self.diffuse_reflection(scene, &tdata, depth) / (1.0 - s_p);
+            // russian roulette once the guaranteed bounces are used up
+            if depth >= self.reflection_depth {
+                if u_rr >= self.continue_prob {
+                    break;
+                }
+                beta /= self.continue_prob;
             }
-            if depth > 0 {
-                color += lcolor;
-            } else {
-                color += lcolor / self.continue_prob;
+            depth = depth.saturating_add(1);
+
+            let Some(bs) = bsdf.sample(tdata.isect.wo, u_lobe, u_dir) else {
+                break;
+            };
+            beta = beta * bs.f * (bsdf.abs_cos(bs.wi) / bs.pdf);
+            bsdf_pdf = bs.pdf;
+            specular_bounce = bs.is_specular;
+            let dir = bs.wi;
+            if beta.is_zero() {
+                break;
             }
+
+            prev_point = tdata.isect.point;
+            prev_normal = tdata.isect.sh_normal;
+            ray = tdata.isect.spawn_ray(dir, self.collision_bias);
+            tdata_opt = scene.trace(&ray);
         }
 
-        color += self.direct_lighting_smpl(scene, &tdata);
         color
     }
 }
 
 impl Shader for PathTracerShader {
-    fn shade(
+    fn shade<S: Sampler>(
         &self,
-        scene: &crate::scene::Scene,
-        tdata_opt: &Option<crate::scene::TraceData>,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        sampler: &mut S,
     ) -> RGB {
-        self.shade_impl(scene, tdata_opt, self.reflection_depth)
+        self.shade_impl(scene, ray, tdata_opt, sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use crate::{
+        lights::{AreaLight, Light},
+        primitives::{material_data::MaterialData, quad::Quad, sphere::Sphere, AnyShape, Shape},
+        rays::ray::Ray,
+        samplers::{AnySampler, Sampler, SamplerType},
+        scene::Scene,
+        shaders::Shader,
+        utils::{
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::PathTracerShader;
+
+    // a diffuse floor under a rough glass pane, lit by a sphere between the two that is
+    // either a light, found by both strategies, or plain emissive geometry that only
+    // BSDF sampling finds
+    fn glass_scene(sampled_light: bool) -> Scene {
+        let mut scene = Scene::new();
+        let floor = scene.add_material(
+            "floor",
+            MaterialData {
+                kd: RGB::new(0.5, 0.5, 0.5),
+                ..Default::default()
+            },
+        );
+        let glass = scene.add_material(
+            "glass",
+            MaterialData {
+                kt: RGB::new(1.0, 1.0, 1.0),
+                ior: 1.5,
+                roughness: Some(0.5),
+                ..Default::default()
+            },
+        );
+        let quad = |y: f32| {
+            AnyShape::Quad(Quad::new(
+                Point::new(-5.0, y, -5.0),
+                Vector::new(0.0, 0.0, 10.0),
+                Vector::new(10.0, 0.0, 0.0),
+            ))
+        };
+        scene.add_shape(quad(0.0), floor);
+        scene.add_shape(quad(1.0), glass);
+
+        let le = RGB::new(4.0, 4.0, 4.0);
+        let sphere = AnyShape::Sphere(Sphere::new(Point::new(0.0, 0.5, 0.0), 0.2));
+        if sampled_light {
+            scene.add_light(Light::Area(AreaLight::new(
+                le * (PI * sphere.area()),
+                sphere,
+            )));
+        } else {
+            let emitter = scene.add_material(
+                "emitter",
+                MaterialData {
+                    le: Some(le),
+                    ..Default::default()
+                },
+            );
+            scene.add_shape(sphere, emitter);
+        }
+        scene.build_accel();
+        scene
+    }
+
+    fn estimate(scene: &Scene, n: u32) -> f32 {
+        let shader = PathTracerShader {
+            background: RGB::default(),
+            collision_bias: 1e-3,
+            continue_prob: 0.5,
+            reflection_depth: 3,
+        };
+        let mut sampler = AnySampler::new(SamplerType::Independent, n, 11);
+        // looking down through the pane at the floor next to the sphere
+        let ray = Ray::new(Point::new(0.3, 3.0, 0.1), Vector::new(0.0, -1.0, 0.0));
+        let tdata = scene.trace(&ray);
+        let mut sum = 0.0;
+        for i in 0..n {
+            sampler.start_pixel_sample(0, 0, i);
+            sum += shader.shade(scene, &ray, &tdata, &mut sampler).g;
+        }
+        sum / n as f32
+    }
+
+    #[test]
+    fn rough_glass_mis_matches_bsdf_sampling() {
+        let n = 100_000;
+        let mis = estimate(&glass_scene(true), n);
+        let bsdf_only = estimate(&glass_scene(false), n);
+        assert!(bsdf_only > 0.0);
+        assert!(
+            (mis - bsdf_only).abs() < 0.03 * bsdf_only,
+            "{} {}",
+            mis,
+            bsdf_only
+        );
     }
 }
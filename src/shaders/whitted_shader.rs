@@ -1,9 +1,10 @@
-use super::Shader;
+use super::{light_visible, Shader};
 use crate::{
     lights::Light,
     rays::ray::Ray,
+    samplers::Sampler,
     scene::{Scene, TraceData},
-    utils::{rgb::RGB, vector::Vector},
+    utils::rgb::RGB,
 };
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -14,64 +15,71 @@ pub struct WhittedShader {
 }
 
 impl WhittedShader {
-    fn shade_impl(&self, scene: &Scene, tdata_opt: &Option<TraceData>, depth: u16) -> RGB {
+    fn shade_impl<S: Sampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        depth: u16,
+        sampler: &mut S,
+    ) -> RGB {
         let mut color = RGB::new(0.0, 0.0, 0.0);
 
-        // if no intersection, return background
+        // if no intersection, return the environment or background
         if tdata_opt.is_none() {
-            return self.background;
+            return scene.escaped_radiance(ray.direction, self.background);
         }
         let tdata = tdata_opt.unwrap();
         if let Some(le) = tdata.mat_data.le {
             return le;
         }
 
-        // specular
-        if !tdata.mat_data.ks.is_zero() && depth > 0 {
-            let gn = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-            let cos = gn.dot(tdata.isect.wo);
-            let ray_dir = 2.0 * cos * gn - tdata.isect.wo;
+        let bsdf = tdata.mat_data.bsdf(&tdata.isect);
+        let wo = tdata.isect.wo;
 
-            let origin = tdata.isect.point + gn * self.shadow_bias;
-            let sp_ray = Ray::new(origin, ray_dir);
-
-            let sp_tdata_opt = scene.trace(&sp_ray);
-            color += self.shade_impl(scene, &sp_tdata_opt, depth - 1);
+        // follow every delta direction of the BSDF, i.e. mirror reflection and refraction
+        if depth > 0 {
+            bsdf.for_each_specular(wo, |bs| {
+                let sp_ray = tdata.isect.spawn_ray(bs.wi, self.shadow_bias);
+                let sp_tdata_opt = scene.trace(&sp_ray);
+                let weight = bs.f * (bsdf.abs_cos(bs.wi) / bs.pdf);
+                color +=
+                    weight * self.shade_impl(scene, &sp_ray, &sp_tdata_opt, depth - 1, sampler);
+            });
         }
 
-        // Loop over scene's light sources and process Ambient Lights
         for light in &scene.lights {
             match light {
                 Light::Ambient(ambient_light) => {
                     color += tdata.mat_data.ka * ambient_light.color;
                 }
-                Light::Point(point_light) => {
-                    if tdata.mat_data.kd.is_zero() {
+                Light::Point(_)
+                | Light::Spot(_)
+                | Light::Directional(_)
+                | Light::Area(_)
+                | Light::Mesh(_) => {
+                    // a single shadow ray per light, to a random point on the lights with
+                    // an extent, drawn before any early out so every light keeps its dimension
+                    let rnd = match light {
+                        Light::Area(_) | Light::Mesh(_) => sampler.get_2d(),
+                        _ => [0.5, 0.5],
+                    };
+                    if bsdf.is_specular() {
                         continue;
                     }
-                    let mut ray_dir: Vector = (point_light.position - tdata.isect.point).into();
-                    let light_dist = ray_dir.norm();
-                    ray_dir.normalize();
-
-                    let mut g_normal = tdata.isect.geo_normal.face_forward(tdata.isect.wo);
-                    g_normal.normalize();
-
-                    if ray_dir.dot(g_normal) < 0.0 {
+                    let Some(ls) = light.sample_li(tdata.isect.point, tdata.isect.time, &rnd)
+                    else {
                         continue;
-                    }
-
-                    let ray_o = tdata.isect.point + g_normal * self.shadow_bias;
-                    let ray: Ray = Ray::new(ray_o, ray_dir);
-                    let light_tdata_opt = scene.trace(&ray); // TODO: visibility instead of trace
-
-                    if light_tdata_opt.is_none()
-                        || light_tdata_opt.unwrap().isect.depth >= light_dist
+                    };
+                    let f = bsdf.eval(wo, ls.wi) * bsdf.abs_cos(ls.wi);
+                    if !f.is_zero()
+                        && light_visible(scene, &tdata.isect, &bsdf, &ls, self.shadow_bias)
                     {
-                        color +=
-                            tdata.mat_data.kd * point_light.color * 0f32.max(g_normal.dot(ray_dir));
+                        color += f * ls.li / ls.pdf;
                     }
                 }
-                Light::Area(_area_light) => {
+                // only seen through escaping rays
+                Light::Environment(_) => {
                     continue;
                 }
             }
@@ -82,7 +90,78 @@ impl WhittedShader {
 }
 
 impl Shader for WhittedShader {
-    fn shade(&self, scene: &Scene, tdata_opt: &Option<TraceData>) -> RGB {
-        self.shade_impl(scene, tdata_opt, self.reflection_depth)
+    fn shade<S: Sampler>(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        tdata_opt: &Option<TraceData>,
+        sampler: &mut S,
+    ) -> RGB {
+        self.shade_impl(scene, ray, tdata_opt, self.reflection_depth, sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        lights::{AreaLight, Light},
+        primitives::{material_data::MaterialData, quad::Quad, sphere::Sphere, AnyShape},
+        rays::ray::Ray,
+        samplers::{AnySampler, Sampler, SamplerType},
+        scene::Scene,
+        shaders::{distributed_shader::DistributedShader, Shader},
+        utils::{
+            rgb::RGB,
+            vector::{Point, Vector},
+        },
+    };
+
+    use super::WhittedShader;
+
+    #[test]
+    fn area_lights_light_diffuse_surfaces() {
+        // a diffuse floor lit only by a sphere light above it
+        let mut scene = Scene::new();
+        let floor = scene.add_material(
+            "floor",
+            MaterialData {
+                kd: RGB::new(0.5, 0.5, 0.5),
+                ..Default::default()
+            },
+        );
+        scene.add_shape(
+            AnyShape::Quad(Quad::new(
+                Point::new(-5.0, 0.0, -5.0),
+                Vector::new(0.0, 0.0, 10.0),
+                Vector::new(10.0, 0.0, 0.0),
+            )),
+            floor,
+        );
+        let sphere = AnyShape::Sphere(Sphere::new(Point::new(0.0, 1.0, 0.0), 0.2));
+        scene.add_light(Light::Area(AreaLight::new(RGB::new(4.0, 4.0, 4.0), sphere)));
+        scene.build_accel();
+
+        let whitted = WhittedShader {
+            shadow_bias: 1e-3,
+            ..Default::default()
+        };
+        let distributed = DistributedShader {
+            shadow_bias: 1e-3,
+            ..Default::default()
+        };
+        let ray = Ray::new(Point::new(0.3, 3.0, 0.1), Vector::new(0.0, -1.0, 0.0));
+        let tdata = scene.trace(&ray);
+        let n = 1000;
+        let mut whitted_sampler = AnySampler::new(SamplerType::Independent, n, 3);
+        let mut distributed_sampler = AnySampler::new(SamplerType::Independent, n, 3);
+        for i in 0..n {
+            whitted_sampler.start_pixel_sample(0, 0, i);
+            distributed_sampler.start_pixel_sample(0, 0, i);
+            // one shadow ray to the same point on the light as the distributed shader
+            let w = whitted.shade(&scene, &ray, &tdata, &mut whitted_sampler);
+            let d = distributed.shade(&scene, &ray, &tdata, &mut distributed_sampler);
+            assert!(w.g > 0.0);
+            assert_eq!(w.g, d.g);
+        }
     }
 }
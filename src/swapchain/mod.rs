@@ -49,6 +49,10 @@ impl DoubleBufferSwapChain {
         bdata.is_closed = true;
     }
 
+    pub fn is_closed(&self) -> bool {
+        self.buf_data.lock().unwrap().is_closed
+    }
+
     // returns true if swapchain is open, false otherwise
     pub fn update_back<OP>(&self, f: OP) -> bool
     where
@@ -80,7 +84,9 @@ impl DoubleBufferSwapChain {
         return true;
     }
 
+    // blocks until a frame is ready, for consumers with no input to handle meanwhile;
     // returns true if swapchain is open, false otherwise
+    #[allow(dead_code)]
     pub fn wait_use_front<OP>(&self, f: OP) -> bool
     where
         OP: FnOnce(&[u32]),
@@ -108,4 +114,31 @@ impl DoubleBufferSwapChain {
         }
         return true;
     }
+
+    // like wait_use_front but returns false right away when no frame is ready, so the
+    // caller keeps handling input while the renderer is idle
+    pub fn try_use_front<OP>(&self, f: OP) -> bool
+    where
+        OP: FnOnce(&[u32]),
+    {
+        let b_mtx;
+        {
+            let bdata = self.buf_data.lock().unwrap();
+            if bdata.is_closed || !bdata.buf_ready[bdata.front as usize] {
+                return false;
+            }
+            b_mtx = &self.buffers[bdata.front as usize];
+        }
+        {
+            let b = b_mtx.lock().unwrap();
+            f(&b.buf);
+        }
+        {
+            let mut bdata = self.buf_data.lock().unwrap();
+            let fr = bdata.front;
+            bdata.buf_ready[fr as usize] = false;
+            bdata.front ^= 1;
+        }
+        true
+    }
 }
@@ -0,0 +1,159 @@
+use std::{io, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    images::{image_rgb::ImageRGB, load_color_image},
+    utils::rgb::RGB,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMode {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Texture {
+    pub image: ImageRGB,
+    pub wrap: WrapMode,
+    pub filter: FilterMode,
+}
+
+impl Texture {
+    pub fn new(image: ImageRGB, wrap: WrapMode, filter: FilterMode) -> Self {
+        Self {
+            image,
+            wrap,
+            filter,
+        }
+    }
+
+    // textures are color maps, see load_color_image
+    pub fn load(path: &Path, wrap: WrapMode, filter: FilterMode) -> io::Result<Self> {
+        Ok(Self::new(load_color_image(path)?, wrap, filter))
+    }
+
+    // uv in texture space, v = 0 is the bottom row of the image
+    pub fn sample(&self, uv: [f32; 2]) -> RGB {
+        if self.image.data.is_empty() {
+            return RGB::default();
+        }
+        let x = uv[0] * self.image.width as f32;
+        let y = (1.0 - uv[1]) * self.image.height as f32;
+
+        match self.filter {
+            FilterMode::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            FilterMode::Bilinear => {
+                // texel centers sit at half integer coordinates
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let dx = x - x0;
+                let dy = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                self.texel(x0, y0) * ((1.0 - dx) * (1.0 - dy))
+                    + self.texel(x0 + 1, y0) * (dx * (1.0 - dy))
+                    + self.texel(x0, y0 + 1) * ((1.0 - dx) * dy)
+                    + self.texel(x0 + 1, y0 + 1) * (dx * dy)
+            }
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> RGB {
+        let x = wrap_coord(x, self.image.width as i64, self.wrap);
+        let y = wrap_coord(y, self.image.height as i64, self.wrap);
+        self.image.get(x, y)
+    }
+}
+
+fn wrap_coord(c: i64, size: i64, wrap: WrapMode) -> u32 {
+    let c = match wrap {
+        WrapMode::Repeat => c.rem_euclid(size),
+        WrapMode::Clamp => c.clamp(0, size - 1),
+        WrapMode::Mirror => {
+            let period = c.rem_euclid(2 * size);
+            if period < size {
+                period
+            } else {
+                2 * size - 1 - period
+            }
+        }
+    };
+    c as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        images::{image_pfm, image_ppm::ImagePPM, image_rgb::ImageRGB},
+        utils::rgb::RGB,
+    };
+
+    use super::{FilterMode, Texture, WrapMode};
+
+    fn checker() -> ImageRGB {
+        // 2x2, top row black/white, bottom row white/black
+        let mut image = ImageRGB::new(2, 2);
+        image.set(1, 0, &RGB::new(1.0, 1.0, 1.0));
+        image.set(0, 1, &RGB::new(1.0, 1.0, 1.0));
+        image
+    }
+
+    #[test]
+    fn texture_nearest_wrap() {
+        let repeat = Texture::new(checker(), WrapMode::Repeat, FilterMode::Nearest);
+        let clamp = Texture::new(checker(), WrapMode::Clamp, FilterMode::Nearest);
+        let mirror = Texture::new(checker(), WrapMode::Mirror, FilterMode::Nearest);
+
+        // bottom left texel is white
+        assert_eq!(repeat.sample([0.25, 0.25]).r, 1.0);
+        assert_eq!(repeat.sample([1.25, 0.25]).r, 1.0);
+        assert_eq!(clamp.sample([1.25, 0.25]).r, 0.0);
+        assert_eq!(mirror.sample([1.25, 0.25]).r, 0.0);
+        assert_eq!(mirror.sample([-0.25, 0.25]).r, 1.0);
+    }
+
+    #[test]
+    fn texture_bilinear() {
+        let tex = Texture::new(checker(), WrapMode::Clamp, FilterMode::Bilinear);
+        // texel centers return the texel itself, the middle averages all four
+        assert!((tex.sample([0.25, 0.25]).r - 1.0).abs() < 1e-6);
+        assert!((tex.sample([0.75, 0.25]).r - 0.0).abs() < 1e-6);
+        assert!((tex.sample([0.5, 0.5]).r - 0.5).abs() < 1e-6);
+        assert!((tex.sample([0.5, 0.25]).r - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ppm_textures_are_srgb() {
+        let mut image = ImageRGB::new(1, 1);
+        image.set(0, 0, &RGB::new(0.5, 0.5, 0.5));
+        let dir = std::env::temp_dir();
+        let ppm_path = dir.join("vi_renderer_texture.ppm");
+        let pfm_path = dir.join("vi_renderer_texture.pfm");
+        ImagePPM::from(image.clone()).save(&ppm_path).unwrap();
+        image_pfm::save(&image, &pfm_path).unwrap();
+
+        // 8 bit mid gray 127 is about 21% linear reflectance, float maps stay as they are
+        let ppm = Texture::load(&ppm_path, WrapMode::Repeat, FilterMode::Nearest).unwrap();
+        let pfm = Texture::load(&pfm_path, WrapMode::Repeat, FilterMode::Nearest).unwrap();
+        assert!((ppm.sample([0.5, 0.5]).g - 0.2122).abs() < 1e-3);
+        assert_eq!(pfm.sample([0.5, 0.5]).g, 0.5);
+
+        std::fs::remove_file(ppm_path).unwrap();
+        std::fs::remove_file(pfm_path).unwrap();
+    }
+}
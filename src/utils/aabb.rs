@@ -1,4 +1,7 @@
-use crate::{rays::ray::Ray, utils::vector::Point};
+use crate::{
+    rays::ray::Ray,
+    utils::vector::{Point, Vector},
+};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct AABB {
@@ -78,6 +81,83 @@ impl AABB {
 
         return tmax >= tmin;
     }
+
+    // same slab test, but clipped to [0, t_max] and returning the entry distance
+    pub fn intersect_range(&self, ray: &Ray, t_max: f32) -> Option<f32> {
+        let tx1 = (self.min.x - ray.origin.x) * ray.direction_inv.x;
+        let tx2 = (self.max.x - ray.origin.x) * ray.direction_inv.x;
+
+        let mut tmin = tx1.min(tx2);
+        let mut tmax = tx1.max(tx2);
+
+        let ty1 = (self.min.y - ray.origin.y) * ray.direction_inv.y;
+        let ty2 = (self.max.y - ray.origin.y) * ray.direction_inv.y;
+
+        tmin = tmin.max(ty1.min(ty2));
+        tmax = tmax.min(ty1.max(ty2));
+
+        let tz1 = (self.min.z - ray.origin.z) * ray.direction_inv.z;
+        let tz2 = (self.max.z - ray.origin.z) * ray.direction_inv.z;
+
+        tmin = tmin.max(tz1.min(tz2)).max(0.0);
+        tmax = tmax.min(tz1.max(tz2)).min(t_max);
+
+        if tmax >= tmin {
+            Some(tmin)
+        } else {
+            None
+        }
+    }
+
+    pub fn union(&self, other: &AABB) -> AABB {
+        AABB {
+            max: Point::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+            min: Point::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn diagonal(&self) -> Vector {
+        (self.max - self.min).into()
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.diagonal();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // position of p inside the box, 0 at min and 1 at max, per axis
+    pub fn offset(&self, p: &Point) -> Vector {
+        let mut o: Vector = (*p - self.min).into();
+        if self.max.x > self.min.x {
+            o.x /= self.max.x - self.min.x;
+        }
+        if self.max.y > self.min.y {
+            o.y /= self.max.y - self.min.y;
+        }
+        if self.max.z > self.min.z {
+            o.z /= self.max.z - self.min.z;
+        }
+        o
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,267 @@
+// based on PBRT's 3rd ed. book, sec 13.3.1
+// piecewise-constant function over [0, 1] sampled by inverting its CDF
+#[derive(Debug, Clone, Default)]
+pub struct Distribution1D {
+    pub func: Vec<f32>,
+    // n + 1 entries running from 0 to 1
+    pub cdf: Vec<f32>,
+    pub func_int: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: &[f32]) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + func[i - 1] / n as f32;
+        }
+        let func_int = cdf[n];
+        // a zero function is sampled uniformly, its pdf stays 0
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            *c = if func_int == 0.0 {
+                i as f32 / n as f32
+            } else {
+                *c / func_int
+            };
+        }
+        Self {
+            func: func.to_vec(),
+            cdf,
+            func_int,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    // value in [0, 1), its pdf and the segment it falls in
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        let (offset, du) = self.find_segment(u);
+        let x = ((offset as f32 + du) / self.count() as f32).min(1.0 - f32::EPSILON);
+        (x, self.pdf_segment(offset), offset)
+    }
+
+    // segment picked with probability proportional to its value, that probability and
+    // `u` remapped to [0, 1) within the segment, fresh for further sampling
+    pub fn sample_discrete(&self, u: f32) -> (usize, f32, f32) {
+        let (offset, du) = self.find_segment(u);
+        (offset, self.pmf(offset), du.min(1.0 - f32::EPSILON))
+    }
+
+    pub fn pmf(&self, offset: usize) -> f32 {
+        self.pdf_segment(offset) / self.count() as f32
+    }
+
+    // last segment whose CDF does not exceed u, skipping empty ones, and how far into it
+    // u lies
+    fn find_segment(&self, u: f32) -> (usize, f32) {
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+        (offset, du)
+    }
+
+    fn pdf_segment(&self, offset: usize) -> f32 {
+        if self.func_int == 0.0 {
+            0.0
+        } else {
+            self.func[offset] / self.func_int
+        }
+    }
+}
+
+// based on PBRT's 3rd ed. book, sec 13.6.7
+// piecewise-constant function over [0, 1]^2 given as `nv` rows of `nu` values, sampled
+// by picking a row from the marginal and then a column within it
+#[derive(Debug, Clone, Default)]
+pub struct Distribution2D {
+    pub conditional: Vec<Distribution1D>,
+    pub marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], nu: usize, nv: usize) -> Self {
+        let conditional: Vec<Distribution1D> = func
+            .chunks_exact(nu)
+            .take(nv)
+            .map(Distribution1D::new)
+            .collect();
+        let marginal_func: Vec<f32> = conditional.iter().map(|d| d.func_int).collect();
+        Self {
+            conditional,
+            marginal: Distribution1D::new(&marginal_func),
+        }
+    }
+
+    // point in [0, 1)^2 and its pdf with respect to area in that square
+    pub fn sample_continuous(&self, u: [f32; 2]) -> ([f32; 2], f32) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u[1]);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u[0]);
+        ([u, v], pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, p: [f32; 2]) -> f32 {
+        if self.marginal.func_int == 0.0 {
+            return 0.0;
+        }
+        let row = &self.conditional[index(p[1], self.marginal.count())];
+        row.func[index(p[0], row.count())] / self.marginal.func_int
+    }
+}
+
+// based on PBRT's 4th ed. book, sec A.1.4
+// discrete distribution sampled in constant time, every bin keeps its own outcome with
+// probability q and hands the rest over to its alias
+#[derive(Debug, Clone, Default)]
+pub struct AliasTable {
+    pub bins: Vec<AliasBin>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AliasBin {
+    pub q: f32,
+    // probability of the bin's own outcome
+    pub p: f32,
+    pub alias: u32,
+}
+
+impl AliasTable {
+    // `weights` need not be normalized but must not all be zero
+    pub fn new(weights: &[f32]) -> Self {
+        let n = weights.len();
+        let sum: f64 = weights.iter().map(|&w| w as f64).sum();
+        let mut bins: Vec<AliasBin> = weights
+            .iter()
+            .map(|&w| AliasBin {
+                q: 0.0,
+                p: (w as f64 / sum) as f32,
+                alias: 0,
+            })
+            .collect();
+
+        // pair bins below the average with ones above it until every bin is full
+        let mut under = Vec::new();
+        let mut over = Vec::new();
+        for (i, &w) in weights.iter().enumerate() {
+            let p_hat = w as f64 / sum * n as f64;
+            if p_hat < 1.0 {
+                under.push((i, p_hat));
+            } else {
+                over.push((i, p_hat));
+            }
+        }
+        while let (Some(&(u, u_hat)), Some(&(o, o_hat))) = (under.last(), over.last()) {
+            under.pop();
+            over.pop();
+            bins[u].q = u_hat as f32;
+            bins[u].alias = o as u32;
+            let excess = u_hat + o_hat - 1.0;
+            if excess < 1.0 {
+                under.push((o, excess));
+            } else {
+                over.push((o, excess));
+            }
+        }
+        // leftovers are 1 up to round-off
+        for (i, _) in under.into_iter().chain(over) {
+            bins[i].q = 1.0;
+            bins[i].alias = i as u32;
+        }
+        Self { bins }
+    }
+
+    // outcome and its probability
+    pub fn sample(&self, u: f32) -> (usize, f32) {
+        let n = self.bins.len();
+        let offset = ((u * n as f32) as usize).min(n - 1);
+        let up = (u * n as f32 - offset as f32).min(1.0 - f32::EPSILON);
+        let bin = &self.bins[offset];
+        if up < bin.q {
+            (offset, bin.p)
+        } else {
+            (bin.alias as usize, self.bins[bin.alias as usize].p)
+        }
+    }
+
+    pub fn pmf(&self, i: usize) -> f32 {
+        self.bins[i].p
+    }
+}
+
+// segment of [0, 1] split in n containing x
+fn index(x: f32, n: usize) -> usize {
+    ((x * n as f32) as usize).min(n - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{AliasTable, Distribution2D};
+
+    #[test]
+    fn distribution_2d_pdf_matches_samples() {
+        let (nu, nv) = (5, 3);
+        let func = [
+            0.0, 1.0, 2.0, 0.0, 4.0, //
+            0.0, 0.0, 0.0, 0.0, 0.0, //
+            8.0, 0.5, 0.0, 3.0, 1.0,
+        ];
+        let dist = Distribution2D::new(&func, nu, nv);
+        let mut rng = StdRng::seed_from_u64(3);
+        let n = 100_000;
+        let mut counts = [0u32; 15];
+        for _ in 0..n {
+            let (p, pdf) = dist.sample_continuous([rng.gen(), rng.gen()]);
+            assert!(p.iter().all(|x| (0.0..1.0).contains(x)));
+            assert!(pdf > 0.0);
+            assert!((dist.pdf(p) - pdf).abs() < 1e-5 * pdf);
+            counts[(p[1] * nv as f32) as usize * nu + (p[0] * nu as f32) as usize] += 1;
+        }
+
+        // each cell is picked in proportion to its value
+        let total: f32 = func.iter().sum();
+        for (i, &c) in counts.iter().enumerate() {
+            let expected = func[i] / total;
+            assert!((c as f32 / n as f32 - expected).abs() < 0.01, "{}", i);
+        }
+        // the pdf integrates to 1 over the unit square
+        let mut integral = 0.0;
+        for j in 0..nv {
+            for i in 0..nu {
+                let center = [(i as f32 + 0.5) / nu as f32, (j as f32 + 0.5) / nv as f32];
+                integral += dist.pdf(center) / (nu * nv) as f32;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn alias_table_matches_weights() {
+        let weights = [0.5, 0.0, 3.0, 1.0, 0.25, 8.0];
+        let table = AliasTable::new(&weights);
+        let total: f32 = weights.iter().sum();
+        let mut rng = StdRng::seed_from_u64(1);
+        let n = 100_000;
+        let mut counts = [0u32; 6];
+        for _ in 0..n {
+            let (i, pmf) = table.sample(rng.gen());
+            assert_eq!(pmf, table.pmf(i));
+            counts[i] += 1;
+        }
+        for (i, &c) in counts.iter().enumerate() {
+            let expected = weights[i] / total;
+            assert!((table.pmf(i) - expected).abs() < 1e-6);
+            assert!((c as f32 / n as f32 - expected).abs() < 0.01, "{}", i);
+        }
+        assert_eq!(counts[1], 0);
+    }
+}
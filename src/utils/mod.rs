@@ -1,5 +1,7 @@
 pub mod aabb;
+pub mod distribution;
 pub mod rgb;
+pub mod transform;
 pub mod vector;
 
 #[derive(Default, Clone, Copy, Debug)]
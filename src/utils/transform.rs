@@ -0,0 +1,485 @@
+use std::ops;
+
+use crate::rays::ray::Ray;
+
+use super::{
+    aabb::AABB,
+    vector::{Point, Vector},
+};
+
+// based on PBRT's 3rd ed. book, sec 2.7
+// 4x4 matrix plus its inverse, so either direction of a mapping is a lookup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub m: [[f32; 4]; 4],
+    pub m_inv: [[f32; 4]; 4],
+}
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            m: IDENTITY,
+            m_inv: IDENTITY,
+        }
+    }
+}
+
+impl Transform {
+    // None for singular matrices
+    pub fn new(m: [[f32; 4]; 4]) -> Option<Self> {
+        Some(Self {
+            m,
+            m_inv: invert(&m)?,
+        })
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    pub fn translate(delta: Vector) -> Self {
+        let mut m = IDENTITY;
+        let mut m_inv = IDENTITY;
+        for i in 0..3 {
+            m[i][3] = delta[i];
+            m_inv[i][3] = -delta[i];
+        }
+        Self { m, m_inv }
+    }
+
+    pub fn scale(x: f32, y: f32, z: f32) -> Self {
+        let mut m = IDENTITY;
+        let mut m_inv = IDENTITY;
+        for (i, s) in [x, y, z].into_iter().enumerate() {
+            m[i][i] = s;
+            m_inv[i][i] = 1.0 / s;
+        }
+        Self { m, m_inv }
+    }
+
+    // angle in radians, counterclockwise looking down the axis
+    pub fn rotate(angle: f32, axis: Vector) -> Self {
+        Quaternion::from_axis_angle(axis, angle).to_transform()
+    }
+
+    // maps the frame's axes and origin to the given world vectors and point, the
+    // axes being orthonormal the inverse is the transposed rotation
+    pub fn from_frame(origin: Point, x: Vector, y: Vector, z: Vector) -> Self {
+        let o: Vector = origin.into();
+        let m = [
+            [x.x, y.x, z.x, o.x],
+            [x.y, y.y, z.y, o.y],
+            [x.z, y.z, z.z, o.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let m_inv = [
+            [x.x, x.y, x.z, -x.dot(o)],
+            [y.x, y.y, y.z, -y.dot(o)],
+            [z.x, z.y, z.z, -z.dot(o)],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        Self { m, m_inv }
+    }
+
+    // projection of a camera looking down -z onto the z = -1 plane, depth being
+    // remapped to [0, 1] between the near and far planes
+    pub fn perspective(near: f32, far: f32) -> Self {
+        let m = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, -far / (far - near), -far * near / (far - near)],
+            [0.0, 0.0, -1.0, 0.0],
+        ];
+        Self::new(m).expect("near and far planes must differ")
+    }
+
+    // homogeneous points are divided back by their weight
+    pub fn apply_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        let r = |i: usize| m[i][0] * p.x + m[i][1] * p.y + m[i][2] * p.z + m[i][3];
+        let (x, y, z, w) = (r(0), r(1), r(2), r(3));
+        if w == 1.0 {
+            Point::new(x, y, z)
+        } else {
+            Point::new(x / w, y / w, z / w)
+        }
+    }
+
+    pub fn apply_vector(&self, v: Vector) -> Vector {
+        let m = &self.m;
+        let r = |i: usize| m[i][0] * v.x + m[i][1] * v.y + m[i][2] * v.z;
+        Vector::new(r(0), r(1), r(2))
+    }
+
+    // normals transform with the inverse transpose so they stay perpendicular to the
+    // transformed surface, the result is not normalized
+    pub fn apply_normal(&self, n: Vector) -> Vector {
+        let m = &self.m_inv;
+        let r = |i: usize| m[0][i] * n.x + m[1][i] * n.y + m[2][i] * n.z;
+        Vector::new(r(0), r(1), r(2))
+    }
+
+    // the direction is left unnormalized so distances along the ray keep their
+    // parameter, hits found on either side report the same depth
+    pub fn apply_ray(&self, ray: &Ray) -> Ray {
+        let direction = self.apply_vector(ray.direction);
+        Ray {
+            origin: self.apply_point(ray.origin),
+            direction,
+            direction_inv: 1.0 / direction,
+            time: ray.time,
+        }
+    }
+
+    // box around the transformed corners, affine transforms only
+    pub fn apply_aabb(&self, bb: &AABB) -> AABB {
+        let mut out = AABB::default();
+        if bb.is_empty() {
+            return out;
+        }
+        for i in 0..8 {
+            let corner = Point::new(
+                if i & 1 == 0 { bb.min.x } else { bb.max.x },
+                if i & 2 == 0 { bb.min.y } else { bb.max.y },
+                if i & 4 == 0 { bb.min.z } else { bb.max.z },
+            );
+            out.update(&self.apply_point(corner));
+        }
+        out
+    }
+
+    // mirroring transforms turn the winding of triangles around
+    pub fn swaps_handedness(&self) -> bool {
+        let m = &self.m;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        det < 0.0
+    }
+}
+
+// unit quaternion, PBRT's 3rd ed. book, sec 2.9, used to interpolate rotations
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    pub v: Vector,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn from_axis_angle(axis: Vector, angle: f32) -> Self {
+        let mut axis = axis;
+        axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Self {
+            v: sin * axis,
+            w: cos,
+        }
+    }
+
+    pub fn dot(&self, other: Quaternion) -> f32 {
+        self.v.dot(other.v) + self.w * other.w
+    }
+
+    // angle of the rotation taking one orientation to the other
+    pub fn angle_to(&self, other: Quaternion) -> f32 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    // constant speed interpolation along the shorter arc
+    pub fn slerp(&self, other: Quaternion, t: f32) -> Self {
+        let mut cos_theta = self.dot(other);
+        let mut other = other;
+        if cos_theta < 0.0 {
+            other = Quaternion {
+                v: -1.0 * other.v,
+                w: -other.w,
+            };
+            cos_theta = -cos_theta;
+        }
+        let (a, b) = if cos_theta > 0.9995 {
+            // nearly parallel, a normalized lerp is accurate enough
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        let v = a * self.v + b * other.v;
+        let w = a * self.w + b * other.w;
+        let norm = (v.dot(v) + w * w).sqrt();
+        Self {
+            v: v / norm,
+            w: w / norm,
+        }
+    }
+
+    // rotation matrix, its inverse being the transpose
+    pub fn to_transform(self) -> Transform {
+        let Vector { x, y, z } = self.v;
+        let w = self.w;
+        let m = [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let mut m_inv = m;
+        for (i, row) in m_inv.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = m[j][i];
+            }
+        }
+        Transform { m, m_inv }
+    }
+}
+
+// based on PBRT's 3rd ed. book, sec 2.9
+// transform moving between two poses over times 0 to 1, each pose scaling, rotating
+// and translating around `pivot`; scales and translations interpolate linearly and
+// rotations at constant angular speed, around the poses' axis when they share it, so
+// they may exceed half a turn, and along the shorter arc otherwise
+#[derive(Debug, Clone, Copy)]
+pub struct AnimatedTransform {
+    pub scales: [Vector; 2],
+    // axes and angles in radians
+    pub rotations: [(Vector, f32); 2],
+    pub translations: [Vector; 2],
+    pub pivot: Vector,
+}
+
+impl AnimatedTransform {
+    // pose at time t, times outside [0, 1] clamp to the ends of the motion
+    pub fn at(&self, t: f32) -> Transform {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |[a, b]: [Vector; 2]| a + t * (b - a);
+        let s = lerp(self.scales);
+        let [(axis0, angle0), (_, angle1)] = self.rotations;
+        let rotation = if self.same_axis() {
+            Transform::rotate(angle0 + t * (angle1 - angle0), axis0)
+        } else {
+            let [q0, q1] = self.quaternions();
+            q0.slerp(q1, t).to_transform()
+        };
+        // built in place rather than as a product of four transforms, a pose is taken
+        // for every ray; x goes to R S (x - pivot) + pivot + translation
+        let r = &rotation.m;
+        let (mut m, mut m_inv) = (IDENTITY, IDENTITY);
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = r[i][j] * s[j];
+                m_inv[i][j] = r[j][i] / s[i];
+            }
+        }
+        let (p, t) = (self.pivot, self.pivot + lerp(self.translations));
+        for i in 0..3 {
+            m[i][3] = t[i] - (0..3).map(|j| m[i][j] * p[j]).sum::<f32>();
+            m_inv[i][3] = p[i] - (0..3).map(|j| m_inv[i][j] * t[j]).sum::<f32>();
+        }
+        Transform { m, m_inv }
+    }
+
+    // angle the rotation sweeps over the motion, in radians
+    pub fn rotation_angle(&self) -> f32 {
+        if self.same_axis() {
+            (self.rotations[1].1 - self.rotations[0].1).abs()
+        } else {
+            let [q0, q1] = self.quaternions();
+            q0.angle_to(q1)
+        }
+    }
+
+    // how much the pose at time t scales areas compared with the first one, for
+    // motions that scale evenly
+    pub fn area_scale(&self, t: f32) -> f32 {
+        let [s0, s1] = self.scales;
+        let c = 1.0 + t.clamp(0.0, 1.0) * (s1.x / s0.x - 1.0);
+        c * c
+    }
+
+    // whether the end scale is a positive multiple of the start one, so every area
+    // grows by the same factor along the motion
+    pub fn scales_evenly(&self) -> bool {
+        let [s0, s1] = self.scales;
+        let c = s1.x / s0.x;
+        c > 0.0 && (0..3).all(|i| (s1[i] - c * s0[i]).abs() <= 1e-6 * s1[i].abs())
+    }
+
+    fn same_axis(&self) -> bool {
+        let [(mut a, _), (mut b, _)] = self.rotations;
+        a.normalize();
+        b.normalize();
+        a.dot(b) > 1.0 - 1e-6
+    }
+
+    fn quaternions(&self) -> [Quaternion; 2] {
+        self.rotations
+            .map(|(axis, angle)| Quaternion::from_axis_angle(axis, angle))
+    }
+}
+
+// a * b applies b first
+impl ops::Mul<Transform> for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        Transform {
+            m: mul(&self.m, &other.m),
+            m_inv: mul(&other.m_inv, &self.m_inv),
+        }
+    }
+}
+
+fn mul(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut r = [[0.0; 4]; 4];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    r
+}
+
+// Gauss-Jordan elimination with partial pivoting, in f64 to keep projective
+// matrices accurate
+fn invert(m: &[[f32; 4]; 4]) -> Option<[[f32; 4]; 4]> {
+    let mut a = m.map(|row| row.map(|v| v as f64));
+    let mut inv = IDENTITY.map(|row| row.map(|v| v as f64));
+    for col in 0..4 {
+        let pivot = (col..4)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+        let d = a[col][col];
+        for j in 0..4 {
+            a[col][j] /= d;
+            inv[col][j] /= d;
+        }
+        for i in 0..4 {
+            if i != col {
+                let f = a[i][col];
+                for j in 0..4 {
+                    a[i][j] -= f * a[col][j];
+                    inv[i][j] -= f * inv[col][j];
+                }
+            }
+        }
+    }
+    Some(inv.map(|row| row.map(|v| v as f32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vector::{Point, Vector};
+
+    use super::{invert, mul, Transform, IDENTITY};
+
+    fn assert_identity(m: &[[f32; 4]; 4]) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((m[i][j] - IDENTITY[i][j]).abs() < 1e-5, "{:?}", m);
+            }
+        }
+    }
+
+    fn assert_close(a: Point, b: Point) {
+        let d: Vector = (a - b).into();
+        assert!(d.norm() < 1e-4, "{:?} {:?}", a, b);
+    }
+
+    fn skewed() -> Transform {
+        Transform::translate(Vector::new(1.0, -2.0, 3.0))
+            * Transform::rotate(0.9, Vector::new(1.0, 2.0, -0.5))
+            * Transform::scale(2.0, 0.5, 3.0)
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let t = skewed();
+        assert_identity(&(t * t.inverse()).m);
+        assert_identity(&(t.inverse() * t).m);
+        let p = Point::new(0.3, -1.2, 4.0);
+        assert_close(t.inverse().apply_point(t.apply_point(p)), p);
+
+        // new inverts general matrices, projective ones included
+        let general = Transform::new(t.m).unwrap();
+        assert_identity(&mul(&general.m, &general.m_inv));
+        let projective = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, -1.5, -0.5],
+            [0.0, 0.0, -1.0, 0.0],
+        ];
+        assert_identity(&mul(&projective, &invert(&projective).unwrap()));
+        // singular matrices have none
+        let mut flat = IDENTITY;
+        flat[2][2] = 0.0;
+        assert!(Transform::new(flat).is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let t = skewed();
+        let n = Vector::new(1.0, 1.0, 1.0);
+        for tangent in [Vector::new(1.0, -1.0, 0.0), Vector::new(0.0, 1.0, -1.0)] {
+            assert_eq!(n.dot(tangent), 0.0);
+            let (n, tangent) = (t.apply_normal(n), t.apply_vector(tangent));
+            assert!(n.dot(tangent).abs() < 1e-4 * n.norm() * tangent.norm());
+        }
+    }
+
+    #[test]
+    fn mirrors_swap_handedness() {
+        assert!(!Transform::default().swaps_handedness());
+        assert!(!skewed().swaps_handedness());
+        assert!(Transform::scale(-1.0, 1.0, 1.0).swaps_handedness());
+        assert!((skewed() * Transform::scale(1.0, 1.0, -2.0)).swaps_handedness());
+        assert!(!Transform::scale(-1.0, -1.0, 1.0).swaps_handedness());
+    }
+
+    #[test]
+    fn product_applies_the_right_factor_first() {
+        let a = Transform::rotate(0.5, Vector::new(0.0, 0.0, 1.0));
+        let b = Transform::translate(Vector::new(1.0, 0.0, 0.0));
+        let p = Point::new(0.5, 2.0, -1.0);
+        assert_close((a * b).apply_point(p), a.apply_point(b.apply_point(p)));
+        assert_close(
+            (a * b).inverse().apply_point(p),
+            b.inverse().apply_point(a.inverse().apply_point(p)),
+        );
+        // rotations and translations don't commute
+        let d: Vector = ((a * b).apply_point(p) - (b * a).apply_point(p)).into();
+        assert!(d.norm() > 0.1);
+    }
+}
@@ -105,6 +105,19 @@ impl Vector {
     }
 }
 
+impl ops::Index<usize> for Vector {
+    type Output = f32;
+
+    fn index(&self, i: usize) -> &f32 {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("vector index out of range: {}", i),
+        }
+    }
+}
+
 impl ops::Add<Vector> for Vector {
     type Output = Vector;
 
@@ -221,6 +234,19 @@ impl Point {
     }
 }
 
+impl ops::Index<usize> for Point {
+    type Output = f32;
+
+    fn index(&self, i: usize) -> &f32 {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("point index out of range: {}", i),
+        }
+    }
+}
+
 impl Into<Vector> for Point {
     fn into(self) -> Vector {
         Vector {